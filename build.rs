@@ -11,9 +11,7 @@ fn main() -> Result<()> {
     let mut copy_options = CopyOptions::new();
     copy_options.overwrite = true;
 
-    let mut paths_to_copy = Vec::new();
-
-    paths_to_copy.push("assets/");
+    let paths_to_copy = vec!["assets/"];
 
     copy_items(&paths_to_copy, out_dir, &copy_options)?;
 
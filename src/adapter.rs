@@ -0,0 +1,314 @@
+//! Adapter and backend selection, configured through [`AdapterConfig`] or
+//! environment variables
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::EngineError;
+
+/// Comma separated backends to choose from, e.g. `vulkan,gl`
+pub const BACKENDS_ENV: &str = "SIMULATION_ENGINE_BACKENDS";
+/// `low-power` or `high-performance`
+pub const POWER_PREFERENCE_ENV: &str = "SIMULATION_ENGINE_POWER_PREFERENCE";
+/// `1` or `true` to only use a software adapter such as lavapipe or WARP
+pub const FORCE_FALLBACK_ADAPTER_ENV: &str = "SIMULATION_ENGINE_FORCE_FALLBACK_ADAPTER";
+/// Case insensitive substring of the adapter name to use
+pub const ADAPTER_NAME_ENV: &str = "SIMULATION_ENGINE_ADAPTER_NAME";
+
+/// Serializable mirror of a single [`wgpu::Backends`] flag
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Backend {
+    Vulkan,
+    Metal,
+    Dx12,
+    Dx11,
+    Gl,
+    BrowserWebGpu,
+}
+
+impl From<Backend> for wgpu::Backends {
+    fn from(backend: Backend) -> Self {
+        match backend {
+            Backend::Vulkan => wgpu::Backends::VULKAN,
+            Backend::Metal => wgpu::Backends::METAL,
+            Backend::Dx12 => wgpu::Backends::DX12,
+            Backend::Dx11 => wgpu::Backends::DX11,
+            Backend::Gl => wgpu::Backends::GL,
+            Backend::BrowserWebGpu => wgpu::Backends::BROWSER_WEBGPU,
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(backend: &str) -> Result<Self, Self::Err> {
+        match backend.trim().to_lowercase().as_str() {
+            "vulkan" | "vk" => Ok(Backend::Vulkan),
+            "metal" | "mtl" => Ok(Backend::Metal),
+            "dx12" | "d3d12" => Ok(Backend::Dx12),
+            "dx11" | "d3d11" => Ok(Backend::Dx11),
+            "gl" | "opengl" | "gles" => Ok(Backend::Gl),
+            "webgpu" | "browserwebgpu" => Ok(Backend::BrowserWebGpu),
+            _ => Err(format!("unknown backend {:?}", backend)),
+        }
+    }
+}
+
+/// Serializable mirror of [`wgpu::PowerPreference`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PowerPreference {
+    #[default]
+    LowPower,
+    HighPerformance,
+}
+
+impl From<PowerPreference> for wgpu::PowerPreference {
+    fn from(power_preference: PowerPreference) -> Self {
+        match power_preference {
+            PowerPreference::LowPower => wgpu::PowerPreference::LowPower,
+            PowerPreference::HighPerformance => wgpu::PowerPreference::HighPerformance,
+        }
+    }
+}
+
+impl FromStr for PowerPreference {
+    type Err = String;
+
+    fn from_str(power_preference: &str) -> Result<Self, Self::Err> {
+        match power_preference.trim().to_lowercase().as_str() {
+            "low" | "low-power" | "lowpower" => Ok(PowerPreference::LowPower),
+            "high" | "high-performance" | "highperformance" => Ok(PowerPreference::HighPerformance),
+            _ => Err(format!("unknown power preference {:?}", power_preference)),
+        }
+    }
+}
+
+/// Which adapter the engine renders with. The `SIMULATION_ENGINE_*`
+/// environment variables override these when set.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdapterConfig {
+    /// Backends to choose from, all backends when empty
+    pub backends: Vec<Backend>,
+    pub power_preference: PowerPreference,
+    /// Only use a software adapter such as lavapipe or WARP
+    pub force_fallback_adapter: bool,
+    /// Retry with a software adapter when no hardware adapter is found
+    pub allow_fallback_adapter: bool,
+    /// Case insensitive substring of the adapter name to prefer, native only.
+    /// Only software adapters match when forcing the fallback adapter.
+    pub adapter_name: Option<String>,
+}
+
+impl AdapterConfig {
+    pub fn wgpu_backends(&self) -> wgpu::Backends {
+        if self.backends.is_empty() {
+            return wgpu::Backends::all();
+        }
+
+        self.backends
+            .iter()
+            .fold(wgpu::Backends::empty(), |backends, backend| {
+                backends | wgpu::Backends::from(*backend)
+            })
+    }
+
+    /// Applies any `SIMULATION_ENGINE_*` environment variables that are set,
+    /// ignoring ones that fail to parse
+    pub fn with_env_overrides(self) -> Self {
+        self.with_overrides(|name| std::env::var(name).ok())
+    }
+
+    // Applies overrides looked up by environment variable name
+    fn with_overrides(mut self, var: impl Fn(&str) -> Option<String>) -> Self {
+        if let Some(backends) = var(BACKENDS_ENV) {
+            match backends
+                .split(',')
+                .map(Backend::from_str)
+                .collect::<Result<Vec<_>, _>>()
+            {
+                Ok(backends) => self.backends = backends,
+                Err(error) => log::warn!("Ignoring {}: {}", BACKENDS_ENV, error),
+            }
+        }
+
+        if let Some(power_preference) = var(POWER_PREFERENCE_ENV) {
+            match power_preference.parse() {
+                Ok(power_preference) => self.power_preference = power_preference,
+                Err(error) => log::warn!("Ignoring {}: {}", POWER_PREFERENCE_ENV, error),
+            }
+        }
+
+        if let Some(force_fallback_adapter) = var(FORCE_FALLBACK_ADAPTER_ENV) {
+            self.force_fallback_adapter = matches!(
+                force_fallback_adapter.trim().to_lowercase().as_str(),
+                "1" | "true" | "yes"
+            );
+        }
+
+        if let Some(adapter_name) = var(ADAPTER_NAME_ENV) {
+            self.adapter_name = Some(adapter_name);
+        }
+
+        self
+    }
+}
+
+impl Default for AdapterConfig {
+    fn default() -> Self {
+        Self {
+            backends: Vec::new(),
+            power_preference: PowerPreference::default(),
+            force_fallback_adapter: false,
+            allow_fallback_adapter: true,
+            adapter_name: None,
+        }
+    }
+}
+
+/// Lists the adapters available on the given backends
+#[cfg(not(target_arch = "wasm32"))]
+pub fn list_adapters(backends: wgpu::Backends) -> Vec<wgpu::AdapterInfo> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends,
+        dx12_shader_compiler: Default::default(),
+    });
+
+    instance
+        .enumerate_adapters(backends)
+        .map(|adapter| adapter.get_info())
+        .collect()
+}
+
+// Picks an adapter following the config, retrying with the fallback adapter
+// if allowed and nothing else was found
+pub(crate) async fn request_adapter(
+    instance: &wgpu::Instance,
+    config: &AdapterConfig,
+    compatible_surface: Option<&wgpu::Surface>,
+) -> Result<wgpu::Adapter, EngineError> {
+    let backends = config.wgpu_backends();
+
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(adapter_name) = &config.adapter_name {
+        let adapter_name = adapter_name.to_lowercase();
+        let adapter = instance.enumerate_adapters(backends).find(|adapter| {
+            adapter
+                .get_info()
+                .name
+                .to_lowercase()
+                .contains(&adapter_name)
+                && (!config.force_fallback_adapter
+                    || adapter.get_info().device_type == wgpu::DeviceType::Cpu)
+                && compatible_surface.is_none_or(|surface| adapter.is_surface_supported(surface))
+        });
+
+        match adapter {
+            Some(adapter) => return Ok(adapter),
+            None => log::warn!("No adapter named {:?}, picking one instead", adapter_name),
+        }
+    }
+
+    let attempts: &[bool] = if config.force_fallback_adapter {
+        &[true]
+    } else if config.allow_fallback_adapter {
+        &[false, true]
+    } else {
+        &[false]
+    };
+
+    for &force_fallback_adapter in attempts {
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: config.power_preference.into(),
+                compatible_surface,
+                force_fallback_adapter,
+            })
+            .await;
+
+        if let Some(adapter) = adapter {
+            return Ok(adapter);
+        }
+
+        if !force_fallback_adapter && attempts.len() > 1 {
+            log::warn!("No hardware adapter found, trying the fallback adapter");
+        }
+    }
+
+    Err(EngineError::Adapter {
+        backends,
+        force_fallback_adapter: attempts.contains(&true),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn with_vars(vars: &[(&str, &str)]) -> AdapterConfig {
+        let vars = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<HashMap<_, _>>();
+
+        AdapterConfig::default().with_overrides(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn backends_parse_in_any_case() {
+        assert_eq!("Vulkan".parse(), Ok(Backend::Vulkan));
+        assert_eq!(" VK ".parse(), Ok(Backend::Vulkan));
+        assert_eq!("mtl".parse(), Ok(Backend::Metal));
+        assert_eq!("D3D12".parse(), Ok(Backend::Dx12));
+        assert_eq!("dx11".parse(), Ok(Backend::Dx11));
+        assert_eq!("OpenGL".parse(), Ok(Backend::Gl));
+        assert_eq!("WebGPU".parse(), Ok(Backend::BrowserWebGpu));
+        assert!("directx".parse::<Backend>().is_err());
+        assert!("".parse::<Backend>().is_err());
+    }
+
+    #[test]
+    fn power_preferences_parse_in_any_case() {
+        assert_eq!("LOW".parse(), Ok(PowerPreference::LowPower));
+        assert_eq!(
+            "High-Performance".parse(),
+            Ok(PowerPreference::HighPerformance)
+        );
+        assert!("medium".parse::<PowerPreference>().is_err());
+    }
+
+    #[test]
+    fn env_overrides_apply_valid_values() {
+        let config = with_vars(&[
+            (BACKENDS_ENV, "vulkan, GL"),
+            (POWER_PREFERENCE_ENV, "high"),
+            (FORCE_FALLBACK_ADAPTER_ENV, "True"),
+            (ADAPTER_NAME_ENV, "llvmpipe"),
+        ]);
+
+        assert_eq!(config.backends, vec![Backend::Vulkan, Backend::Gl]);
+        assert_eq!(
+            config.wgpu_backends(),
+            wgpu::Backends::VULKAN | wgpu::Backends::GL
+        );
+        assert_eq!(config.power_preference, PowerPreference::HighPerformance);
+        assert!(config.force_fallback_adapter);
+        assert_eq!(config.adapter_name.as_deref(), Some("llvmpipe"));
+    }
+
+    #[test]
+    fn env_overrides_ignore_invalid_values() {
+        let config = with_vars(&[
+            (BACKENDS_ENV, "vulkan,glide"),
+            (POWER_PREFERENCE_ENV, "turbo"),
+            (FORCE_FALLBACK_ADAPTER_ENV, "maybe"),
+        ]);
+
+        assert_eq!(config, AdapterConfig::default());
+        assert_eq!(with_vars(&[]), AdapterConfig::default());
+    }
+}
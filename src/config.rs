@@ -0,0 +1,283 @@
+//! Engine configuration, see [`EngineConfig`]
+
+use serde::{Deserialize, Serialize};
+
+use crate::adapter::AdapterConfig;
+use crate::error::EngineError;
+use crate::physics::PhysicsConfig;
+use crate::random::EngineRng;
+
+// Window defaults
+const WIDTH_DEFAULT: u32 = 450;
+const HEIGHT_DEFAULT: u32 = 400;
+const TITLE_DEFAULT: &str = "Simulation Engine";
+const RESIZABLE_DEFAULT: bool = true;
+
+// Frame rate defaults
+const SHOW_FRAME_RATE_DEFAULT: bool = true;
+const FRAME_BUFFER_LENGTH_DEFAULT: usize = 128;
+
+// Render defaults
+const BACKGROUND_COLOR_DEFAULT: [f64; 4] = [0.1, 0.2, 0.3, 1.0];
+const PRESENT_MODE_DEFAULT: PresentMode = if cfg!(target_arch = "wasm32") {
+    PresentMode::AutoVsync
+} else {
+    PresentMode::AutoNoVsync
+};
+
+// Simulation defaults
+const FIXED_UPDATE_RATE_DEFAULT: f32 = 60.0;
+const MAX_FIXED_STEPS_PER_FRAME_DEFAULT: u32 = 8;
+const INTERPOLATE_DEFAULT: bool = true;
+const DETERMINISTIC_DEFAULT: bool = false;
+
+// Demo defaults
+const LIGHT_ROTATION_PER_SECOND_DEFAULT: f32 = 30.0;
+
+/// Serializable mirror of [`wgpu::PresentMode`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PresentMode {
+    AutoVsync,
+    AutoNoVsync,
+    Fifo,
+    Immediate,
+    Mailbox,
+}
+
+impl From<PresentMode> for wgpu::PresentMode {
+    fn from(present_mode: PresentMode) -> Self {
+        match present_mode {
+            PresentMode::AutoVsync => wgpu::PresentMode::AutoVsync,
+            PresentMode::AutoNoVsync => wgpu::PresentMode::AutoNoVsync,
+            PresentMode::Fifo => wgpu::PresentMode::Fifo,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+        }
+    }
+}
+
+/// Engine configuration, built with the `with_*` methods or loaded from TOML.
+/// Missing keys in a TOML file fall back to the defaults.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EngineConfig {
+    /// Initial logical window width
+    pub width: u32,
+    /// Initial logical window height
+    pub height: u32,
+    pub title: String,
+    pub resizable: bool,
+    /// Whether the frame rate is shown in the title, toggled with F
+    pub show_frame_rate: bool,
+    /// Number of frames the frame rate is averaged over
+    pub frame_buffer_length: usize,
+    /// Clear color as RGBA
+    pub background_color: [f64; 4],
+    pub present_mode: PresentMode,
+    /// Adapter and backend selection, a `[adapter]` table in TOML
+    pub adapter: AdapterConfig,
+    /// Fixed steps per second passed to [`crate::Simulation::fixed_update`]
+    pub fixed_update_rate: f32,
+    /// Steps run in a single frame before the remaining time is dropped
+    pub max_fixed_steps_per_frame: u32,
+    /// Contact and joint solver and sleeping settings, a `[physics]` table in
+    /// TOML
+    pub physics: PhysicsConfig,
+    /// Whether transforms are drawn blended between the last two fixed steps
+    pub interpolate: bool,
+    /// Advances exactly one fixed step of simulation time per frame at a time
+    /// scale of 1, ignoring wall time, so runs with the same seed and input
+    /// reproduce bit for bit
+    pub deterministic: bool,
+    /// Seed for [`crate::random::EngineRng`], taken from the system time when
+    /// unset. Deterministic runs without one use 0.
+    pub seed: Option<u64>,
+    /// Degrees per second the demo light orbits the origin
+    pub light_rotation_per_second: f32,
+}
+
+impl EngineConfig {
+    pub fn new() -> Self {
+        Self {
+            width: WIDTH_DEFAULT,
+            height: HEIGHT_DEFAULT,
+            title: String::from(TITLE_DEFAULT),
+            resizable: RESIZABLE_DEFAULT,
+            show_frame_rate: SHOW_FRAME_RATE_DEFAULT,
+            frame_buffer_length: FRAME_BUFFER_LENGTH_DEFAULT,
+            background_color: BACKGROUND_COLOR_DEFAULT,
+            present_mode: PRESENT_MODE_DEFAULT,
+            adapter: AdapterConfig::default(),
+            fixed_update_rate: FIXED_UPDATE_RATE_DEFAULT,
+            max_fixed_steps_per_frame: MAX_FIXED_STEPS_PER_FRAME_DEFAULT,
+            physics: PhysicsConfig::default(),
+            interpolate: INTERPOLATE_DEFAULT,
+            deterministic: DETERMINISTIC_DEFAULT,
+            seed: None,
+            light_rotation_per_second: LIGHT_ROTATION_PER_SECOND_DEFAULT,
+        }
+    }
+
+    /// Parses a config from a TOML string
+    pub fn from_toml_str(toml_str: &str) -> anyhow::Result<Self> {
+        let config: Self = toml::from_str(toml_str)?;
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks the values the engine can't run with
+    pub fn validate(&self) -> Result<(), EngineError> {
+        if !(self.fixed_update_rate > 0.0 && self.fixed_update_rate.is_finite()) {
+            return Err(EngineError::Config(format!(
+                "fixed_update_rate must be positive, got {}",
+                self.fixed_update_rate
+            )));
+        }
+
+        // No steps per frame would drop all accumulated time every frame
+        if self.max_fixed_steps_per_frame == 0 {
+            return Err(EngineError::Config(String::from(
+                "max_fixed_steps_per_frame must be at least 1",
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Reads and parses a TOML config file
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_toml_file<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<Self> {
+        let toml_str = std::fs::read_to_string(path)?;
+
+        Self::from_toml_str(&toml_str)
+    }
+
+    /// Serializes the config to TOML, useful as a starting point for a file
+    pub fn to_toml_string(&self) -> anyhow::Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.title = String::from(title);
+        self
+    }
+
+    pub fn with_resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    pub fn with_show_frame_rate(mut self, show_frame_rate: bool) -> Self {
+        self.show_frame_rate = show_frame_rate;
+        self
+    }
+
+    pub fn with_frame_buffer_length(mut self, frame_buffer_length: usize) -> Self {
+        self.frame_buffer_length = frame_buffer_length;
+        self
+    }
+
+    pub fn with_background_color(mut self, background_color: [f64; 4]) -> Self {
+        self.background_color = background_color;
+        self
+    }
+
+    pub fn with_present_mode(mut self, present_mode: PresentMode) -> Self {
+        self.present_mode = present_mode;
+        self
+    }
+
+    pub fn with_adapter(mut self, adapter: AdapterConfig) -> Self {
+        self.adapter = adapter;
+        self
+    }
+
+    pub fn with_fixed_update_rate(mut self, fixed_update_rate: f32) -> Self {
+        self.fixed_update_rate = fixed_update_rate;
+        self
+    }
+
+    pub fn with_max_fixed_steps_per_frame(mut self, max_fixed_steps_per_frame: u32) -> Self {
+        self.max_fixed_steps_per_frame = max_fixed_steps_per_frame;
+        self
+    }
+
+    pub fn with_physics(mut self, physics: PhysicsConfig) -> Self {
+        self.physics = physics;
+        self
+    }
+
+    pub fn with_interpolate(mut self, interpolate: bool) -> Self {
+        self.interpolate = interpolate;
+        self
+    }
+
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn with_light_rotation_per_second(mut self, light_rotation_per_second: f32) -> Self {
+        self.light_rotation_per_second = light_rotation_per_second;
+        self
+    }
+
+    pub(crate) fn rng(&self) -> EngineRng {
+        match self.seed {
+            Some(seed) => EngineRng::new(seed),
+            None if self.deterministic => EngineRng::new(0),
+            None => EngineRng::from_entropy(),
+        }
+    }
+
+    pub(crate) fn background_color(&self) -> wgpu::Color {
+        let [r, g, b, a] = self.background_color;
+
+        wgpu::Color { r, g, b, a }
+    }
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn non_positive_fixed_update_rate_is_rejected() {
+        for rate in [0.0, -60.0, f32::NAN] {
+            let config = EngineConfig::new().with_fixed_update_rate(rate);
+
+            assert!(matches!(config.validate(), Err(EngineError::Config(_))));
+        }
+
+        assert!(EngineConfig::from_toml_str("fixed_update_rate = 0.0").is_err());
+        assert!(EngineConfig::from_toml_str("fixed_update_rate = 30.0").is_ok());
+        assert!(EngineConfig::new().validate().is_ok());
+    }
+
+    #[test]
+    fn zero_max_fixed_steps_per_frame_is_rejected() {
+        let config = EngineConfig::new().with_max_fixed_steps_per_frame(0);
+
+        assert!(matches!(config.validate(), Err(EngineError::Config(_))));
+        assert!(EngineConfig::from_toml_str("max_fixed_steps_per_frame = 0").is_err());
+        assert!(EngineConfig::from_toml_str("max_fixed_steps_per_frame = 1").is_ok());
+    }
+}
@@ -0,0 +1,54 @@
+use cgmath::prelude::*;
+use cgmath::Vector3;
+
+use crate::hash::{StateHash, StateHasher};
+use crate::model::instance::Instance;
+
+/// Placement of an entity, entities with one are drawn by the context
+pub type Transform = Instance;
+
+/// Draws the entity's model with this index into the model's materials for
+/// every mesh, instead of each mesh's own material
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Material(pub usize);
+
+/// Linear velocity in units per second and angular velocity in radians per
+/// second about each axis. Entities in the context's world move by it every
+/// fixed step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Velocity {
+    pub linear: Vector3<f32>,
+    pub angular: Vector3<f32>,
+}
+
+impl Velocity {
+    pub fn new(linear: Vector3<f32>, angular: Vector3<f32>) -> Self {
+        Self { linear, angular }
+    }
+}
+
+impl Default for Velocity {
+    fn default() -> Self {
+        Self {
+            linear: Vector3::zero(),
+            angular: Vector3::zero(),
+        }
+    }
+}
+
+impl StateHash for Velocity {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        self.linear.hash_state(hasher);
+        self.angular.hash_state(hasher);
+    }
+}
+
+/// Mass in kilograms
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mass(pub f32);
+
+impl StateHash for Mass {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        self.0.hash_state(hasher);
+    }
+}
@@ -0,0 +1,332 @@
+//! Lightweight entity–component store for simulation objects, see [`World`]
+
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::hash::Hasher;
+
+use cgmath::prelude::*;
+use cgmath::{Quaternion, Rad};
+
+use crate::hash::{StateHash, StateHasher};
+
+mod components;
+mod storage;
+
+pub use crate::model::ModelHandle;
+pub use components::{Mass, Material, Transform, Velocity};
+use storage::{AnyStorage, Storage};
+
+/// Handle to an entity in a [`World`], invalidated when the entity is despawned
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+/// Entities and their components. Any `'static` type can be used as a
+/// component, an entity holds at most one of each type.
+#[derive(Default)]
+pub struct World {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free_indices: Vec<u32>,
+    storages: HashMap<TypeId, Box<dyn AnyStorage>>,
+    len: usize,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of living entities
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn spawn(&mut self) -> Entity {
+        self.len += 1;
+
+        match self.free_indices.pop() {
+            Some(index) => {
+                self.alive[index as usize] = true;
+
+                Entity {
+                    index,
+                    generation: self.generations[index as usize],
+                }
+            }
+            None => {
+                self.generations.push(0);
+                self.alive.push(true);
+
+                Entity {
+                    index: self.generations.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    /// Removes the entity and all of its components, returning false if it
+    /// was already despawned
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+
+        for storage in self.storages.values_mut() {
+            storage.remove_entity(entity);
+        }
+
+        let index = entity.index as usize;
+
+        self.alive[index] = false;
+        self.generations[index] = self.generations[index].wrapping_add(1);
+        self.free_indices.push(entity.index);
+        self.len -= 1;
+
+        true
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        let index = entity.index as usize;
+
+        self.alive.get(index).copied().unwrap_or(false)
+            && self.generations[index] == entity.generation
+    }
+
+    fn storage<T: 'static>(&self) -> Option<&Storage<T>> {
+        self.storages
+            .get(&TypeId::of::<T>())
+            .map(|storage| storage.as_any().downcast_ref().unwrap())
+    }
+
+    fn storage_mut<T: 'static>(&mut self) -> Option<&mut Storage<T>> {
+        self.storages
+            .get_mut(&TypeId::of::<T>())
+            .map(|storage| storage.as_any_mut().downcast_mut().unwrap())
+    }
+
+    /// Adds or replaces the entity's component of type `T`, returning the old
+    /// one. Panics if the entity has been despawned.
+    pub fn insert<T: 'static>(&mut self, entity: Entity, component: T) -> Option<T> {
+        assert!(self.is_alive(entity), "Entity has been despawned");
+
+        self.storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Storage::<T>::new()))
+            .as_any_mut()
+            .downcast_mut::<Storage<T>>()
+            .unwrap()
+            .insert(entity, component)
+    }
+
+    pub fn remove<T: 'static>(&mut self, entity: Entity) -> Option<T> {
+        self.storage_mut::<T>()?.remove(entity)
+    }
+
+    pub fn get<T: 'static>(&self, entity: Entity) -> Option<&T> {
+        self.storage::<T>()?.get(entity)
+    }
+
+    pub fn get_mut<T: 'static>(&mut self, entity: Entity) -> Option<&mut T> {
+        self.storage_mut::<T>()?.get_mut(entity)
+    }
+
+    pub fn has<T: 'static>(&self, entity: Entity) -> bool {
+        self.get::<T>(entity).is_some()
+    }
+
+    /// Every entity with a `T`, in no particular order
+    pub fn query<T: 'static>(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.storage::<T>().into_iter().flat_map(Storage::iter)
+    }
+
+    pub fn query_mut<T: 'static>(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.storage_mut::<T>()
+            .into_iter()
+            .flat_map(Storage::iter_mut)
+    }
+
+    /// Every entity with both an `A` and a `B`
+    pub fn query2<A: 'static, B: 'static>(&self) -> impl Iterator<Item = (Entity, &A, &B)> {
+        let a = self.storage::<A>();
+        let b = self.storage::<B>();
+
+        a.zip(b).into_iter().flat_map(|(a, b)| {
+            a.iter()
+                .filter_map(move |(entity, a)| b.get(entity).map(|b| (entity, a, b)))
+        })
+    }
+
+    /// Every entity with both an `A` and a `B`, with `A` mutable. Panics if
+    /// `A` and `B` are the same type.
+    pub fn query2_mut<A: 'static, B: 'static>(
+        &mut self,
+    ) -> impl Iterator<Item = (Entity, &mut A, &B)> {
+        assert_ne!(
+            TypeId::of::<A>(),
+            TypeId::of::<B>(),
+            "Query components must be different types"
+        );
+
+        let [a, b] = self
+            .storages
+            .get_disjoint_mut([&TypeId::of::<A>(), &TypeId::of::<B>()]);
+
+        let a = a.map(|a| a.as_any_mut().downcast_mut::<Storage<A>>().unwrap());
+        let b = b.map(|b| b.as_any().downcast_ref::<Storage<B>>().unwrap());
+
+        a.zip(b).into_iter().flat_map(|(a, b)| {
+            a.iter_mut()
+                .filter_map(move |(entity, a)| b.get(entity).map(|b| (entity, a, b)))
+        })
+    }
+
+    /// Number of entities with a `T`
+    pub fn count<T: 'static>(&self) -> usize {
+        self.storage::<T>().map_or(0, Storage::len)
+    }
+
+    /// Adds every `T` and the entity holding it to the hash, in storage order
+    pub fn hash_components<T: StateHash + 'static>(&self, hasher: &mut StateHasher) {
+        hasher.write_usize(self.count::<T>());
+
+        for (entity, component) in self.query::<T>() {
+            entity.hash_state(hasher);
+            component.hash_state(hasher);
+        }
+    }
+}
+
+impl StateHash for Entity {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u32(self.index);
+        hasher.write_u32(self.generation);
+    }
+}
+
+/// Moves every entity with a [`Transform`] and [`Velocity`] by its velocity
+/// over `dt` seconds. The context runs it every fixed step, after the forces
+/// and [`crate::Simulation::fixed_update`], so only worlds outside a context
+/// need to call it.
+pub fn apply_velocity(world: &mut World, dt: f32) {
+    for (_, transform, velocity) in world.query2_mut::<Transform, Velocity>() {
+        transform.set_position(transform.position() + velocity.linear * dt);
+
+        let angle = velocity.angular.magnitude() * dt;
+
+        if angle > 0.0 {
+            let rotation = Quaternion::from_axis_angle(velocity.angular.normalize(), Rad(angle));
+
+            transform.set_rotation((rotation * transform.rotation()).normalize());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Vector3;
+
+    use super::*;
+
+    #[test]
+    fn despawned_handles_go_stale() {
+        let mut world = World::new();
+        let old = world.spawn();
+
+        world.insert(old, Mass(1.0));
+        assert!(world.despawn(old));
+        assert!(!world.despawn(old));
+
+        // The index is reused under a new generation
+        let new = world.spawn();
+
+        assert_ne!(old, new);
+        assert!(!world.is_alive(old));
+        assert!(world.is_alive(new));
+        assert_eq!(world.get::<Mass>(old), None);
+        assert_eq!(world.get::<Mass>(new), None);
+        assert_eq!(world.remove::<Mass>(old), None);
+        assert_eq!(world.len(), 1);
+        assert_eq!(world.count::<Mass>(), 0);
+    }
+
+    #[test]
+    fn components_follow_their_entity_after_despawn() {
+        let mut world = World::new();
+        let entities = (0..3).map(|_| world.spawn()).collect::<Vec<_>>();
+
+        for (index, entity) in entities.iter().enumerate() {
+            world.insert(*entity, Mass(index as f32));
+        }
+
+        world.despawn(entities[0]);
+
+        assert_eq!(world.get::<Mass>(entities[1]), Some(&Mass(1.0)));
+        assert_eq!(world.get::<Mass>(entities[2]), Some(&Mass(2.0)));
+        assert_eq!(world.insert(entities[2], Mass(4.0)), Some(Mass(2.0)));
+        assert_eq!(world.remove::<Mass>(entities[1]), Some(Mass(1.0)));
+        assert!(!world.has::<Mass>(entities[1]));
+        assert_eq!(world.get::<Mass>(entities[2]), Some(&Mass(4.0)));
+    }
+
+    #[test]
+    fn query2_mut_yields_entities_with_both_components() {
+        let mut world = World::new();
+        let both = world.spawn();
+        let mass_only = world.spawn();
+        let velocity_only = world.spawn();
+        let removed = world.spawn();
+
+        world.insert(both, Mass(1.0));
+        world.insert(both, Velocity::default());
+        world.insert(mass_only, Mass(2.0));
+        world.insert(velocity_only, Velocity::default());
+        world.insert(removed, Mass(3.0));
+        world.insert(removed, Velocity::default());
+        world.remove::<Velocity>(removed);
+
+        for (_, mass, _) in world.query2_mut::<Mass, Velocity>() {
+            mass.0 *= 10.0;
+        }
+
+        let entities = world
+            .query2::<Mass, Velocity>()
+            .map(|(entity, _, _)| entity)
+            .collect::<Vec<_>>();
+
+        assert_eq!(entities, [both]);
+        assert_eq!(world.get::<Mass>(both), Some(&Mass(10.0)));
+        assert_eq!(world.get::<Mass>(mass_only), Some(&Mass(2.0)));
+        assert_eq!(world.get::<Mass>(removed), Some(&Mass(3.0)));
+        assert_eq!(world.query2_mut::<Mass, Transform>().count(), 0);
+    }
+
+    #[test]
+    fn apply_velocity_moves_transforms() {
+        let mut world = World::new();
+        let moving = world.spawn();
+        let still = world.spawn();
+
+        world.insert(moving, Transform::default());
+        world.insert(moving, Velocity::new(Vector3::unit_x(), Vector3::zero()));
+        world.insert(still, Transform::default());
+
+        apply_velocity(&mut world, 0.5);
+
+        assert_eq!(
+            world.get::<Transform>(moving).unwrap().position(),
+            Vector3::new(0.5, 0.0, 0.0)
+        );
+        assert_eq!(
+            world.get::<Transform>(still).unwrap().position(),
+            Vector3::zero()
+        );
+    }
+}
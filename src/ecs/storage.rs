@@ -0,0 +1,165 @@
+use std::any::Any;
+
+use crate::ecs::Entity;
+
+// Type erased storage so the world can remove an entity's components
+// without knowing their types
+pub(crate) trait AnyStorage {
+    fn remove_entity(&mut self, entity: Entity);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+// Sparse set, components are packed densely for fast iteration and looked up
+// through the entity index
+pub(crate) struct Storage<T> {
+    components: Vec<T>,
+    entities: Vec<Entity>,
+    sparse: Vec<Option<usize>>,
+}
+
+impl<T> Storage<T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            components: Vec::new(),
+            entities: Vec::new(),
+            sparse: Vec::new(),
+        }
+    }
+
+    fn dense_index(&self, entity: Entity) -> Option<usize> {
+        let dense_index = (*self.sparse.get(entity.index as usize)?)?;
+
+        (self.entities[dense_index] == entity).then_some(dense_index)
+    }
+
+    pub(crate) fn insert(&mut self, entity: Entity, component: T) -> Option<T> {
+        if let Some(dense_index) = self.dense_index(entity) {
+            return Some(std::mem::replace(
+                &mut self.components[dense_index],
+                component,
+            ));
+        }
+
+        let index = entity.index as usize;
+
+        if index >= self.sparse.len() {
+            self.sparse.resize(index + 1, None);
+        }
+
+        self.sparse[index] = Some(self.components.len());
+        self.components.push(component);
+        self.entities.push(entity);
+
+        None
+    }
+
+    pub(crate) fn remove(&mut self, entity: Entity) -> Option<T> {
+        let dense_index = self.dense_index(entity)?;
+
+        self.sparse[entity.index as usize] = None;
+        self.entities.swap_remove(dense_index);
+
+        // Point the entity moved into the hole at its new position
+        if let Some(moved) = self.entities.get(dense_index) {
+            self.sparse[moved.index as usize] = Some(dense_index);
+        }
+
+        Some(self.components.swap_remove(dense_index))
+    }
+
+    pub(crate) fn get(&self, entity: Entity) -> Option<&T> {
+        self.dense_index(entity)
+            .map(|dense_index| &self.components[dense_index])
+    }
+
+    pub(crate) fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        self.dense_index(entity)
+            .map(|dense_index| &mut self.components[dense_index])
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.components.len()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.entities.iter().copied().zip(self.components.iter())
+    }
+
+    pub(crate) fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.entities
+            .iter()
+            .copied()
+            .zip(self.components.iter_mut())
+    }
+}
+
+impl<T: 'static> AnyStorage for Storage<T> {
+    fn remove_entity(&mut self, entity: Entity) {
+        self.remove(entity);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::World;
+
+    #[test]
+    fn lookups_survive_swap_remove() {
+        let mut world = World::new();
+        let entities = (0..4).map(|_| world.spawn()).collect::<Vec<_>>();
+        let mut storage = Storage::new();
+
+        for (value, entity) in entities.iter().enumerate() {
+            assert_eq!(storage.insert(*entity, value), None);
+        }
+
+        // The last component moves into the removed one's slot
+        assert_eq!(storage.remove(entities[1]), Some(1));
+        assert_eq!(storage.remove(entities[1]), None);
+        assert_eq!(storage.len(), 3);
+        assert_eq!(storage.get(entities[0]), Some(&0));
+        assert_eq!(storage.get(entities[1]), None);
+        assert_eq!(storage.get(entities[2]), Some(&2));
+        assert_eq!(storage.get(entities[3]), Some(&3));
+
+        *storage.get_mut(entities[3]).unwrap() = 30;
+        assert_eq!(storage.insert(entities[2], 20), Some(2));
+        assert_eq!(storage.insert(entities[1], 10), None);
+
+        let mut components = storage.iter().map(|(_, value)| *value).collect::<Vec<_>>();
+        components.sort_unstable();
+        assert_eq!(components, [0, 10, 20, 30]);
+
+        // Removing the last component has nothing to move
+        assert_eq!(storage.remove(entities[1]), Some(10));
+        assert_eq!(storage.get(entities[3]), Some(&30));
+    }
+
+    #[test]
+    fn stale_entities_miss_reused_indices() {
+        let mut world = World::new();
+        let old = world.spawn();
+        let mut storage = Storage::new();
+
+        storage.insert(old, "old");
+        storage.remove(old);
+        world.despawn(old);
+
+        let new = world.spawn();
+        storage.insert(new, "new");
+
+        assert_eq!(storage.get(old), None);
+        assert_eq!(storage.remove(old), None);
+        assert_eq!(storage.get(new), Some(&"new"));
+    }
+}
@@ -0,0 +1,43 @@
+//! Errors returned while setting up the engine
+
+use thiserror::Error;
+
+/// Failure while creating the window, GPU resources or loading assets
+#[derive(Debug, Error)]
+pub enum EngineError {
+    #[error("invalid config: {0}")]
+    Config(String),
+
+    #[error("failed to create window: {0}")]
+    Window(#[from] winit::error::OsError),
+
+    #[error("failed to create surface: {0}")]
+    Surface(#[from] wgpu::CreateSurfaceError),
+
+    #[error("no adapter found for backends {backends:?} (force fallback adapter: {force_fallback_adapter})")]
+    Adapter {
+        backends: wgpu::Backends,
+        force_fallback_adapter: bool,
+    },
+
+    #[error("failed to request device from adapter {adapter:?}: {source}")]
+    Device {
+        adapter: String,
+        source: wgpu::RequestDeviceError,
+    },
+
+    #[error("adapter does not support {feature}")]
+    Unsupported { feature: String },
+
+    #[error("failed to create pipeline for shader {label:?}: {message}")]
+    Shader { label: String, message: String },
+
+    #[error("failed to read back rendered frame: {0}")]
+    Readback(#[from] wgpu::BufferAsyncError),
+
+    #[error("failed to load asset {file_name:?}: {source}")]
+    Asset {
+        file_name: String,
+        source: anyhow::Error,
+    },
+}
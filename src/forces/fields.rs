@@ -0,0 +1,306 @@
+use cgmath::prelude::*;
+use cgmath::Vector3;
+
+use super::{ForceField, PointMass};
+
+// Gravity default, in units per second squared
+const GRAVITY_DEFAULT: Vector3<f32> = Vector3::new(0.0, -9.81, 0.0);
+
+// Distance within which inverse square falloff stops growing
+const SOFTENING_DEFAULT: f32 = 0.1;
+
+/// Uniform acceleration, the same for every mass
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Gravity {
+    pub acceleration: Vector3<f32>,
+}
+
+impl Gravity {
+    pub fn new(acceleration: Vector3<f32>) -> Self {
+        Self { acceleration }
+    }
+}
+
+impl Default for Gravity {
+    fn default() -> Self {
+        Self::new(GRAVITY_DEFAULT)
+    }
+}
+
+impl ForceField for Gravity {
+    fn force(&self, point: &PointMass) -> Vector3<f32> {
+        self.acceleration * point.mass
+    }
+}
+
+/// Resistance against the velocity, `linear * speed + quadratic * speed^2`
+/// in magnitude
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Drag {
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Drag {
+    pub fn new(linear: f32, quadratic: f32) -> Self {
+        Self { linear, quadratic }
+    }
+}
+
+impl ForceField for Drag {
+    fn force(&self, point: &PointMass) -> Vector3<f32> {
+        let speed = point.velocity.magnitude();
+
+        -point.velocity * (self.linear + self.quadratic * speed)
+    }
+}
+
+/// How a field's strength changes with distance from its centre
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Falloff {
+    /// Full strength out to the radius
+    Constant,
+    /// Fades linearly to nothing at the radius
+    Linear,
+    /// Strength over distance squared, like gravity
+    InverseSquare,
+}
+
+impl Falloff {
+    /// Scale of a field's strength at `distance`, 0 beyond `radius`. Inverse
+    /// square falloff treats distances below `softening` as `softening`.
+    pub fn factor(self, distance: f32, radius: f32, softening: f32) -> f32 {
+        if distance > radius {
+            return 0.0;
+        }
+
+        match self {
+            Falloff::Constant => 1.0,
+            Falloff::Linear if radius.is_finite() => 1.0 - distance / radius,
+            Falloff::Linear => 1.0,
+            Falloff::InverseSquare => 1.0 / distance.max(softening).powi(2),
+        }
+    }
+}
+
+/// Pulls towards a point, or pushes away with a negative strength
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Attractor {
+    pub position: Vector3<f32>,
+    /// Force at unit distance with inverse square falloff, otherwise at the
+    /// centre
+    pub strength: f32,
+    pub falloff: Falloff,
+    /// Distance beyond which the attractor has no effect
+    pub radius: f32,
+    pub softening: f32,
+}
+
+impl Attractor {
+    /// Inverse square attractor reaching any distance
+    pub fn new(position: Vector3<f32>, strength: f32) -> Self {
+        Self {
+            position,
+            strength,
+            falloff: Falloff::InverseSquare,
+            radius: f32::INFINITY,
+            softening: SOFTENING_DEFAULT,
+        }
+    }
+
+    /// Attractor pushing away with `strength`
+    pub fn repulsor(position: Vector3<f32>, strength: f32) -> Self {
+        Self::new(position, -strength)
+    }
+
+    pub fn with_falloff(mut self, falloff: Falloff) -> Self {
+        self.falloff = falloff;
+        self
+    }
+
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    pub fn with_softening(mut self, softening: f32) -> Self {
+        self.softening = softening;
+        self
+    }
+}
+
+impl ForceField for Attractor {
+    fn force(&self, point: &PointMass) -> Vector3<f32> {
+        let offset = self.position - point.position;
+        let distance = offset.magnitude();
+
+        if distance <= f32::EPSILON {
+            return Vector3::zero();
+        }
+
+        offset / distance
+            * self.strength
+            * self.falloff.factor(distance, self.radius, self.softening)
+    }
+}
+
+/// Swirls around an axis through a point, turning right handed about the
+/// axis for a positive strength
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vortex {
+    pub center: Vector3<f32>,
+    /// Direction of the axis, of any length
+    pub axis: Vector3<f32>,
+    /// Force at unit distance from the axis with inverse square falloff,
+    /// otherwise on the axis
+    pub strength: f32,
+    pub falloff: Falloff,
+    /// Distance from the axis beyond which the vortex has no effect
+    pub radius: f32,
+    pub softening: f32,
+}
+
+impl Vortex {
+    /// Vortex of constant strength out to `radius`
+    pub fn new(center: Vector3<f32>, axis: Vector3<f32>, strength: f32, radius: f32) -> Self {
+        Self {
+            center,
+            axis,
+            strength,
+            falloff: Falloff::Constant,
+            radius,
+            softening: SOFTENING_DEFAULT,
+        }
+    }
+
+    pub fn with_falloff(mut self, falloff: Falloff) -> Self {
+        self.falloff = falloff;
+        self
+    }
+
+    pub fn with_softening(mut self, softening: f32) -> Self {
+        self.softening = softening;
+        self
+    }
+
+    // The axis normalised, zero when it has no direction
+    pub(crate) fn unit_axis(&self) -> Vector3<f32> {
+        let length = self.axis.magnitude();
+
+        if length <= f32::EPSILON {
+            return Vector3::zero();
+        }
+
+        self.axis / length
+    }
+}
+
+impl ForceField for Vortex {
+    fn force(&self, point: &PointMass) -> Vector3<f32> {
+        let axis = self.unit_axis();
+        let offset = point.position - self.center;
+        let radial = offset - axis * offset.dot(axis);
+        let distance = radial.magnitude();
+
+        if distance <= f32::EPSILON {
+            return Vector3::zero();
+        }
+
+        axis.cross(radial / distance)
+            * self.strength
+            * self.falloff.factor(distance, self.radius, self.softening)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(position: Vector3<f32>) -> PointMass {
+        PointMass {
+            position,
+            velocity: Vector3::zero(),
+            mass: 2.0,
+        }
+    }
+
+    fn assert_near(a: Vector3<f32>, b: Vector3<f32>) {
+        assert!((a - b).magnitude() < 1e-5, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn falloff_factors() {
+        assert_eq!(Falloff::Constant.factor(3.0, 4.0, 0.1), 1.0);
+        assert_eq!(Falloff::Linear.factor(3.0, 4.0, 0.1), 0.25);
+        assert_eq!(Falloff::Linear.factor(3.0, f32::INFINITY, 0.1), 1.0);
+        assert_eq!(Falloff::InverseSquare.factor(2.0, 4.0, 0.1), 0.25);
+
+        // Nothing beyond the radius
+        for falloff in [Falloff::Constant, Falloff::Linear, Falloff::InverseSquare] {
+            assert_eq!(falloff.factor(4.5, 4.0, 0.1), 0.0);
+        }
+
+        // Softening caps inverse square close in
+        assert_eq!(Falloff::InverseSquare.factor(0.1, 4.0, 0.5), 4.0);
+        assert_eq!(Falloff::InverseSquare.factor(0.0, 4.0, 0.5), 4.0);
+    }
+
+    #[test]
+    fn attractors_pull_and_repulsors_push() {
+        let center = Vector3::new(1.0, 0.0, 0.0);
+        let point = at(Vector3::new(1.0, 2.0, 0.0));
+
+        assert_near(
+            Attractor::new(center, 8.0).force(&point),
+            Vector3::new(0.0, -2.0, 0.0),
+        );
+        assert_near(
+            Attractor::repulsor(center, 8.0).force(&point),
+            Vector3::new(0.0, 2.0, 0.0),
+        );
+        assert_near(
+            Attractor::new(center, 8.0)
+                .with_falloff(Falloff::Constant)
+                .with_radius(1.0)
+                .force(&point),
+            Vector3::zero(),
+        );
+        assert_near(
+            Attractor::new(center, 8.0).force(&at(center)),
+            Vector3::zero(),
+        );
+    }
+
+    #[test]
+    fn vortices_turn_right_handed_about_their_axis() {
+        let vortex = Vortex::new(Vector3::zero(), Vector3::unit_y(), 3.0, 10.0);
+
+        // Height along the axis doesn't matter
+        assert_near(
+            vortex.force(&at(Vector3::new(2.0, 5.0, 0.0))),
+            Vector3::new(0.0, 0.0, -3.0),
+        );
+        assert_near(
+            vortex.force(&at(Vector3::new(0.0, 0.0, 2.0))),
+            Vector3::new(3.0, 0.0, 0.0),
+        );
+        assert_near(vortex.force(&at(Vector3::unit_y())), Vector3::zero());
+        assert_near(
+            vortex.force(&at(Vector3::new(11.0, 0.0, 0.0))),
+            Vector3::zero(),
+        );
+    }
+
+    #[test]
+    fn vortex_axis_length_does_not_scale_the_force() {
+        let mut vortex = Vortex::new(Vector3::zero(), Vector3::unit_y(), 3.0, 10.0);
+        let point = at(Vector3::new(1.0, 1.0, 1.0));
+        let unit = vortex.force(&point);
+
+        vortex.axis = Vector3::new(0.0, 5.0, 0.0);
+        assert_near(vortex.force(&point), unit);
+
+        vortex.axis = Vector3::zero();
+        assert_near(vortex.force(&point), Vector3::zero());
+    }
+}
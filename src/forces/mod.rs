@@ -0,0 +1,449 @@
+//! Composable force generators acting on entities, rigid bodies and
+//! particles, see [`Forces`]
+
+use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet};
+
+use cgmath::prelude::*;
+use cgmath::Vector3;
+
+use crate::ecs::{Entity, Mass, Transform, Velocity, World};
+use crate::particles::EmitterId;
+use crate::physics::{BodyId, PhysicsWorld};
+
+mod fields;
+mod spring;
+
+pub use fields::{Attractor, Drag, Falloff, Gravity, Vortex};
+pub use spring::Spring;
+
+/// Position, velocity and mass of whatever a force acts on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PointMass {
+    pub position: Vector3<f32>,
+    pub velocity: Vector3<f32>,
+    pub mass: f32,
+}
+
+/// A force depending only on the state of what it acts on. Closures taking
+/// a [`PointMass`] are fields too.
+pub trait ForceField: Any {
+    fn force(&self, point: &PointMass) -> Vector3<f32>;
+}
+
+impl<F: Fn(&PointMass) -> Vector3<f32> + 'static> ForceField for F {
+    fn force(&self, point: &PointMass) -> Vector3<f32> {
+        self(point)
+    }
+}
+
+/// Something a force acts on. Entities need a [`Transform`] and a
+/// [`Velocity`] and weigh their [`Mass`], or 1 without one. Fields on an
+/// emitter act on each of its particles as the particles are stepped, and
+/// springs ignore emitters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ForceTarget {
+    Entity(Entity),
+    Body(BodyId),
+    Emitter(EmitterId),
+}
+
+impl From<Entity> for ForceTarget {
+    fn from(entity: Entity) -> Self {
+        ForceTarget::Entity(entity)
+    }
+}
+
+impl From<BodyId> for ForceTarget {
+    fn from(body: BodyId) -> Self {
+        ForceTarget::Body(body)
+    }
+}
+
+impl From<EmitterId> for ForceTarget {
+    fn from(emitter: EmitterId) -> Self {
+        ForceTarget::Emitter(emitter)
+    }
+}
+
+/// Handle to a generator in [`Forces`], invalidated when it's removed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ForceId {
+    index: u32,
+    generation: u32,
+}
+
+enum Generator {
+    Field {
+        field: Box<dyn ForceField>,
+        targets: BTreeSet<ForceTarget>,
+    },
+    Spring(Spring),
+}
+
+struct Slot {
+    generation: u32,
+    generator: Option<Generator>,
+}
+
+/// Force generators and the targets each acts on, applied by the context at
+/// the start of every fixed step, before [`crate::Simulation::fixed_update`].
+/// Bodies take the forces into the physics step, entities' velocities change
+/// straight away and particles feel them when they're stepped after the
+/// physics. Targets that no longer exist are skipped, and sleeping bodies
+/// ignore forces until something wakes them.
+#[derive(Default)]
+pub struct Forces {
+    slots: Vec<Slot>,
+    free_indices: Vec<u32>,
+    len: usize,
+}
+
+impl Forces {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn insert(&mut self, generator: Generator) -> ForceId {
+        self.len += 1;
+
+        match self.free_indices.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.generator = Some(generator);
+
+                ForceId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    generator: Some(generator),
+                });
+
+                ForceId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    /// Adds a field acting on each of `targets`
+    pub fn add_field<T: Into<ForceTarget>>(
+        &mut self,
+        field: impl ForceField,
+        targets: impl IntoIterator<Item = T>,
+    ) -> ForceId {
+        self.insert(Generator::Field {
+            field: Box::new(field),
+            targets: targets.into_iter().map(Into::into).collect(),
+        })
+    }
+
+    pub fn add_spring(&mut self, spring: Spring) -> ForceId {
+        self.insert(Generator::Spring(spring))
+    }
+
+    /// Removes a generator, returning false if it was already removed
+    pub fn remove(&mut self, id: ForceId) -> bool {
+        let Some(slot) = self
+            .slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation && slot.generator.is_some())
+        else {
+            return false;
+        };
+
+        slot.generator = None;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_indices.push(id.index);
+        self.len -= 1;
+
+        true
+    }
+
+    pub fn contains(&self, id: ForceId) -> bool {
+        self.generator(id).is_some()
+    }
+
+    fn generator(&self, id: ForceId) -> Option<&Generator> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.generator.as_ref())
+    }
+
+    fn generator_mut(&mut self, id: ForceId) -> Option<&mut Generator> {
+        self.slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.generator.as_mut())
+    }
+
+    /// The field if it's a `T`, e.g. to move an attractor
+    pub fn field<T: ForceField>(&self, id: ForceId) -> Option<&T> {
+        match self.generator(id)? {
+            Generator::Field { field, .. } => (field.as_ref() as &dyn Any).downcast_ref(),
+            Generator::Spring(_) => None,
+        }
+    }
+
+    pub fn field_mut<T: ForceField>(&mut self, id: ForceId) -> Option<&mut T> {
+        match self.generator_mut(id)? {
+            Generator::Field { field, .. } => (field.as_mut() as &mut dyn Any).downcast_mut(),
+            Generator::Spring(_) => None,
+        }
+    }
+
+    pub fn spring(&self, id: ForceId) -> Option<&Spring> {
+        match self.generator(id)? {
+            Generator::Spring(spring) => Some(spring),
+            Generator::Field { .. } => None,
+        }
+    }
+
+    pub fn spring_mut(&mut self, id: ForceId) -> Option<&mut Spring> {
+        match self.generator_mut(id)? {
+            Generator::Spring(spring) => Some(spring),
+            Generator::Field { .. } => None,
+        }
+    }
+
+    /// Adds a target to a field, returning false if `id` isn't a field or
+    /// already acts on it
+    pub fn attach(&mut self, id: ForceId, target: impl Into<ForceTarget>) -> bool {
+        match self.generator_mut(id) {
+            Some(Generator::Field { targets, .. }) => targets.insert(target.into()),
+            _ => false,
+        }
+    }
+
+    /// Removes a target from a field, returning false if it didn't act on it
+    pub fn detach(&mut self, id: ForceId, target: impl Into<ForceTarget>) -> bool {
+        match self.generator_mut(id) {
+            Some(Generator::Field { targets, .. }) => targets.remove(&target.into()),
+            _ => false,
+        }
+    }
+
+    /// Targets of a field, or a spring's two ends
+    pub fn targets(&self, id: ForceId) -> Vec<ForceTarget> {
+        match self.generator(id) {
+            Some(Generator::Field { targets, .. }) => targets.iter().copied().collect(),
+            Some(Generator::Spring(spring)) => vec![spring.a, spring.b],
+            None => Vec::new(),
+        }
+    }
+
+    /// Every field acting on `target`
+    pub fn fields_acting_on(&self, target: ForceTarget) -> impl Iterator<Item = &dyn ForceField> {
+        self.slots
+            .iter()
+            .filter_map(move |slot| match slot.generator.as_ref()? {
+                Generator::Field { field, targets } if targets.contains(&target) => {
+                    Some(field.as_ref())
+                }
+                _ => None,
+            })
+    }
+
+    /// Total force from every generator on each entity and body that exists
+    pub fn forces(
+        &self,
+        world: &World,
+        physics: &PhysicsWorld,
+    ) -> BTreeMap<ForceTarget, Vector3<f32>> {
+        let mut forces = BTreeMap::new();
+        let mut add = |target, force| {
+            *forces.entry(target).or_insert_with(Vector3::zero) += force;
+        };
+
+        for generator in self.slots.iter().filter_map(|slot| slot.generator.as_ref()) {
+            match generator {
+                Generator::Field { field, targets } => {
+                    for &target in targets {
+                        if let Some(point) = point_mass(target, world, physics) {
+                            add(target, field.force(&point));
+                        }
+                    }
+                }
+                Generator::Spring(spring) => {
+                    let (Some(a), Some(b)) = (
+                        point_mass(spring.a, world, physics),
+                        point_mass(spring.b, world, physics),
+                    ) else {
+                        continue;
+                    };
+
+                    let force = spring.force(&a, &b);
+
+                    add(spring.a, force);
+                    add(spring.b, -force);
+                }
+            }
+        }
+
+        forces
+    }
+
+    /// Adds every generator's force to its bodies for the next physics step
+    /// and changes its entities' velocities by the force over `dt` seconds
+    pub fn apply(&self, world: &mut World, physics: &mut PhysicsWorld, dt: f32) {
+        if self.is_empty() {
+            return;
+        }
+
+        for (target, force) in self.forces(world, physics) {
+            match target {
+                ForceTarget::Entity(entity) => {
+                    let mass = world.get::<Mass>(entity).map_or(1.0, |mass| mass.0);
+
+                    if let Some(velocity) = world.get_mut::<Velocity>(entity) {
+                        if mass > 0.0 {
+                            velocity.linear += force / mass * dt;
+                        }
+                    }
+                }
+                ForceTarget::Body(body) => {
+                    if let Some(body) = physics.get_mut(body).filter(|body| body.is_dynamic()) {
+                        body.accumulate_force(force);
+                    }
+                }
+                // Particles are pushed as they're stepped
+                ForceTarget::Emitter(_) => {}
+            }
+        }
+    }
+}
+
+// Current state of a target, if it still exists
+fn point_mass(target: ForceTarget, world: &World, physics: &PhysicsWorld) -> Option<PointMass> {
+    match target {
+        ForceTarget::Entity(entity) => Some(PointMass {
+            position: world.get::<Transform>(entity)?.position(),
+            velocity: world.get::<Velocity>(entity)?.linear,
+            mass: world.get::<Mass>(entity).map_or(1.0, |mass| mass.0),
+        }),
+        ForceTarget::Body(body) => physics.get(body).map(|body| PointMass {
+            position: body.position(),
+            velocity: body.linear_velocity(),
+            mass: body.mass(),
+        }),
+        ForceTarget::Emitter(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Matrix3, Quaternion};
+
+    use super::*;
+    use crate::physics::RigidBody;
+
+    fn entity(world: &mut World, x: f32, mass: Option<f32>) -> Entity {
+        let entity = world.spawn();
+
+        world.insert(
+            entity,
+            Transform::new(Vector3::new(x, 0.0, 0.0), Quaternion::one()),
+        );
+        world.insert(entity, Velocity::default());
+
+        if let Some(mass) = mass {
+            world.insert(entity, Mass(mass));
+        }
+
+        entity
+    }
+
+    #[test]
+    fn springs_push_both_ends_equally_and_oppositely() {
+        let mut world = World::new();
+        let mut physics = PhysicsWorld::new();
+        let a = entity(&mut world, 0.0, None);
+        let b = physics.add(
+            RigidBody::dynamic(2.0, Matrix3::identity()).with_position(Vector3::new(3.0, 0.0, 0.0)),
+        );
+        let mut forces = Forces::new();
+
+        forces.add_spring(Spring::new(a, b, 1.0, 5.0));
+
+        let totals = forces.forces(&world, &physics);
+
+        assert_eq!(totals[&a.into()], Vector3::new(10.0, 0.0, 0.0));
+        assert_eq!(totals[&b.into()], Vector3::new(-10.0, 0.0, 0.0));
+
+        forces.apply(&mut world, &mut physics, 0.5);
+
+        assert_eq!(
+            world.get::<Velocity>(a).unwrap().linear,
+            Vector3::new(5.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            physics.get(b).unwrap().force(),
+            Vector3::new(-10.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn entity_velocities_change_by_force_over_mass() {
+        let mut world = World::new();
+        let mut physics = PhysicsWorld::new();
+        let light = entity(&mut world, 0.0, None);
+        let heavy = entity(&mut world, 0.0, Some(4.0));
+        let massless = entity(&mut world, 0.0, Some(0.0));
+        let mut forces = Forces::new();
+
+        forces.add_field(
+            |_: &PointMass| Vector3::new(2.0, 0.0, 0.0),
+            [light, heavy, massless],
+        );
+        forces.apply(&mut world, &mut physics, 0.5);
+
+        let velocity = |entity| world.get::<Velocity>(entity).unwrap().linear.x;
+
+        assert_eq!(velocity(light), 1.0);
+        assert_eq!(velocity(heavy), 0.25);
+        assert_eq!(velocity(massless), 0.0);
+    }
+
+    #[test]
+    fn removed_targets_are_skipped() {
+        let mut world = World::new();
+        let mut physics = PhysicsWorld::new();
+        let kept = entity(&mut world, 0.0, None);
+        let despawned = entity(&mut world, 1.0, None);
+        let body = physics.add(RigidBody::dynamic(1.0, Matrix3::identity()));
+        let mut forces = Forces::new();
+
+        forces.add_field(Gravity::default(), [ForceTarget::from(kept), body.into()]);
+        forces.add_field(Gravity::default(), [despawned]);
+        forces.add_spring(Spring::new(kept, despawned, 0.0, 1.0));
+        forces.add_spring(Spring::new(kept, body, 0.0, 1.0));
+
+        world.despawn(despawned);
+        physics.remove(body);
+
+        let totals = forces.forces(&world, &physics);
+
+        assert_eq!(totals.keys().copied().collect::<Vec<_>>(), [kept.into()]);
+        assert_eq!(totals[&kept.into()], Gravity::default().acceleration);
+
+        forces.apply(&mut world, &mut physics, 1.0);
+        assert_eq!(
+            world.get::<Velocity>(kept).unwrap().linear,
+            Gravity::default().acceleration
+        );
+    }
+}
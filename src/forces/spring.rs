@@ -0,0 +1,114 @@
+use cgmath::prelude::*;
+use cgmath::Vector3;
+
+use super::{ForceTarget, PointMass};
+
+/// Damped spring between the centres of two targets
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Spring {
+    pub a: ForceTarget,
+    pub b: ForceTarget,
+    pub rest_length: f32,
+    /// Force per unit of stretch
+    pub stiffness: f32,
+    /// Force per unit of speed the ends move apart or together
+    pub damping: f32,
+}
+
+impl Spring {
+    /// Undamped spring, see [`Spring::with_damping`]
+    pub fn new(
+        a: impl Into<ForceTarget>,
+        b: impl Into<ForceTarget>,
+        rest_length: f32,
+        stiffness: f32,
+    ) -> Self {
+        Self {
+            a: a.into(),
+            b: b.into(),
+            rest_length,
+            stiffness,
+            damping: 0.0,
+        }
+    }
+
+    pub fn with_damping(mut self, damping: f32) -> Self {
+        self.damping = damping;
+        self
+    }
+
+    /// Force on end `a`, end `b` feels the opposite
+    pub fn force(&self, a: &PointMass, b: &PointMass) -> Vector3<f32> {
+        let offset = b.position - a.position;
+        let length = offset.magnitude();
+
+        if length <= f32::EPSILON {
+            return Vector3::zero();
+        }
+
+        let direction = offset / length;
+        let stretch = length - self.rest_length;
+        let separating = (b.velocity - a.velocity).dot(direction);
+
+        direction * (self.stiffness * stretch + self.damping * separating)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::World;
+
+    fn point(x: f32, velocity: f32) -> PointMass {
+        PointMass {
+            position: Vector3::new(x, 0.0, 0.0),
+            velocity: Vector3::new(velocity, 0.0, 0.0),
+            mass: 1.0,
+        }
+    }
+
+    #[test]
+    fn stretch_pulls_together_and_compression_pushes_apart() {
+        let mut world = World::new();
+        let spring = Spring::new(world.spawn(), world.spawn(), 2.0, 10.0);
+
+        assert_eq!(
+            spring.force(&point(0.0, 0.0), &point(3.0, 0.0)),
+            Vector3::new(10.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            spring.force(&point(0.0, 0.0), &point(1.0, 0.0)),
+            Vector3::new(-10.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            spring.force(&point(0.0, 0.0), &point(2.0, 0.0)),
+            Vector3::zero()
+        );
+        assert_eq!(
+            spring.force(&point(1.0, 0.0), &point(1.0, 0.0)),
+            Vector3::zero()
+        );
+    }
+
+    #[test]
+    fn damping_resists_the_ends_separating_or_closing() {
+        let mut world = World::new();
+        let spring = Spring::new(world.spawn(), world.spawn(), 2.0, 10.0).with_damping(3.0);
+
+        // At rest length, moving apart is pulled back and closing is pushed out
+        assert_eq!(
+            spring.force(&point(0.0, -1.0), &point(2.0, 1.0)),
+            Vector3::new(6.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            spring.force(&point(0.0, 1.0), &point(2.0, -1.0)),
+            Vector3::new(-6.0, 0.0, 0.0)
+        );
+
+        // Moving together leaves only the stretch
+        assert_eq!(
+            spring.force(&point(0.0, 1.0), &point(3.0, 1.0)),
+            Vector3::new(10.0, 0.0, 0.0)
+        );
+    }
+}
@@ -0,0 +1,431 @@
+//! Bounds, rays, poses and closest point helpers shared by collision detection
+//! and spatial queries
+
+use cgmath::prelude::*;
+use cgmath::{Quaternion, Vector3};
+
+use crate::model::Model;
+
+// Squared lengths below this are treated as degenerate
+const EPSILON: f32 = 1e-12;
+
+/// Axis aligned bounding box, empty while `min` exceeds `max`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Bounds {
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    /// Bounds containing nothing, grown by [`Bounds::grow`] and [`Bounds::union`]
+    pub fn empty() -> Self {
+        Self {
+            min: Vector3::from_value(f32::INFINITY),
+            max: Vector3::from_value(f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_center(center: Vector3<f32>, half_extents: Vector3<f32>) -> Self {
+        Self {
+            min: center - half_extents,
+            max: center + half_extents,
+        }
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = Vector3<f32>>) -> Self {
+        points.into_iter().fold(Self::empty(), |mut bounds, point| {
+            bounds.grow(point);
+            bounds
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vector3<f32> {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn size(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+
+    /// Index of the axis the bounds are longest along
+    pub fn longest_axis(&self) -> usize {
+        let size = self.size();
+
+        if size.x >= size.y && size.x >= size.z {
+            0
+        } else if size.y >= size.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let size = self.size();
+
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    pub fn contains(&self, point: Vector3<f32>) -> bool {
+        (0..3).all(|axis| point[axis] >= self.min[axis] && point[axis] <= self.max[axis])
+    }
+
+    pub fn overlaps(&self, other: &Bounds) -> bool {
+        (0..3).all(|axis| self.min[axis] <= other.max[axis] && other.min[axis] <= self.max[axis])
+    }
+
+    pub fn grow(&mut self, point: Vector3<f32>) {
+        for axis in 0..3 {
+            self.min[axis] = self.min[axis].min(point[axis]);
+            self.max[axis] = self.max[axis].max(point[axis]);
+        }
+    }
+
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        let mut bounds = *self;
+
+        for axis in 0..3 {
+            bounds.min[axis] = bounds.min[axis].min(other.min[axis]);
+            bounds.max[axis] = bounds.max[axis].max(other.max[axis]);
+        }
+
+        bounds
+    }
+
+    /// Bounds grown by `margin` on every side
+    pub fn expanded(&self, margin: f32) -> Bounds {
+        let margin = Vector3::from_value(margin);
+
+        Bounds::new(self.min - margin, self.max + margin)
+    }
+
+    /// Squared distance from `point` to the closest point in the bounds
+    pub fn distance2(&self, point: Vector3<f32>) -> f32 {
+        (0..3)
+            .map(|axis| {
+                let excess = (self.min[axis] - point[axis]).max(point[axis] - self.max[axis]);
+                excess.max(0.0).powi(2)
+            })
+            .sum()
+    }
+
+    /// Distance along the ray to where it enters the bounds, zero if it
+    /// starts inside, or `None` if it misses within `max_distance`
+    pub fn ray_intersection(&self, ray: &Ray, max_distance: f32) -> Option<f32> {
+        let mut near = 0.0_f32;
+        let mut far = max_distance;
+
+        for axis in 0..3 {
+            let inverse = 1.0 / ray.direction[axis];
+            let mut t0 = (self.min[axis] - ray.origin[axis]) * inverse;
+            let mut t1 = (self.max[axis] - ray.origin[axis]) * inverse;
+
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+
+            // NaNs from rays parallel to and on a slab face fall through max and min
+            near = near.max(t0);
+            far = far.min(t1);
+
+            if near > far {
+                return None;
+            }
+        }
+
+        Some(near)
+    }
+}
+
+impl Model {
+    /// Bounds of every mesh's vertices in model space
+    pub fn bounds(&self) -> Bounds {
+        Bounds::from_points(self.positions())
+    }
+
+    /// Vertex positions of every mesh in model space
+    pub fn positions(&self) -> impl Iterator<Item = Vector3<f32>> + '_ {
+        self.meshes
+            .iter()
+            .flat_map(|mesh| mesh.positions.iter().map(|position| (*position).into()))
+    }
+
+    /// Corners of every triangle in model space with the index of its mesh
+    pub fn triangles(&self) -> impl Iterator<Item = (usize, [Vector3<f32>; 3])> + '_ {
+        self.meshes.iter().enumerate().flat_map(|(index, mesh)| {
+            mesh.indices.chunks_exact(3).map(move |corners| {
+                (
+                    index,
+                    [0, 1, 2].map(|i| mesh.positions[corners[i] as usize].into()),
+                )
+            })
+        })
+    }
+}
+
+/// Half line from `origin` along the unit length `direction`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Vector3<f32>,
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    /// Normalises `direction`
+    pub fn new(origin: Vector3<f32>, direction: Vector3<f32>) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> Vector3<f32> {
+        self.origin + self.direction * distance
+    }
+
+    /// Distance along the ray to triangle `a`, `b`, `c` from either side
+    pub fn triangle_intersection(
+        &self,
+        a: Vector3<f32>,
+        b: Vector3<f32>,
+        c: Vector3<f32>,
+    ) -> Option<f32> {
+        // Möller–Trumbore
+        let ab = b - a;
+        let ac = c - a;
+        let p = self.direction.cross(ac);
+        let determinant = ab.dot(p);
+
+        if determinant.abs() < 1e-8 {
+            return None;
+        }
+
+        let inverse = 1.0 / determinant;
+        let offset = self.origin - a;
+        let u = offset.dot(p) * inverse;
+
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = offset.cross(ab);
+        let v = self.direction.dot(q) * inverse;
+
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let distance = ac.dot(q) * inverse;
+
+        (distance >= 0.0).then_some(distance)
+    }
+
+    /// Distance along the ray to where it enters the sphere, 0 if it starts
+    /// inside
+    pub fn sphere_intersection(&self, center: Vector3<f32>, radius: f32) -> Option<f32> {
+        let offset = self.origin - center;
+        let c = offset.magnitude2() - radius * radius;
+
+        if c <= 0.0 {
+            return Some(0.0);
+        }
+
+        let b = offset.dot(self.direction);
+        let discriminant = b * b - c;
+
+        if b > 0.0 || discriminant < 0.0 {
+            return None;
+        }
+
+        Some(-b - discriminant.sqrt())
+    }
+}
+
+/// Position and orientation of a rigid frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pose {
+    pub position: Vector3<f32>,
+    pub orientation: Quaternion<f32>,
+}
+
+impl Pose {
+    pub fn new(position: Vector3<f32>, orientation: Quaternion<f32>) -> Self {
+        Self {
+            position,
+            orientation,
+        }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(Vector3::zero(), Quaternion::one())
+    }
+
+    pub fn transform_point(&self, point: Vector3<f32>) -> Vector3<f32> {
+        self.position + self.orientation.rotate_vector(point)
+    }
+
+    pub fn inverse_transform_point(&self, point: Vector3<f32>) -> Vector3<f32> {
+        self.orientation
+            .conjugate()
+            .rotate_vector(point - self.position)
+    }
+
+    pub fn transform_vector(&self, vector: Vector3<f32>) -> Vector3<f32> {
+        self.orientation.rotate_vector(vector)
+    }
+
+    pub fn inverse_transform_vector(&self, vector: Vector3<f32>) -> Vector3<f32> {
+        self.orientation.conjugate().rotate_vector(vector)
+    }
+
+    /// `local`, given relative to this pose, in this pose's parent frame
+    pub fn then(&self, local: &Pose) -> Pose {
+        Pose::new(
+            self.transform_point(local.position),
+            self.orientation * local.orientation,
+        )
+    }
+}
+
+impl Default for Pose {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+/// Closest point to `point` on segment `a`, `b` and how far along it lies
+pub fn closest_point_on_segment(
+    point: Vector3<f32>,
+    a: Vector3<f32>,
+    b: Vector3<f32>,
+) -> (Vector3<f32>, f32) {
+    let ab = b - a;
+    let length2 = ab.magnitude2();
+
+    if length2 < EPSILON {
+        return (a, 0.0);
+    }
+
+    let t = ((point - a).dot(ab) / length2).clamp(0.0, 1.0);
+
+    (a + ab * t, t)
+}
+
+/// Closest points between segments `p1`, `q1` and `p2`, `q2`, one on each
+pub fn closest_points_on_segments(
+    p1: Vector3<f32>,
+    q1: Vector3<f32>,
+    p2: Vector3<f32>,
+    q2: Vector3<f32>,
+) -> (Vector3<f32>, Vector3<f32>) {
+    let d1 = q1 - p1;
+    let d2 = q2 - p2;
+    let r = p1 - p2;
+    let a = d1.magnitude2();
+    let e = d2.magnitude2();
+    let f = d2.dot(r);
+
+    if a < EPSILON && e < EPSILON {
+        return (p1, p2);
+    }
+
+    let (s, t) = if a < EPSILON {
+        (0.0, (f / e).clamp(0.0, 1.0))
+    } else {
+        let c = d1.dot(r);
+
+        if e < EPSILON {
+            ((-c / a).clamp(0.0, 1.0), 0.0)
+        } else {
+            // Closest points on the infinite lines, clamped back onto the segments
+            let b = d1.dot(d2);
+            let denominator = a * e - b * b;
+            let s = if denominator > EPSILON {
+                ((b * f - c * e) / denominator).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let t = (b * s + f) / e;
+
+            if t < 0.0 {
+                ((-c / a).clamp(0.0, 1.0), 0.0)
+            } else if t > 1.0 {
+                (((b - c) / a).clamp(0.0, 1.0), 1.0)
+            } else {
+                (s, t)
+            }
+        }
+    };
+
+    (p1 + d1 * s, p2 + d2 * t)
+}
+
+/// Closest point to `point` on triangle `a`, `b`, `c`
+pub fn closest_point_on_triangle(
+    point: Vector3<f32>,
+    a: Vector3<f32>,
+    b: Vector3<f32>,
+    c: Vector3<f32>,
+) -> Vector3<f32> {
+    // Find the feature of the triangle's Voronoi regions holding the point
+    let ab = b - a;
+    let ac = c - a;
+    let ap = point - a;
+    let (d1, d2) = (ab.dot(ap), ac.dot(ap));
+
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+
+    let bp = point - b;
+    let (d3, d4) = (ab.dot(bp), ac.dot(bp));
+
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+
+    let cp = point - c;
+    let (d5, d6) = (ab.dot(cp), ac.dot(cp));
+
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+
+    let denominator = 1.0 / (va + vb + vc);
+
+    a + ab * (vb * denominator) + ac * (vc * denominator)
+}
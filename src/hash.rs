@@ -0,0 +1,308 @@
+//! Platform independent hashing of simulation state, for checking that two
+//! runs match step for step
+
+use std::hash::Hasher;
+
+use cgmath::{Quaternion, Vector3};
+
+// FNV-1a 64 bit constants
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// FNV-1a hasher writing integers little endian, so hashes agree across
+/// platforms. Floats are hashed by their bits.
+#[derive(Clone, Copy, Debug)]
+pub struct StateHasher {
+    hash: u64,
+}
+
+impl StateHasher {
+    pub fn new() -> Self {
+        Self {
+            hash: FNV_OFFSET_BASIS,
+        }
+    }
+
+    pub fn hash_str(value: &str) -> u64 {
+        let mut hasher = Self::new();
+
+        hasher.write(value.as_bytes());
+        hasher.finish()
+    }
+
+    pub fn write_f32(&mut self, value: f32) {
+        self.write_u32(value.to_bits());
+    }
+
+    pub fn write_f64(&mut self, value: f64) {
+        self.write_u64(value.to_bits());
+    }
+
+    /// Hashes anything implementing [`StateHash`]
+    pub fn update<T: StateHash + ?Sized>(&mut self, value: &T) {
+        value.hash_state(self);
+    }
+}
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hasher for StateHasher {
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.hash ^= *byte as u64;
+            self.hash = self.hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u16(&mut self, value: u16) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+}
+
+/// State that contributes to [`crate::Context::state_hash`]. Unlike
+/// [`std::hash::Hash`] floats are included, bit for bit.
+pub trait StateHash {
+    fn hash_state(&self, hasher: &mut StateHasher);
+}
+
+impl StateHash for f32 {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_f32(*self);
+    }
+}
+
+impl StateHash for f64 {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_f64(*self);
+    }
+}
+
+impl StateHash for u32 {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u32(*self);
+    }
+}
+
+impl StateHash for u64 {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u64(*self);
+    }
+}
+
+impl StateHash for usize {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_usize(*self);
+    }
+}
+
+impl StateHash for bool {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u8(*self as u8);
+    }
+}
+
+impl<T: StateHash> StateHash for [T] {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_usize(self.len());
+
+        for value in self {
+            value.hash_state(hasher);
+        }
+    }
+}
+
+impl<T: StateHash, const N: usize> StateHash for [T; N] {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        for value in self {
+            value.hash_state(hasher);
+        }
+    }
+}
+
+impl<T: StateHash> StateHash for Vec<T> {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        self.as_slice().hash_state(hasher);
+    }
+}
+
+impl<T: StateHash> StateHash for Option<T> {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        match self {
+            Some(value) => {
+                hasher.write_u8(1);
+                value.hash_state(hasher);
+            }
+            None => hasher.write_u8(0),
+        }
+    }
+}
+
+impl StateHash for Vector3<f32> {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_f32(self.x);
+        hasher.write_f32(self.y);
+        hasher.write_f32(self.z);
+    }
+}
+
+impl StateHash for Quaternion<f32> {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_f32(self.s);
+        self.v.hash_state(hasher);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Matrix3, SquareMatrix, Vector3};
+
+    use super::*;
+    use crate::ecs::World;
+    use crate::forces::{Forces, Vortex};
+    use crate::particles::{Emitter, EmitterShape, ParticleSystem};
+    use crate::physics::{Collider, PhysicsWorld, RigidBody};
+    use crate::random::EngineRng;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn hash_of<T: StateHash>(value: &T) -> u64 {
+        let mut hasher = StateHasher::new();
+
+        hasher.update(value);
+        hasher.finish()
+    }
+
+    // The CPU side of a context: bodies falling onto a floor next to a
+    // fountain, both drawing from the seeded streams
+    struct Run {
+        world: World,
+        physics: PhysicsWorld,
+        forces: Forces,
+        particles: ParticleSystem,
+        rng: EngineRng,
+    }
+
+    impl Run {
+        fn new(seed: u64) -> Self {
+            let mut rng = EngineRng::new(seed);
+            let mut physics = PhysicsWorld::new();
+            let mut particles = ParticleSystem::new();
+            let mut forces = Forces::new();
+
+            physics.add(
+                RigidBody::fixed()
+                    .with_position(Vector3::new(0.0, -1.0, 0.0))
+                    .with_collider(Collider::obb(Vector3::new(10.0, 1.0, 10.0))),
+            );
+
+            for _ in 0..8 {
+                let setup = rng.stream("setup");
+                let position = Vector3::new(
+                    setup.range_f32(-2.0..2.0),
+                    setup.range_f32(1.0..4.0),
+                    setup.range_f32(-2.0..2.0),
+                );
+
+                physics.add(
+                    RigidBody::dynamic(1.0, Matrix3::identity())
+                        .with_position(position)
+                        .with_collider(Collider::sphere(0.5)),
+                );
+            }
+
+            let emitter = particles.add(Emitter::new(EmitterShape::Sphere { radius: 0.5 }));
+
+            forces.add_field(
+                Vortex::new(Vector3::new(0.0, 0.0, 0.0), Vector3::unit_y(), 2.0, 5.0),
+                [emitter],
+            );
+
+            Self {
+                world: World::new(),
+                physics,
+                forces,
+                particles,
+                rng,
+            }
+        }
+
+        fn step(&mut self) {
+            self.forces.apply(&mut self.world, &mut self.physics, DT);
+            self.physics.step(DT);
+            self.particles
+                .step(&self.forces, self.rng.stream("particles"), DT);
+        }
+
+        fn hash(&self) -> u64 {
+            let mut hasher = StateHasher::new();
+
+            hasher.update(&self.physics);
+            hasher.update(&self.particles);
+            hasher.update(&self.rng);
+            hasher.finish()
+        }
+    }
+
+    #[test]
+    fn seeded_runs_match_step_for_step() {
+        let mut a = Run::new(42);
+        let mut b = Run::new(42);
+
+        assert_eq!(a.hash(), b.hash());
+
+        for step in 0..240 {
+            a.step();
+            b.step();
+
+            assert_eq!(a.hash(), b.hash(), "diverged at step {step}");
+        }
+
+        assert!(a.particles.particle_count() > 0);
+    }
+
+    #[test]
+    fn different_seeds_hash_differently() {
+        let mut a = Run::new(1);
+        let mut b = Run::new(2);
+
+        assert_ne!(a.hash(), b.hash());
+
+        for _ in 0..60 {
+            a.step();
+            b.step();
+
+            assert_ne!(a.hash(), b.hash());
+        }
+    }
+
+    #[test]
+    fn rng_draws_change_the_hash() {
+        let mut a = EngineRng::new(7);
+        let mut b = EngineRng::new(7);
+
+        a.stream("particles");
+        b.stream("particles").next_u32();
+
+        assert_ne!(hash_of(&a), hash_of(&b));
+    }
+}
@@ -0,0 +1,372 @@
+//! Offscreen rendering without a window, see [`HeadlessContext`]
+
+use std::hash::Hasher;
+use std::num::NonZeroU32;
+use std::sync::mpsc;
+
+use crate::adapter;
+use crate::config::EngineConfig;
+use crate::error::EngineError;
+use crate::hash::StateHasher;
+use crate::simulation::Simulation;
+use crate::window::Context;
+
+// Type alias for size
+type TargetSize = winit::dpi::PhysicalSize<u32>;
+
+const TARGET_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+const BYTES_PER_PIXEL: u32 = 4;
+
+/// Renders the same scene and pipelines as a window into an offscreen
+/// texture, for machines without a display. Uses the software fallback
+/// adapter when no GPU is available, unless the adapter config disallows it.
+pub struct HeadlessContext {
+    context: Context,
+    target: wgpu::Texture,
+    target_view: wgpu::TextureView,
+}
+
+impl HeadlessContext {
+    /// Creates a context rendering at the config's width and height
+    pub async fn new(engine_config: &EngineConfig) -> Result<Self, EngineError> {
+        let adapter_config = engine_config.adapter.clone().with_env_overrides();
+
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: adapter_config.wgpu_backends(),
+            dx12_shader_compiler: Default::default(),
+        });
+
+        let adapter = adapter::request_adapter(&instance, &adapter_config, None).await?;
+
+        log::info!("Rendering headless on {:?}", adapter.get_info());
+
+        let (device, queue) = Context::request_device(&adapter).await?;
+
+        let size = TargetSize::new(engine_config.width, engine_config.height);
+        let (target, target_view) = Self::create_target(&device, size);
+
+        let mut context = Context::new(
+            device,
+            queue,
+            adapter.get_downlevel_capabilities().flags,
+            TARGET_FORMAT,
+            size,
+            engine_config,
+        )
+        .await?;
+
+        context.init();
+
+        Ok(Self {
+            context,
+            target,
+            target_view,
+        })
+    }
+
+    fn create_target(
+        device: &wgpu::Device,
+        size: TargetSize,
+    ) -> (wgpu::Texture, wgpu::TextureView) {
+        let target = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("HeadlessTarget"),
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: TARGET_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        let target_view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+        (target, target_view)
+    }
+
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    pub fn context_mut(&mut self) -> &mut Context {
+        &mut self.context
+    }
+
+    /// Ignores zero sizes, which can't be rendered to
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let size = TargetSize::new(width, height);
+
+        (self.target, self.target_view) = Self::create_target(self.context.device(), size);
+        self.context.resize(size);
+    }
+
+    pub fn init<S: Simulation>(&mut self, simulation: &mut S) {
+        simulation.init(&mut self.context);
+    }
+
+    /// Advances the simulation and scene by `dt` seconds, running any fixed
+    /// steps that fall due
+    pub fn update<S: Simulation>(&mut self, simulation: &mut S, dt: f32) {
+        self.context.advance(simulation, dt);
+        self.context.finalize();
+    }
+
+    /// Advances by exactly one fixed step, the unit deterministic runs are
+    /// compared in
+    pub fn step<S: Simulation>(&mut self, simulation: &mut S) {
+        let step = self.context.fixed_timestep().step();
+
+        self.update(simulation, step);
+    }
+
+    /// Hash of the context's state and whatever the simulation adds to it,
+    /// equal between deterministic runs that are in step
+    pub fn state_hash<S: Simulation>(&self, simulation: &S) -> u64 {
+        let mut hasher = StateHasher::new();
+
+        self.context.hash_state(&mut hasher);
+        simulation.state_hash(&mut hasher);
+
+        hasher.finish()
+    }
+
+    /// Renders the scene into the offscreen target
+    pub fn render<S: Simulation>(&mut self, simulation: &S) {
+        self.context.render(&self.target_view, simulation);
+    }
+
+    /// Copies the last rendered frame back to the CPU as sRGB RGBA pixels
+    pub fn read_pixels(&self) -> Result<image::RgbaImage, EngineError> {
+        let device = self.context.device();
+        let queue = self.context.queue();
+        let size = self.context.size();
+
+        // Buffer rows have to be padded to the copy alignment
+        let unpadded_bytes_per_row = size.width * BYTES_PER_PIXEL;
+        let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(alignment) * alignment;
+
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("HeadlessReadbackBuffer"),
+            size: (padded_bytes_per_row * size.height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("ReadbackEncoder"),
+        });
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &self.target,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &readback_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_bytes_per_row),
+                    rows_per_image: NonZeroU32::new(size.height),
+                },
+            },
+            wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        queue.submit(std::iter::once(encoder.finish()));
+
+        // Map the buffer and block until the copy has finished
+        let buffer_slice = readback_buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+
+        buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+            sender.send(result).ok();
+        });
+        device.poll(wgpu::Maintain::Wait);
+
+        receiver
+            .recv()
+            .expect("Buffer map callback wasn't called after waiting on the device")?;
+
+        // Strip the row padding
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * size.height) as usize);
+
+        {
+            let padded_pixels = buffer_slice.get_mapped_range();
+
+            for row in padded_pixels.chunks(padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+            }
+        }
+
+        readback_buffer.unmap();
+
+        Ok(image::RgbaImage::from_raw(size.width, size.height, pixels)
+            .expect("Pixel buffer matches the target size"))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use async_std::task::block_on;
+    use cgmath::prelude::*;
+    use cgmath::{Matrix3, Quaternion, Vector3};
+
+    use super::*;
+    use crate::ecs::{Transform, Velocity};
+    use crate::particles::{Emitter, EmitterShape};
+    use crate::physics::{Collider, RigidBody};
+
+    // None when the machine has no adapter at all, not even the fallback
+    pub(crate) fn headless(engine_config: &EngineConfig) -> Option<HeadlessContext> {
+        match block_on(HeadlessContext::new(engine_config)) {
+            Ok(headless) => Some(headless),
+            Err(EngineError::Adapter { .. }) => {
+                eprintln!("No adapter available, skipping");
+                None
+            }
+            Err(error) => panic!("{error}"),
+        }
+    }
+
+    struct Empty;
+
+    impl Simulation for Empty {}
+
+    // Bodies dropped at seeded positions next to a fountain
+    struct Fountain;
+
+    impl Simulation for Fountain {
+        fn init(&mut self, context: &mut Context) {
+            let model = context.light_model();
+
+            for _ in 0..4 {
+                let setup = context.rng_mut().stream("setup");
+                let position = Vector3::new(
+                    setup.range_f32(-2.0..2.0),
+                    setup.range_f32(1.0..4.0),
+                    setup.range_f32(-2.0..2.0),
+                );
+
+                context.spawn_body(
+                    model,
+                    RigidBody::dynamic(1.0, Matrix3::identity())
+                        .with_position(position)
+                        .with_collider(Collider::sphere(0.5)),
+                );
+            }
+
+            context
+                .particles_mut()
+                .add(Emitter::new(EmitterShape::Cone {
+                    angle: 0.3,
+                    radius: 0.1,
+                }));
+        }
+    }
+
+    fn run(seed: u64, steps: usize) -> Option<Vec<u64>> {
+        let engine_config = EngineConfig::default()
+            .with_seed(seed)
+            .with_deterministic(true);
+        let mut headless = headless(&engine_config)?;
+        let mut simulation = Fountain;
+
+        headless.init(&mut simulation);
+
+        Some(
+            (0..steps)
+                .map(|_| {
+                    headless.step(&mut simulation);
+                    headless.state_hash(&simulation)
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn entities_move_by_their_velocity() {
+        let Some(mut headless) = headless(&EngineConfig::default().with_deterministic(true)) else {
+            return;
+        };
+        let step = headless.context().fixed_timestep().step();
+
+        let world = headless.context_mut().world_mut();
+        let entity = world.spawn();
+
+        world.insert(entity, Transform::new(Vector3::zero(), Quaternion::one()));
+        world.insert(entity, Velocity::new(Vector3::unit_x(), Vector3::zero()));
+
+        for _ in 0..10 {
+            headless.step(&mut Empty);
+        }
+
+        let transform = headless.context().world().get::<Transform>(entity).unwrap();
+
+        assert!((transform.position().x - 10.0 * step).abs() < 1e-5);
+    }
+
+    #[test]
+    fn resize_ignores_zero_sizes() {
+        let Some(mut headless) = headless(&EngineConfig::default()) else {
+            return;
+        };
+
+        headless.resize(0, 240);
+        headless.resize(320, 0);
+        headless.render(&Fountain);
+
+        let pixels = headless.read_pixels().unwrap();
+
+        assert_eq!(
+            pixels.dimensions(),
+            (
+                EngineConfig::default().width,
+                EngineConfig::default().height
+            )
+        );
+
+        headless.resize(320, 240);
+        headless.render(&Fountain);
+
+        assert_eq!(headless.read_pixels().unwrap().dimensions(), (320, 240));
+    }
+
+    #[test]
+    fn deterministic_runs_report_unit_time_scale() {
+        for (deterministic, expected) in [(false, 4.0), (true, 1.0)] {
+            let engine_config = EngineConfig::default().with_deterministic(deterministic);
+            let Some(mut headless) = headless(&engine_config) else {
+                return;
+            };
+
+            headless.context_mut().clock_mut().set_time_scale(4.0);
+
+            assert_eq!(headless.context().time_scale(), expected);
+        }
+    }
+
+    #[test]
+    fn deterministic_runs_hash_equal_each_step() {
+        let Some(a) = run(3, 60) else { return };
+        let b = run(3, 60).unwrap();
+        let c = run(4, 60).unwrap();
+
+        assert_eq!(a, b);
+        assert!(a.iter().zip(&c).all(|(a, c)| a != c));
+    }
+}
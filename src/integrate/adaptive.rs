@@ -0,0 +1,535 @@
+use cgmath::Vector3;
+
+use crate::integrate::{Derivative, PhaseState, State, StateVector};
+
+// Tolerance defaults
+const ABSOLUTE_TOLERANCE_DEFAULT: f32 = 1e-6;
+const RELATIVE_TOLERANCE_DEFAULT: f32 = 1e-4;
+
+// Step size control, the new step is the old scaled by a factor in these bounds
+const SAFETY: f32 = 0.9;
+const FACTOR_MIN: f32 = 0.2;
+const FACTOR_MAX: f32 = 10.0;
+
+// Dormand–Prince 5(4) tableau
+const C2: f32 = 1.0 / 5.0;
+const C3: f32 = 3.0 / 10.0;
+const C4: f32 = 4.0 / 5.0;
+const C5: f32 = 8.0 / 9.0;
+
+const A21: f32 = 1.0 / 5.0;
+const A31: f32 = 3.0 / 40.0;
+const A32: f32 = 9.0 / 40.0;
+const A41: f32 = 44.0 / 45.0;
+const A42: f32 = -56.0 / 15.0;
+const A43: f32 = 32.0 / 9.0;
+const A51: f32 = 19372.0 / 6561.0;
+const A52: f32 = -25360.0 / 2187.0;
+const A53: f32 = 64448.0 / 6561.0;
+const A54: f32 = -212.0 / 729.0;
+const A61: f32 = 9017.0 / 3168.0;
+const A62: f32 = -355.0 / 33.0;
+const A63: f32 = 46732.0 / 5247.0;
+const A64: f32 = 49.0 / 176.0;
+const A65: f32 = -5103.0 / 18656.0;
+
+// Fifth order weights, also the last stage's row (first same as last)
+const B1: f32 = 35.0 / 384.0;
+const B3: f32 = 500.0 / 1113.0;
+const B4: f32 = 125.0 / 192.0;
+const B5: f32 = -2187.0 / 6784.0;
+const B6: f32 = 11.0 / 84.0;
+
+// Difference between the fifth and embedded fourth order weights
+const E1: f32 = 71.0 / 57600.0;
+const E3: f32 = -71.0 / 16695.0;
+const E4: f32 = 71.0 / 1920.0;
+const E5: f32 = -17253.0 / 339200.0;
+const E6: f32 = 22.0 / 525.0;
+const E7: f32 = -1.0 / 40.0;
+
+// Dense output weights (Hairer, Nørsett & Wanner)
+const D1: f32 = -12715105075.0 / 11282082432.0;
+const D3: f32 = 87487479700.0 / 32700410799.0;
+const D4: f32 = -10690763975.0 / 1880347072.0;
+const D5: f32 = 701980252875.0 / 199316789632.0;
+const D6: f32 = -1453857185.0 / 822651844.0;
+const D7: f32 = 69997945.0 / 29380423.0;
+
+/// States whose components can be compared against tolerances
+pub trait ErrorNorm: State {
+    /// Calls `visit` with each component of the state, the matching component
+    /// of `next` and of `error`
+    fn visit_components(
+        &self,
+        next: &Self,
+        error: &Self::Derivative,
+        visit: &mut dyn FnMut(f32, f32, f32),
+    );
+}
+
+impl ErrorNorm for f32 {
+    fn visit_components(&self, next: &f32, error: &f32, visit: &mut dyn FnMut(f32, f32, f32)) {
+        visit(*self, *next, *error);
+    }
+}
+
+impl ErrorNorm for Vector3<f32> {
+    fn visit_components(&self, next: &Self, error: &Self, visit: &mut dyn FnMut(f32, f32, f32)) {
+        visit(self.x, next.x, error.x);
+        visit(self.y, next.y, error.y);
+        visit(self.z, next.z, error.z);
+    }
+}
+
+impl ErrorNorm for PhaseState {
+    fn visit_components(
+        &self,
+        next: &Self,
+        error: &Self::Derivative,
+        visit: &mut dyn FnMut(f32, f32, f32),
+    ) {
+        self.position
+            .visit_components(&next.position, &error.velocity, visit);
+        self.velocity
+            .visit_components(&next.velocity, &error.acceleration, visit);
+    }
+}
+
+impl ErrorNorm for StateVector {
+    fn visit_components(&self, next: &Self, error: &Self, visit: &mut dyn FnMut(f32, f32, f32)) {
+        for ((value, next), error) in self.0.iter().zip(&next.0).zip(&error.0) {
+            visit(*value, *next, *error);
+        }
+    }
+}
+
+/// Interpolant over one accepted step, fourth order accurate anywhere in it
+#[derive(Clone, Debug)]
+pub struct DenseOutput<S: State> {
+    t: f32,
+    dt: f32,
+    state: S,
+    coefficients: [S::Derivative; 4],
+}
+
+impl<S: State> DenseOutput<S> {
+    /// Start and end time of the step
+    pub fn interval(&self) -> (f32, f32) {
+        (self.t, self.t + self.dt)
+    }
+
+    /// State at `t`, which should lie within the interval
+    pub fn sample(&self, t: f32) -> S {
+        let theta = (t - self.t) / self.dt;
+        let theta1 = 1.0 - theta;
+        let [r2, r3, r4, r5] = &self.coefficients;
+
+        let slope = r2
+            .add(
+                &r3.add(&r4.add(&r5.scale(theta1)).scale(theta))
+                    .scale(theta1),
+            )
+            .scale(theta);
+
+        self.state.apply(&slope, self.dt)
+    }
+}
+
+/// Adaptive Dormand–Prince RK45 solver. Each step is checked against an
+/// embedded fourth order solution and retried with a smaller step if the
+/// error exceeds `atol + rtol * |y|`, while smooth stretches take larger
+/// steps. Dense output gives the state at any time within the last step, so
+/// frames can sample exact times without forcing the step size.
+#[derive(Clone, Debug)]
+pub struct DormandPrince<S: ErrorNorm> {
+    absolute_tolerance: f32,
+    relative_tolerance: f32,
+    min_step: f32,
+    max_step: f32,
+    t: f32,
+    state: S,
+    dt: Option<f32>,
+    // Derivative at the current state, reused from the last stage
+    derivative: Option<S::Derivative>,
+    dense_output: Option<DenseOutput<S>>,
+    accepted_steps: u32,
+    rejected_steps: u32,
+}
+
+impl<S: ErrorNorm> DormandPrince<S> {
+    pub fn new(t: f32, state: S) -> Self {
+        Self {
+            absolute_tolerance: ABSOLUTE_TOLERANCE_DEFAULT,
+            relative_tolerance: RELATIVE_TOLERANCE_DEFAULT,
+            min_step: 1e-6,
+            max_step: f32::INFINITY,
+            t,
+            state,
+            dt: None,
+            derivative: None,
+            dense_output: None,
+            accepted_steps: 0,
+            rejected_steps: 0,
+        }
+    }
+
+    pub fn with_tolerances(mut self, absolute_tolerance: f32, relative_tolerance: f32) -> Self {
+        self.absolute_tolerance = absolute_tolerance;
+        self.relative_tolerance = relative_tolerance;
+        self
+    }
+
+    /// Steps are kept within these bounds. Steps at the minimum are accepted
+    /// even when they miss the tolerances. Far from time 0 the minimum grows
+    /// to a few units of float precision of the time, so every step moves
+    /// time forward.
+    pub fn with_step_bounds(mut self, min_step: f32, max_step: f32) -> Self {
+        self.min_step = min_step;
+        self.max_step = max_step;
+        self
+    }
+
+    /// First step to try, estimated from the derivative when unset
+    pub fn with_initial_step(mut self, dt: f32) -> Self {
+        self.dt = Some(dt);
+        self
+    }
+
+    pub fn time(&self) -> f32 {
+        self.t
+    }
+
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// Replaces the state, e.g. after a collision, discarding the dense output
+    pub fn set_state(&mut self, t: f32, state: S) {
+        self.t = t;
+        self.state = state;
+        self.derivative = None;
+        self.dense_output = None;
+    }
+
+    /// Size the next step will try
+    pub fn step_size(&self) -> Option<f32> {
+        self.dt
+    }
+
+    pub fn accepted_steps(&self) -> u32 {
+        self.accepted_steps
+    }
+
+    pub fn rejected_steps(&self) -> u32 {
+        self.rejected_steps
+    }
+
+    /// Interpolant over the last accepted step
+    pub fn dense_output(&self) -> Option<&DenseOutput<S>> {
+        self.dense_output.as_ref()
+    }
+
+    // Root mean square of the components scaled by the tolerances
+    fn norm(&self, state: &S, next: &S, error: &S::Derivative) -> f32 {
+        let mut sum = 0.0;
+        let mut count = 0;
+
+        state.visit_components(next, error, &mut |value, next, error| {
+            let scale =
+                self.absolute_tolerance + self.relative_tolerance * value.abs().max(next.abs());
+
+            sum += (error / scale).powi(2);
+            count += 1;
+        });
+
+        if count == 0 {
+            0.0
+        } else {
+            (sum / count as f32).sqrt()
+        }
+    }
+
+    // Smallest step that still moves time forward from `t`
+    fn min_step_at(&self, t: f32) -> f32 {
+        self.min_step.max(t.abs() * f32::EPSILON * 4.0)
+    }
+
+    // Step of 1% of the ratio between the state and derivative magnitudes
+    fn initial_step(&self, derivative: &S::Derivative) -> f32 {
+        let mut state_sum = 0.0;
+        let mut derivative_sum = 0.0;
+
+        self.state
+            .visit_components(&self.state, derivative, &mut |value, _, rate| {
+                let scale = self.absolute_tolerance + self.relative_tolerance * value.abs();
+
+                state_sum += (value / scale).powi(2);
+                derivative_sum += (rate / scale).powi(2);
+            });
+
+        let dt = if state_sum < 1e-10 || derivative_sum < 1e-10 {
+            1e-6
+        } else {
+            0.01 * (state_sum / derivative_sum).sqrt()
+        };
+
+        dt.clamp(
+            self.min_step_at(self.t),
+            self.max_step.max(self.min_step_at(self.t)),
+        )
+    }
+
+    /// Takes one accepted step, retrying with smaller steps as needed, and
+    /// returns its size
+    pub fn step<F>(&mut self, mut derivative: F) -> f32
+    where
+        F: FnMut(f32, &S) -> S::Derivative,
+    {
+        let t = self.t;
+        let min_step = self.min_step_at(t);
+        let max_step = self.max_step.max(min_step);
+        let k1 = match self.derivative.take() {
+            Some(k1) => k1,
+            None => derivative(t, &self.state),
+        };
+        let mut dt = match self.dt {
+            Some(dt) => dt.clamp(min_step, max_step),
+            None => self.initial_step(&k1),
+        };
+
+        loop {
+            let y = &self.state;
+
+            let k2 = derivative(t + C2 * dt, &y.apply(&k1.scale(A21), dt));
+            let k3 = derivative(
+                t + C3 * dt,
+                &y.apply(&k1.scale(A31).add(&k2.scale(A32)), dt),
+            );
+            let k4 = derivative(
+                t + C4 * dt,
+                &y.apply(&k1.scale(A41).add(&k2.scale(A42)).add(&k3.scale(A43)), dt),
+            );
+            let k5 = derivative(
+                t + C5 * dt,
+                &y.apply(
+                    &k1.scale(A51)
+                        .add(&k2.scale(A52))
+                        .add(&k3.scale(A53))
+                        .add(&k4.scale(A54)),
+                    dt,
+                ),
+            );
+            let k6 = derivative(
+                t + dt,
+                &y.apply(
+                    &k1.scale(A61)
+                        .add(&k2.scale(A62))
+                        .add(&k3.scale(A63))
+                        .add(&k4.scale(A64))
+                        .add(&k5.scale(A65)),
+                    dt,
+                ),
+            );
+
+            let slope = k1
+                .scale(B1)
+                .add(&k3.scale(B3))
+                .add(&k4.scale(B4))
+                .add(&k5.scale(B5))
+                .add(&k6.scale(B6));
+            let next = y.apply(&slope, dt);
+            let k7 = derivative(t + dt, &next);
+
+            let error = k1
+                .scale(E1)
+                .add(&k3.scale(E3))
+                .add(&k4.scale(E4))
+                .add(&k5.scale(E5))
+                .add(&k6.scale(E6))
+                .add(&k7.scale(E7))
+                .scale(dt);
+            let error_norm = self.norm(y, &next, &error);
+
+            // Non-finite errors, from a state blowing up, shrink as far as allowed
+            let factor = if !error_norm.is_finite() {
+                FACTOR_MIN
+            } else if error_norm == 0.0 {
+                FACTOR_MAX
+            } else {
+                (SAFETY * error_norm.powf(-0.2)).clamp(FACTOR_MIN, FACTOR_MAX)
+            };
+
+            if (error_norm > 1.0 || error_norm.is_nan()) && dt > min_step {
+                self.rejected_steps += 1;
+                dt = (dt * factor.min(1.0)).max(min_step);
+
+                continue;
+            }
+
+            // Coefficients of the interpolating polynomial, relative to dt
+            let r3 = k1.add(&slope.scale(-1.0));
+            let r4 = slope.add(&k7.scale(-1.0)).add(&r3.scale(-1.0));
+            let r5 = k1
+                .scale(D1)
+                .add(&k3.scale(D3))
+                .add(&k4.scale(D4))
+                .add(&k5.scale(D5))
+                .add(&k6.scale(D6))
+                .add(&k7.scale(D7));
+
+            self.dense_output = Some(DenseOutput {
+                t,
+                dt,
+                state: self.state.clone(),
+                coefficients: [slope, r3, r4, r5],
+            });
+
+            self.t = t + dt;
+            self.state = next;
+            self.derivative = Some(k7);
+            self.dt = Some((dt * factor).clamp(min_step, max_step));
+            self.accepted_steps += 1;
+
+            return dt;
+        }
+    }
+
+    /// Steps until the solver has reached or passed `t`, or stops making
+    /// progress as at non-finite times
+    pub fn advance_to<F>(&mut self, t: f32, mut derivative: F)
+    where
+        F: FnMut(f32, &S) -> S::Derivative,
+    {
+        while self.t < t {
+            let previous = self.t;
+
+            self.step(&mut derivative);
+
+            if self.t <= previous || !self.t.is_finite() {
+                break;
+            }
+        }
+    }
+
+    /// State at exactly `t`, stepping as far as needed and interpolating
+    /// within the last step. Times before the last step return the current
+    /// state.
+    pub fn state_at<F>(&mut self, t: f32, derivative: F) -> S
+    where
+        F: FnMut(f32, &S) -> S::Derivative,
+    {
+        self.advance_to(t, derivative);
+
+        match &self.dense_output {
+            Some(dense_output) if t >= dense_output.t => dense_output.sample(t),
+            _ => self.state.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{InnerSpace, Vector3};
+
+    use super::*;
+    use crate::integrate::PhaseDerivative;
+
+    fn decay(_t: f32, y: &f32) -> f32 {
+        -y
+    }
+
+    fn oscillator(_t: f32, state: &PhaseState) -> PhaseDerivative {
+        state.derivative(-state.position)
+    }
+
+    #[test]
+    fn exponential_decay_matches_analytic() {
+        let mut solver = DormandPrince::new(0.0, 1.0).with_tolerances(1e-7, 1e-6);
+
+        for &t in &[0.5, 1.0, 2.0, 5.0] {
+            let y = solver.state_at(t, decay);
+
+            assert!((y - (-t).exp()).abs() < 1e-5, "y({t}) = {y}");
+        }
+    }
+
+    #[test]
+    fn harmonic_oscillator_matches_analytic() {
+        let state = PhaseState::new(Vector3::unit_x(), Vector3::new(0.0, 0.0, 0.0));
+        let mut solver = DormandPrince::new(0.0, state).with_tolerances(1e-7, 1e-6);
+
+        solver.advance_to(10.0, oscillator);
+
+        let t = solver.time();
+        let state = solver.state();
+
+        assert!((state.position.x - t.cos()).abs() < 1e-3);
+        assert!((state.velocity.x + t.sin()).abs() < 1e-3);
+        assert!(solver.rejected_steps() < solver.accepted_steps());
+    }
+
+    #[test]
+    fn dense_output_matches_analytic_inside_step() {
+        let state = PhaseState::new(Vector3::unit_x(), Vector3::new(0.0, 0.0, 0.0));
+        let mut solver = DormandPrince::new(0.0, state)
+            .with_tolerances(1e-7, 1e-6)
+            .with_initial_step(0.5);
+
+        solver.step(oscillator);
+
+        let dense_output = solver.dense_output().unwrap();
+        let (start, end) = dense_output.interval();
+
+        assert!(end > start);
+
+        for fraction in [0.1, 0.25, 0.5, 0.75, 0.9] {
+            let t = start + (end - start) * fraction;
+            let sample = dense_output.sample(t);
+            let expected = Vector3::new(t.cos(), 0.0, 0.0);
+
+            assert!((sample.position - expected).magnitude() < 1e-4, "x({t})");
+        }
+
+        // Interior samples agree with stepping straight to the time
+        let middle = (start + end) / 2.0;
+        let direct = DormandPrince::new(
+            0.0,
+            PhaseState::new(Vector3::unit_x(), Vector3::new(0.0, 0.0, 0.0)),
+        )
+        .with_tolerances(1e-7, 1e-6)
+        .state_at(middle, oscillator);
+
+        assert!((dense_output.sample(middle).position - direct.position).magnitude() < 1e-4);
+    }
+
+    #[test]
+    fn min_step_is_accepted_and_keeps_time_moving() {
+        let mut solver = DormandPrince::new(0.0, 1.0).with_step_bounds(1e-3, 1.0);
+
+        // Far too stiff for explicit steps of 1e-3
+        solver.advance_to(0.1, |t, y| -1e6 * (y - t.cos()));
+
+        assert!(solver.time() >= 0.1);
+        assert_eq!(solver.step_size(), Some(1e-3));
+
+        // Where t + min_step == t the minimum grows with t
+        let mut solver = DormandPrince::new(100.0, 0.0);
+
+        solver.advance_to(100.1, |t, y| -1e6 * (y - t.cos()));
+
+        assert!(solver.time() >= 100.1);
+    }
+
+    #[test]
+    fn non_finite_error_is_rejected() {
+        let mut solver = DormandPrince::new(0.0, 1.0).with_initial_step(0.5);
+
+        // Blows up only for steps past 0.1
+        solver.step(|t, y| if t > 0.1 { f32::NAN } else { -y });
+
+        assert!(solver.rejected_steps() > 0);
+        assert!(solver.state().is_finite());
+        assert!(solver.time() <= 0.1);
+    }
+}
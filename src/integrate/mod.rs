@@ -0,0 +1,79 @@
+//! Numerical integration of simulation state, see [`State`] and [`Integrator`]
+//!
+//! A system is described by a function returning the [`State::Derivative`]
+//! of a state at a time. Any [`Integrator`] can then advance it, so switching
+//! from [`Euler`] to [`Rk4`] is a one word change. Systems that need their
+//! step size adapted to the error use [`DormandPrince`] instead.
+
+use serde::{Deserialize, Serialize};
+
+mod adaptive;
+mod state;
+mod stepper;
+
+pub use adaptive::{DenseOutput, DormandPrince, ErrorNorm};
+pub use state::{PhaseDerivative, PhaseState, StateVector};
+pub use stepper::{Euler, Rk4, SemiImplicitEuler, VelocityVerlet};
+
+/// Rate of change of a [`State`], which integrators scale and sum
+pub trait Derivative: Clone {
+    fn add(&self, other: &Self) -> Self;
+
+    fn scale(&self, factor: f32) -> Self;
+}
+
+/// Anything integrators can advance. Second order states, with positions
+/// and velocities, override [`State::kick`] and [`State::drift`] so the
+/// symplectic integrators can update the two separately.
+pub trait State: Clone {
+    type Derivative: Derivative;
+
+    /// The state moved along `derivative` for `dt` seconds
+    fn apply(&self, derivative: &Self::Derivative, dt: f32) -> Self;
+
+    /// Updates only the velocities from the derivative's accelerations. First
+    /// order states apply the whole derivative.
+    fn kick(&self, derivative: &Self::Derivative, dt: f32) -> Self {
+        self.apply(derivative, dt)
+    }
+
+    /// Updates only the positions from the state's own velocities. First
+    /// order states are left unchanged.
+    fn drift(&self, _dt: f32) -> Self {
+        self.clone()
+    }
+}
+
+/// Advances a state by one step of `dt` seconds from time `t`, given the
+/// function returning the state's derivative at a time
+pub trait Integrator {
+    fn step<S, F>(&self, state: &S, t: f32, dt: f32, derivative: F) -> S
+    where
+        S: State,
+        F: FnMut(f32, &S) -> S::Derivative;
+}
+
+/// The built in integrators, for picking one at runtime or from config
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IntegratorKind {
+    Euler,
+    #[default]
+    SemiImplicitEuler,
+    VelocityVerlet,
+    Rk4,
+}
+
+impl Integrator for IntegratorKind {
+    fn step<S, F>(&self, state: &S, t: f32, dt: f32, derivative: F) -> S
+    where
+        S: State,
+        F: FnMut(f32, &S) -> S::Derivative,
+    {
+        match self {
+            IntegratorKind::Euler => Euler.step(state, t, dt, derivative),
+            IntegratorKind::SemiImplicitEuler => SemiImplicitEuler.step(state, t, dt, derivative),
+            IntegratorKind::VelocityVerlet => VelocityVerlet.step(state, t, dt, derivative),
+            IntegratorKind::Rk4 => Rk4.step(state, t, dt, derivative),
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use cgmath::Vector3;
+
+use crate::hash::{StateHash, StateHasher};
+use crate::integrate::{Derivative, State};
+
+impl Derivative for f32 {
+    fn add(&self, other: &Self) -> Self {
+        self + other
+    }
+
+    fn scale(&self, factor: f32) -> Self {
+        self * factor
+    }
+}
+
+impl State for f32 {
+    type Derivative = f32;
+
+    fn apply(&self, derivative: &f32, dt: f32) -> Self {
+        self + derivative * dt
+    }
+}
+
+impl Derivative for Vector3<f32> {
+    fn add(&self, other: &Self) -> Self {
+        self + other
+    }
+
+    fn scale(&self, factor: f32) -> Self {
+        self * factor
+    }
+}
+
+impl State for Vector3<f32> {
+    type Derivative = Vector3<f32>;
+
+    fn apply(&self, derivative: &Vector3<f32>, dt: f32) -> Self {
+        self + derivative * dt
+    }
+}
+
+/// Position and velocity of a point mass
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PhaseState {
+    pub position: Vector3<f32>,
+    pub velocity: Vector3<f32>,
+}
+
+impl PhaseState {
+    pub fn new(position: Vector3<f32>, velocity: Vector3<f32>) -> Self {
+        Self { position, velocity }
+    }
+
+    /// The derivative of a point mass under `acceleration`
+    pub fn derivative(&self, acceleration: Vector3<f32>) -> PhaseDerivative {
+        PhaseDerivative {
+            velocity: self.velocity,
+            acceleration,
+        }
+    }
+}
+
+/// Derivative of a [`PhaseState`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PhaseDerivative {
+    pub velocity: Vector3<f32>,
+    pub acceleration: Vector3<f32>,
+}
+
+impl Derivative for PhaseDerivative {
+    fn add(&self, other: &Self) -> Self {
+        Self {
+            velocity: self.velocity + other.velocity,
+            acceleration: self.acceleration + other.acceleration,
+        }
+    }
+
+    fn scale(&self, factor: f32) -> Self {
+        Self {
+            velocity: self.velocity * factor,
+            acceleration: self.acceleration * factor,
+        }
+    }
+}
+
+impl State for PhaseState {
+    type Derivative = PhaseDerivative;
+
+    fn apply(&self, derivative: &PhaseDerivative, dt: f32) -> Self {
+        Self {
+            position: self.position + derivative.velocity * dt,
+            velocity: self.velocity + derivative.acceleration * dt,
+        }
+    }
+
+    fn kick(&self, derivative: &PhaseDerivative, dt: f32) -> Self {
+        Self {
+            position: self.position,
+            velocity: self.velocity + derivative.acceleration * dt,
+        }
+    }
+
+    fn drift(&self, dt: f32) -> Self {
+        Self {
+            position: self.position + self.velocity * dt,
+            velocity: self.velocity,
+        }
+    }
+}
+
+impl StateHash for PhaseState {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        self.position.hash_state(hasher);
+        self.velocity.hash_state(hasher);
+    }
+}
+
+/// State of an arbitrary first order system `dy/dt = f(t, y)`, also used as
+/// its own derivative
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StateVector(pub Vec<f32>);
+
+impl StateVector {
+    pub fn zeros(len: usize) -> Self {
+        Self(vec![0.0; len])
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<Vec<f32>> for StateVector {
+    fn from(values: Vec<f32>) -> Self {
+        Self(values)
+    }
+}
+
+impl std::ops::Index<usize> for StateVector {
+    type Output = f32;
+
+    fn index(&self, index: usize) -> &f32 {
+        &self.0[index]
+    }
+}
+
+impl std::ops::IndexMut<usize> for StateVector {
+    fn index_mut(&mut self, index: usize) -> &mut f32 {
+        &mut self.0[index]
+    }
+}
+
+impl Derivative for StateVector {
+    fn add(&self, other: &Self) -> Self {
+        Self(self.0.iter().zip(&other.0).map(|(a, b)| a + b).collect())
+    }
+
+    fn scale(&self, factor: f32) -> Self {
+        Self(self.0.iter().map(|value| value * factor).collect())
+    }
+}
+
+impl State for StateVector {
+    type Derivative = StateVector;
+
+    fn apply(&self, derivative: &StateVector, dt: f32) -> Self {
+        Self(
+            self.0
+                .iter()
+                .zip(&derivative.0)
+                .map(|(value, rate)| value + rate * dt)
+                .collect(),
+        )
+    }
+}
+
+impl StateHash for StateVector {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        self.0.hash_state(hasher);
+    }
+}
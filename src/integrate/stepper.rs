@@ -0,0 +1,139 @@
+use crate::integrate::{Derivative, Integrator, State};
+
+/// Explicit Euler, first order. Cheap but gains energy, so orbits spiral out.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Euler;
+
+impl Integrator for Euler {
+    fn step<S, F>(&self, state: &S, t: f32, dt: f32, mut derivative: F) -> S
+    where
+        S: State,
+        F: FnMut(f32, &S) -> S::Derivative,
+    {
+        state.apply(&derivative(t, state), dt)
+    }
+}
+
+/// Semi-implicit (symplectic) Euler, first order. Velocities are updated
+/// first and positions move with the new velocities, which keeps energy
+/// bounded.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SemiImplicitEuler;
+
+impl Integrator for SemiImplicitEuler {
+    fn step<S, F>(&self, state: &S, t: f32, dt: f32, mut derivative: F) -> S
+    where
+        S: State,
+        F: FnMut(f32, &S) -> S::Derivative,
+    {
+        state.kick(&derivative(t, state), dt).drift(dt)
+    }
+}
+
+/// Velocity Verlet (kick-drift-kick), second order and symplectic. Assumes
+/// accelerations depend on positions rather than velocities.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VelocityVerlet;
+
+impl Integrator for VelocityVerlet {
+    fn step<S, F>(&self, state: &S, t: f32, dt: f32, mut derivative: F) -> S
+    where
+        S: State,
+        F: FnMut(f32, &S) -> S::Derivative,
+    {
+        let half_dt = dt / 2.0;
+
+        let drifted = state.kick(&derivative(t, state), half_dt).drift(dt);
+
+        drifted.kick(&derivative(t + dt, &drifted), half_dt)
+    }
+}
+
+/// Classic fourth order Runge–Kutta, accurate for smooth systems but not
+/// symplectic
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Rk4;
+
+impl Integrator for Rk4 {
+    fn step<S, F>(&self, state: &S, t: f32, dt: f32, mut derivative: F) -> S
+    where
+        S: State,
+        F: FnMut(f32, &S) -> S::Derivative,
+    {
+        let half_dt = dt / 2.0;
+
+        let k1 = derivative(t, state);
+        let k2 = derivative(t + half_dt, &state.apply(&k1, half_dt));
+        let k3 = derivative(t + half_dt, &state.apply(&k2, half_dt));
+        let k4 = derivative(t + dt, &state.apply(&k3, dt));
+
+        let slope = k1
+            .add(&k2.scale(2.0))
+            .add(&k3.scale(2.0))
+            .add(&k4)
+            .scale(1.0 / 6.0);
+
+        state.apply(&slope, dt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::prelude::*;
+    use cgmath::Vector3;
+
+    use super::*;
+    use crate::integrate::{PhaseDerivative, PhaseState};
+
+    // Unit point mass around a unit central mass
+    fn gravity(_: f32, state: &PhaseState) -> PhaseDerivative {
+        let r = state.position.magnitude();
+
+        state.derivative(-state.position / (r * r * r))
+    }
+
+    fn energy(state: &PhaseState) -> f32 {
+        state.velocity.magnitude2() / 2.0 - 1.0 / state.position.magnitude()
+    }
+
+    // Largest energy error over ten circular orbits
+    fn orbit_energy_drift(integrator: impl Integrator) -> f32 {
+        let dt = 0.01;
+        let steps = (10.0 * std::f32::consts::TAU / dt) as usize;
+
+        let mut state = PhaseState::new(Vector3::unit_x(), Vector3::unit_y());
+        let initial = energy(&state);
+        let mut drift = 0.0f32;
+
+        for i in 0..steps {
+            state = integrator.step(&state, i as f32 * dt, dt, gravity);
+            drift = drift.max((energy(&state) - initial).abs());
+        }
+
+        drift
+    }
+
+    #[test]
+    fn symplectic_orbits_keep_their_energy() {
+        assert!(orbit_energy_drift(SemiImplicitEuler) < 1e-2);
+        assert!(orbit_energy_drift(VelocityVerlet) < 1e-4);
+        assert!(orbit_energy_drift(Euler) > 0.1);
+    }
+
+    fn evaluations(integrator: impl Integrator) -> usize {
+        let state = PhaseState::new(Vector3::unit_x(), Vector3::unit_y());
+        let mut count = 0;
+
+        integrator.step(&state, 0.0, 0.01, |t, state| {
+            count += 1;
+            gravity(t, state)
+        });
+        count
+    }
+
+    #[test]
+    fn symplectic_steps_evaluate_once_per_kick() {
+        assert_eq!(evaluations(SemiImplicitEuler), 1);
+        assert_eq!(evaluations(VelocityVerlet), 2);
+    }
+}
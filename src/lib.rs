@@ -1,18 +1,61 @@
-mod model;
-mod resource;
-mod texture;
-mod time;
-mod window;
+//! wgpu rendered engine providing flexible, lightweight tools to quickly bring
+//! to life simulation concepts.
+//!
+//! Implement [`Simulation`] and hand it to [`run_simulation`] (or
+//! [`Window::run`]) to drive the engine. Most of what a simulation needs is
+//! re-exported from [`prelude`].
 
-use window::Window;
+pub mod adapter;
+pub mod config;
+pub mod ecs;
+pub mod error;
+pub mod forces;
+pub mod geometry;
+pub mod hash;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod headless;
+pub mod integrate;
+pub mod model;
+pub mod particles;
+pub mod physics;
+pub mod prelude;
+pub mod random;
+pub mod resource;
+pub mod scene;
+pub mod simulation;
+pub mod spatial;
+pub mod texture;
+pub mod time;
+pub mod window;
+
+pub use config::EngineConfig;
+pub use error::EngineError;
+#[cfg(not(target_arch = "wasm32"))]
+pub use headless::HeadlessContext;
+pub use model::instance::Instance;
+pub use simulation::{Demo, Simulation};
+pub use window::{Context, Window};
 
 use async_std::task::block_on;
 
 #[cfg(target_arch = "wasm32")]
 pub use wasm_bindgen::prelude::*;
 
+/// Runs the built-in [`Demo`] simulation with the default config, logging
+/// initialisation errors
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen(start))]
 pub fn run() {
+    if let Err(error) = run_simulation(EngineConfig::default(), Demo) {
+        log::error!("{}", error);
+    }
+}
+
+/// Initialises logging, opens a window and runs `simulation` until it is
+/// closed. Only returns if initialisation fails.
+pub fn run_simulation<S: Simulation + 'static>(
+    config: EngineConfig,
+    simulation: S,
+) -> Result<(), EngineError> {
     // Toggle logging based on whether we are using webassembly or not
     cfg_if::cfg_if! {
         if #[cfg(target_arch = "wasm32")] {
@@ -24,5 +67,5 @@ pub fn run() {
     }
 
     // Create and run window
-    block_on(Window::new().run());
+    block_on(Window::new(config).run(simulation))
 }
@@ -1,14 +1,57 @@
 use cgmath::prelude::*;
 use cgmath::{Deg, Matrix3, Matrix4, Quaternion, Vector3};
 
+use crate::hash::{StateHash, StateHasher};
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct InstanceRaw {
+pub(crate) struct InstanceRaw {
     object: [[f32; 4]; 4],
     normal: [[f32; 3]; 3],
+    color: [f32; 4],
 }
 
 impl InstanceRaw {
+    pub(crate) fn from_matrix(matrix: Matrix4<f32>, color: [f32; 4]) -> Self {
+        let linear = Matrix3::from_cols(
+            matrix.x.truncate(),
+            matrix.y.truncate(),
+            matrix.z.truncate(),
+        );
+
+        InstanceRaw {
+            object: matrix.into(),
+            normal: Self::normal_matrix(linear).into(),
+            color,
+        }
+    }
+
+    // Translated and uniformly scaled, skipping the general normal matrix
+    pub(crate) fn from_position_scale(position: Vector3<f32>, scale: f32, color: [f32; 4]) -> Self {
+        let normal = if scale != 0.0 { scale.recip() } else { 0.0 };
+
+        InstanceRaw {
+            object: (Matrix4::from_translation(position) * Matrix4::from_scale(scale)).into(),
+            normal: Matrix3::from_scale(normal).into(),
+            color,
+        }
+    }
+
+    // Normals transform by the inverse transpose so they stay perpendicular
+    // to surfaces under non-uniform scale, degenerate scales keep the
+    // original matrix
+    fn normal_matrix(linear: Matrix3<f32>) -> Matrix3<f32> {
+        linear
+            .invert()
+            .map(|inverse| inverse.transpose())
+            .unwrap_or(linear)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn matrix(&self) -> Matrix4<f32> {
+        self.object.into()
+    }
+
     pub fn get_buffer_layout<'a>() -> wgpu::VertexBufferLayout<'a> {
         use std::mem;
         wgpu::VertexBufferLayout {
@@ -50,21 +93,102 @@ impl InstanceRaw {
                     shader_location: 11,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 25]>() as wgpu::BufferAddress,
+                    shader_location: 12,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
             ],
         }
     }
 }
 
-// Instance
+/// Tint that leaves the model's own colours unchanged
+pub const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+/// Placement, size and tint of one copy of a model in the scene
+#[derive(Copy, Clone, Debug)]
 pub struct Instance {
     position: Vector3<f32>,
     rotation: Quaternion<f32>,
-    // _scale: Vector3<f32>,
+    scale: Vector3<f32>,
+    color: [f32; 4],
 }
 
 impl Instance {
     pub fn new(position: Vector3<f32>, rotation: Quaternion<f32>) -> Self {
-        Self { position, rotation }
+        Self {
+            position,
+            rotation,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_scale(mut self, scale: Vector3<f32>) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn position(&self) -> Vector3<f32> {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: Vector3<f32>) {
+        self.position = position;
+    }
+
+    pub fn rotation(&self) -> Quaternion<f32> {
+        self.rotation
+    }
+
+    pub fn set_rotation(&mut self, rotation: Quaternion<f32>) {
+        self.rotation = rotation;
+    }
+
+    /// Scale along each of the model's axes, applied before rotation
+    pub fn scale(&self) -> Vector3<f32> {
+        self.scale
+    }
+
+    pub fn set_scale(&mut self, scale: Vector3<f32>) {
+        self.scale = scale;
+    }
+
+    /// RGBA tint multiplied with the model's texture
+    pub fn color(&self) -> [f32; 4] {
+        self.color
+    }
+
+    pub fn set_color(&mut self, color: [f32; 4]) {
+        self.color = color;
+    }
+
+    /// Blends towards `next` by `alpha` in [0, 1], slerping the rotation
+    pub fn interpolate(&self, next: &Instance, alpha: f32) -> Instance {
+        let mut color = self.color;
+
+        for (channel, next_channel) in color.iter_mut().zip(next.color) {
+            *channel += (next_channel - *channel) * alpha;
+        }
+
+        Instance {
+            position: self.position.lerp(next.position, alpha),
+            rotation: self.rotation.slerp(next.rotation, alpha),
+            scale: self.scale.lerp(next.scale, alpha),
+            color,
+        }
+    }
+
+    /// Transform from model space into the instance's parent space
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.position)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
     }
 }
 
@@ -73,17 +197,71 @@ impl Default for Instance {
         Self {
             position: Vector3::zero(),
             rotation: Quaternion::from_axis_angle(Vector3::unit_z(), Deg(0.0)),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+            color: WHITE,
         }
     }
 }
 
+impl StateHash for Instance {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        self.position.hash_state(hasher);
+        self.rotation.hash_state(hasher);
+        self.scale.hash_state(hasher);
+        self.color.hash_state(hasher);
+    }
+}
+
 impl From<&Instance> for InstanceRaw {
     fn from(instance: &Instance) -> Self {
-        InstanceRaw {
-            object: (Matrix4::from_translation(instance.position)
-                * Matrix4::from(instance.rotation))
-            .into(),
-            normal: Matrix3::from(instance.rotation).into(),
+        InstanceRaw::from_matrix(instance.matrix(), instance.color)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normals_stay_perpendicular_under_non_uniform_scale() {
+        let instance = Instance::new(
+            Vector3::new(1.0, 2.0, 3.0),
+            Quaternion::from_axis_angle(Vector3::new(1.0, 1.0, 0.0).normalize(), Deg(35.0)),
+        )
+        .with_scale(Vector3::new(2.0, 1.0, 0.5));
+        let raw = InstanceRaw::from(&instance);
+        let object = raw.matrix();
+        let normal = Matrix3::from(raw.normal);
+
+        // Surface of a slanted plane, its normal and two tangents
+        let surface_normal = Vector3::new(1.0, 1.0, 1.0).normalize();
+        let tangents = [Vector3::new(1.0, -1.0, 0.0), Vector3::new(1.0, 1.0, -2.0)];
+
+        let transformed_normal = normal * surface_normal;
+
+        for tangent in tangents {
+            assert_eq!(surface_normal.dot(tangent), 0.0);
+
+            let transformed_tangent = (object * tangent.extend(0.0)).truncate();
+
+            assert!(
+                transformed_normal
+                    .normalize()
+                    .dot(transformed_tangent.normalize())
+                    .abs()
+                    < 1e-5
+            );
         }
+
+        // The plain linear part would tilt the normal off the surface
+        let linear = Matrix3::from_cols(
+            object.x.truncate(),
+            object.y.truncate(),
+            object.z.truncate(),
+        );
+        let naive_normal = (linear * surface_normal).normalize();
+        let tangent = (object * tangents[0].extend(0.0)).truncate().normalize();
+
+        assert!(naive_normal.dot(tangent).abs() > 0.1);
     }
 }
@@ -0,0 +1,520 @@
+use std::hash::Hasher;
+use std::ops::Range;
+
+use crate::hash::{StateHash, StateHasher};
+
+use crate::model::instance::{Instance, InstanceRaw};
+
+const INSTANCE_SIZE: wgpu::BufferAddress =
+    std::mem::size_of::<InstanceRaw>() as wgpu::BufferAddress;
+const INITIAL_CAPACITY: usize = 64;
+
+// Vertex buffer of instance data that is rewritten every frame and
+// reallocated to the next power of two when it runs out of room. The GPU
+// buffer is created on the first write.
+pub(crate) struct DynamicBuffer {
+    label: String,
+    buffer: Option<wgpu::Buffer>,
+    capacity: usize,
+    len: usize,
+}
+
+impl DynamicBuffer {
+    pub(crate) fn new(label: &str, capacity: usize) -> Self {
+        Self {
+            label: String::from(label),
+            buffer: None,
+            capacity: capacity.max(1),
+            len: 0,
+        }
+    }
+
+    fn create_buffer(device: &wgpu::Device, label: &str, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: capacity as wgpu::BufferAddress * INSTANCE_SIZE,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    // Makes room for len instances, returning true if the buffer was
+    // reallocated and has lost its contents
+    pub(crate) fn reserve(&mut self, device: &wgpu::Device, len: usize) -> bool {
+        if !self.grow(len) && self.buffer.is_some() {
+            return false;
+        }
+
+        self.buffer = Some(Self::create_buffer(device, &self.label, self.capacity));
+
+        true
+    }
+
+    // Raises the capacity to fit len instances, returning true if it grew
+    fn grow(&mut self, len: usize) -> bool {
+        if len <= self.capacity {
+            return false;
+        }
+
+        self.capacity = len.next_power_of_two();
+
+        true
+    }
+
+    // Replaces the whole contents
+    pub(crate) fn write(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        instance_data: &[InstanceRaw],
+    ) {
+        self.reserve(device, instance_data.len());
+        self.write_range(queue, 0, instance_data);
+        self.len = instance_data.len();
+    }
+
+    // Overwrites instances starting at offset, which must already fit
+    pub(crate) fn write_range(
+        &mut self,
+        queue: &wgpu::Queue,
+        offset: usize,
+        instance_data: &[InstanceRaw],
+    ) {
+        if let (Some(buffer), false) = (&self.buffer, instance_data.is_empty()) {
+            queue.write_buffer(
+                buffer,
+                offset as wgpu::BufferAddress * INSTANCE_SIZE,
+                bytemuck::cast_slice(instance_data),
+            );
+        }
+    }
+
+    pub(crate) fn set_len(&mut self, len: usize) {
+        self.len = len;
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub(crate) fn slice(&self) -> wgpu::BufferSlice<'_> {
+        self.buffer
+            .as_ref()
+            .expect("Buffer sliced before its first write")
+            .slice(..self.len.max(1) as wgpu::BufferAddress * INSTANCE_SIZE)
+    }
+}
+
+/// Handle to an instance in an [`InstanceBuffer`], invalidated when the
+/// instance is removed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct InstanceId {
+    index: u32,
+    generation: u32,
+}
+
+struct Slot {
+    generation: u32,
+    dense_index: Option<usize>,
+}
+
+/// Instances addressed by stable ids, uploaded to a GPU buffer that grows as
+/// instances are added. Only the instances changed since the last upload are
+/// written.
+pub struct InstanceBuffer {
+    instances: Vec<Instance>,
+    // Instances as of the last snapshot, for interpolated uploads
+    previous: Vec<Instance>,
+    has_snapshot: bool,
+    interpolating: bool,
+    ids: Vec<InstanceId>,
+    slots: Vec<Slot>,
+    free_indices: Vec<u32>,
+    buffer: DynamicBuffer,
+    dirty: Option<Range<usize>>,
+}
+
+impl InstanceBuffer {
+    pub fn new(label: &str) -> Self {
+        Self::with_capacity(label, INITIAL_CAPACITY)
+    }
+
+    pub fn with_capacity(label: &str, capacity: usize) -> Self {
+        Self {
+            instances: Vec::with_capacity(capacity),
+            previous: Vec::new(),
+            has_snapshot: false,
+            interpolating: false,
+            ids: Vec::with_capacity(capacity),
+            slots: Vec::new(),
+            free_indices: Vec::new(),
+            buffer: DynamicBuffer::new(label, capacity),
+            dirty: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    /// Number of instances the GPU buffer holds before it is reallocated
+    pub fn capacity(&self) -> usize {
+        self.buffer.capacity()
+    }
+
+    fn mark_all_dirty(&mut self) {
+        if !self.instances.is_empty() {
+            self.dirty = Some(0..self.instances.len());
+        }
+    }
+
+    fn mark_dirty(&mut self, dense_index: usize) {
+        self.dirty = Some(match self.dirty.take() {
+            Some(dirty) => dirty.start.min(dense_index)..dirty.end.max(dense_index + 1),
+            None => dense_index..dense_index + 1,
+        });
+    }
+
+    fn dense_index(&self, id: InstanceId) -> Option<usize> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.dense_index)
+    }
+
+    pub fn insert(&mut self, instance: Instance) -> InstanceId {
+        let dense_index = self.instances.len();
+
+        let id = match self.free_indices.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.dense_index = Some(dense_index);
+
+                InstanceId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    dense_index: Some(dense_index),
+                });
+
+                InstanceId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        };
+
+        self.instances.push(instance);
+        self.previous.push(instance);
+        self.ids.push(id);
+        self.mark_dirty(dense_index);
+
+        id
+    }
+
+    pub fn remove(&mut self, id: InstanceId) -> Option<Instance> {
+        let dense_index = self.dense_index(id)?;
+
+        let slot = &mut self.slots[id.index as usize];
+        slot.dense_index = None;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_indices.push(id.index);
+
+        // The last instance moves into the hole
+        self.ids.swap_remove(dense_index);
+        let instance = self.instances.swap_remove(dense_index);
+        self.previous.swap_remove(dense_index);
+
+        if let Some(moved) = self.ids.get(dense_index) {
+            self.slots[moved.index as usize].dense_index = Some(dense_index);
+            self.mark_dirty(dense_index);
+        }
+
+        Some(instance)
+    }
+
+    pub fn contains(&self, id: InstanceId) -> bool {
+        self.dense_index(id).is_some()
+    }
+
+    pub fn get(&self, id: InstanceId) -> Option<&Instance> {
+        self.dense_index(id)
+            .map(|dense_index| &self.instances[dense_index])
+    }
+
+    /// Marks the instance to be uploaded again
+    pub fn get_mut(&mut self, id: InstanceId) -> Option<&mut Instance> {
+        let dense_index = self.dense_index(id)?;
+
+        self.mark_dirty(dense_index);
+        self.interpolating |= self.has_snapshot;
+
+        Some(&mut self.instances[dense_index])
+    }
+
+    /// Replaces the instance, drawing it straight at its new state rather
+    /// than blending from the old one. Returns false for removed ids.
+    pub fn teleport(&mut self, id: InstanceId, instance: Instance) -> bool {
+        let Some(dense_index) = self.dense_index(id) else {
+            return false;
+        };
+
+        self.mark_dirty(dense_index);
+        self.interpolating |= self.has_snapshot;
+        self.instances[dense_index] = instance;
+        self.previous[dense_index] = instance;
+
+        true
+    }
+
+    pub fn clear(&mut self) {
+        for id in self.ids.drain(..) {
+            let slot = &mut self.slots[id.index as usize];
+            slot.dense_index = None;
+            slot.generation = slot.generation.wrapping_add(1);
+            self.free_indices.push(id.index);
+        }
+
+        self.instances.clear();
+        self.previous.clear();
+        self.dirty = None;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (InstanceId, &Instance)> {
+        self.ids.iter().copied().zip(self.instances.iter())
+    }
+
+    /// Marks every instance to be uploaded again
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (InstanceId, &mut Instance)> {
+        self.mark_all_dirty();
+        self.interpolating |= self.has_snapshot;
+
+        self.ids.iter().copied().zip(self.instances.iter_mut())
+    }
+
+    /// Writes changed instances to the GPU buffer, reallocating it if the
+    /// instances no longer fit
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let len = self.instances.len();
+        let reallocated = self.buffer.reserve(device, len);
+
+        if let Some(dirty) = self.take_dirty(reallocated) {
+            let instance_data = self.instances[dirty.clone()]
+                .iter()
+                .map(InstanceRaw::from)
+                .collect::<Vec<_>>();
+
+            self.buffer.write_range(queue, dirty.start, &instance_data);
+        }
+
+        self.buffer.set_len(len);
+    }
+
+    // Range of instances to write, all of them when the GPU buffer was
+    // reallocated and lost its contents
+    fn take_dirty(&mut self, reallocated: bool) -> Option<Range<usize>> {
+        let len = self.instances.len();
+        let dirty = self.dirty.take();
+
+        if reallocated {
+            return Some(0..len);
+        }
+
+        dirty.map(|dirty| dirty.start.min(len)..dirty.end.min(len))
+    }
+
+    // Records the current instances as the state interpolated from
+    pub(crate) fn snapshot(&mut self) {
+        // The buffer holds blended data that needs replacing
+        if self.interpolating {
+            self.mark_all_dirty();
+            self.interpolating = false;
+        }
+
+        self.previous.clone_from(&self.instances);
+        self.has_snapshot = true;
+    }
+
+    // Uploads instances blended between the snapshot and their current state,
+    // falling back to a plain upload when nothing moved since the snapshot
+    pub(crate) fn upload_interpolated(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        alpha: f32,
+    ) {
+        if !self.interpolating {
+            return self.upload(device, queue);
+        }
+
+        let len = self.instances.len();
+        let instance_data = self
+            .previous
+            .iter()
+            .zip(&self.instances)
+            .map(|(previous, current)| InstanceRaw::from(&previous.interpolate(current, alpha)))
+            .collect::<Vec<_>>();
+
+        self.buffer.reserve(device, len);
+        self.buffer.write_range(queue, 0, &instance_data);
+        self.buffer.set_len(len);
+        self.dirty = None;
+    }
+
+    /// Instance data as of the last upload, for binding to vertex slot 1
+    pub fn slice(&self) -> wgpu::BufferSlice<'_> {
+        self.buffer.slice()
+    }
+
+    /// Number of instances as of the last upload
+    pub fn uploaded_len(&self) -> u32 {
+        self.buffer.len() as u32
+    }
+}
+
+impl StateHash for InstanceId {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u32(self.index);
+        hasher.write_u32(self.generation);
+    }
+}
+
+impl StateHash for InstanceBuffer {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_usize(self.len());
+
+        for (id, instance) in self.iter() {
+            id.hash_state(hasher);
+            instance.hash_state(hasher);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::prelude::*;
+    use cgmath::{Quaternion, Vector3};
+
+    use super::*;
+
+    fn at(x: f32) -> Instance {
+        Instance::new(Vector3::new(x, 0.0, 0.0), Quaternion::one())
+    }
+
+    fn x(instances: &InstanceBuffer, id: InstanceId) -> Option<f32> {
+        instances.get(id).map(|instance| instance.position().x)
+    }
+
+    #[test]
+    fn ids_stay_stable_after_swap_remove() {
+        let mut instances = InstanceBuffer::new("Test");
+        let ids = (0..4)
+            .map(|i| instances.insert(at(i as f32)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(instances.remove(ids[0]).map(|i| i.position().x), Some(0.0));
+        assert_eq!(instances.len(), 3);
+        assert_eq!(x(&instances, ids[1]), Some(1.0));
+        assert_eq!(x(&instances, ids[2]), Some(2.0));
+        assert_eq!(x(&instances, ids[3]), Some(3.0));
+
+        instances
+            .get_mut(ids[3])
+            .unwrap()
+            .set_position(Vector3::unit_y());
+        assert_eq!(instances.get(ids[3]).unwrap().position(), Vector3::unit_y());
+
+        let ordered = instances.iter().map(|(id, _)| id).collect::<Vec<_>>();
+        assert_eq!(ordered, [ids[3], ids[1], ids[2]]);
+    }
+
+    #[test]
+    fn stale_ids_are_rejected() {
+        let mut instances = InstanceBuffer::new("Test");
+        let old = instances.insert(at(1.0));
+
+        instances.remove(old);
+
+        // The slot is reused under a new generation
+        let new = instances.insert(at(2.0));
+
+        assert_ne!(old, new);
+        assert!(!instances.contains(old));
+        assert!(instances.get(old).is_none());
+        assert!(instances.get_mut(old).is_none());
+        assert!(!instances.teleport(old, at(3.0)));
+        assert!(instances.remove(old).is_none());
+        assert_eq!(x(&instances, new), Some(2.0));
+
+        instances.clear();
+        assert!(!instances.contains(new));
+        assert!(instances.is_empty());
+    }
+
+    #[test]
+    fn dirty_range_covers_the_written_slots() {
+        let mut instances = InstanceBuffer::new("Test");
+        let ids = (0..5)
+            .map(|i| instances.insert(at(i as f32)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(instances.take_dirty(false), Some(0..5));
+        assert_eq!(instances.take_dirty(false), None);
+
+        instances.get(ids[2]);
+        assert_eq!(instances.take_dirty(false), None);
+
+        instances.get_mut(ids[3]);
+        assert_eq!(instances.take_dirty(false), Some(3..4));
+
+        instances.teleport(ids[1], at(10.0));
+        instances.get_mut(ids[3]);
+        assert_eq!(instances.take_dirty(false), Some(1..4));
+
+        // The last instance moves into the hole
+        instances.remove(ids[1]);
+        assert_eq!(instances.take_dirty(false), Some(1..2));
+
+        // Nothing moves when the last instance goes
+        instances.remove(ids[3]);
+        assert_eq!(instances.take_dirty(false), None);
+
+        instances.iter_mut().count();
+        assert_eq!(instances.take_dirty(false), Some(0..3));
+    }
+
+    #[test]
+    fn capacity_grows_without_losing_data() {
+        let mut instances = InstanceBuffer::with_capacity("Test", 4);
+        let ids = (0..10)
+            .map(|i| instances.insert(at(i as f32)))
+            .collect::<Vec<_>>();
+
+        instances.take_dirty(false);
+        instances.get_mut(ids[9]);
+
+        assert_eq!(instances.capacity(), 4);
+        assert!(instances.buffer.grow(instances.len()));
+        assert_eq!(instances.capacity(), 16);
+        assert!(!instances.buffer.grow(instances.len()));
+
+        // A reallocated buffer is written in full
+        assert_eq!(instances.take_dirty(true), Some(0..10));
+
+        for (i, id) in ids.iter().enumerate() {
+            assert_eq!(x(&instances, *id), Some(i as f32));
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use crate::texture;
 
+/// Diffuse and normal textures bound together for the model pipeline
 pub struct Material {
     pub name: String,
     pub diffuse_texture: texture::Texture,
@@ -1,7 +1,14 @@
+/// GPU buffers for a single mesh of a model, with a CPU copy of its geometry
 pub struct Mesh {
     pub name: String,
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
     pub num_elements: u32,
+    /// Model space vertex positions kept on the CPU for collision fitting and
+    /// triangle queries
+    pub positions: Vec<[f32; 3]>,
+    /// Triangle list indices into `positions`
+    pub indices: Vec<u32>,
+    /// Index into the owning model's materials
     pub material: usize,
 }
@@ -1,23 +1,35 @@
+//! Models, meshes, materials and the render pass extensions that draw them
+
 use std::ops::Range;
 
 pub mod instance;
+pub mod instance_buffer;
 pub mod material;
 pub mod mesh;
+pub mod registry;
 
 pub use material::Material;
 pub use mesh::Mesh;
+pub use registry::ModelRegistry;
 
+/// A vertex type that can describe its own buffer layout
 pub trait Vertex {
     fn get_buffer_layout<'a>() -> wgpu::VertexBufferLayout<'a>;
 }
 
+/// A loaded model, each mesh references one of the materials
 pub struct Model {
     pub meshes: Vec<Mesh>,
     pub materials: Vec<Material>,
 }
 
+/// Identifies a model in a [`ModelRegistry`], the default is the context's object model
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ModelHandle(pub(crate) usize);
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+/// Vertex format used by loaded models
 pub struct ModelVertex {
     pub position: [f32; 3],
     pub texture_coordinates: [f32; 2],
@@ -64,6 +76,7 @@ impl Vertex for ModelVertex {
     }
 }
 
+/// Draws models with the lit model pipeline bound
 pub trait DrawModel<'a> {
     fn draw_mesh(
         &mut self,
@@ -94,6 +107,14 @@ pub trait DrawModel<'a> {
         camera_bind_group: &'a wgpu::BindGroup,
         light_bind_group: &'a wgpu::BindGroup,
     );
+    fn draw_model_instanced_with_material(
+        &mut self,
+        model: &'a Model,
+        material: &'a Material,
+        instances: Range<u32>,
+        camera_bind_group: &'a wgpu::BindGroup,
+        light_bind_group: &'a wgpu::BindGroup,
+    );
 }
 
 impl<'a, 'b> DrawModel<'b> for wgpu::RenderPass<'a>
@@ -153,8 +174,28 @@ where
             );
         }
     }
+
+    fn draw_model_instanced_with_material(
+        &mut self,
+        model: &'b Model,
+        material: &'b Material,
+        instances: Range<u32>,
+        camera_bind_group: &'b wgpu::BindGroup,
+        light_bind_group: &'b wgpu::BindGroup,
+    ) {
+        for mesh in &model.meshes {
+            self.draw_mesh_instanced(
+                mesh,
+                material,
+                instances.clone(),
+                camera_bind_group,
+                light_bind_group,
+            );
+        }
+    }
 }
 
+/// Draws models with the light pipeline bound
 pub trait DrawLight<'a> {
     fn draw_light_mesh(
         &mut self,
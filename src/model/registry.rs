@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use crate::model::{Model, ModelHandle};
+
+/// Loaded models addressed by [`ModelHandle`]s, the default handle refers to
+/// the first model registered
+#[derive(Default)]
+pub struct ModelRegistry {
+    models: Vec<Model>,
+    names: HashMap<String, ModelHandle>,
+}
+
+impl ModelRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.models.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.models.is_empty()
+    }
+
+    pub fn insert(&mut self, model: Model) -> ModelHandle {
+        self.models.push(model);
+
+        ModelHandle(self.models.len() - 1)
+    }
+
+    /// Registers the model so it can be found by name, later models with the
+    /// same name shadow earlier ones
+    pub fn insert_named(&mut self, name: &str, model: Model) -> ModelHandle {
+        let handle = self.insert(model);
+
+        self.names.insert(String::from(name), handle);
+
+        handle
+    }
+
+    pub fn find(&self, name: &str) -> Option<ModelHandle> {
+        self.names.get(name).copied()
+    }
+
+    pub fn get(&self, handle: ModelHandle) -> Option<&Model> {
+        self.models.get(handle.0)
+    }
+
+    pub fn get_mut(&mut self, handle: ModelHandle) -> Option<&mut Model> {
+        self.models.get_mut(handle.0)
+    }
+
+    /// Swaps the model behind an existing handle, returning the old model.
+    /// Names bound to the handle referred to the old model, so they no longer
+    /// find it.
+    pub fn replace(&mut self, handle: ModelHandle, model: Model) -> Option<Model> {
+        let existing = self.models.get_mut(handle.0)?;
+
+        self.names.retain(|_, bound| *bound != handle);
+
+        Some(std::mem::replace(existing, model))
+    }
+
+    pub fn handles(&self) -> impl Iterator<Item = ModelHandle> {
+        (0..self.models.len()).map(ModelHandle)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ModelHandle, &Model)> {
+        self.models
+            .iter()
+            .enumerate()
+            .map(|(index, model)| (ModelHandle(index), model))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model() -> Model {
+        Model {
+            meshes: Vec::new(),
+            materials: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn replace_unbinds_old_name() {
+        let mut registry = ModelRegistry::new();
+        let cube = registry.insert_named("cube.obj", model());
+        let sphere = registry.insert_named("sphere.obj", model());
+
+        assert!(registry.replace(cube, model()).is_some());
+        assert_eq!(registry.find("cube.obj"), None);
+        assert_eq!(registry.find("sphere.obj"), Some(sphere));
+
+        assert!(registry.replace(ModelHandle(2), model()).is_none());
+    }
+}
@@ -0,0 +1,139 @@
+// Keys sorted by time, blended linearly between and held past either end
+fn sample<T: Copy>(keys: &[(f32, T)], t: f32, blend: impl Fn(T, T, f32) -> T) -> T {
+    let next = keys.partition_point(|(time, _)| *time <= t);
+
+    match (keys.get(next.wrapping_sub(1)), keys.get(next)) {
+        (Some(&(start, from)), Some(&(end, to))) => blend(from, to, (t - start) / (end - start)),
+        (Some(&(_, value)), None) | (None, Some(&(_, value))) => value,
+        (None, None) => unreachable!("Curves always have a key"),
+    }
+}
+
+fn insert<T>(keys: &mut Vec<(f32, T)>, t: f32, value: T) {
+    let t = t.clamp(0.0, 1.0);
+    let index = keys.partition_point(|(time, _)| *time <= t);
+
+    keys.insert(index, (t, value));
+}
+
+/// A value over a particle's life, from 0 when it spawns to 1 when it dies,
+/// blending linearly between keys
+#[derive(Clone, Debug, PartialEq)]
+pub struct Curve {
+    keys: Vec<(f32, f32)>,
+}
+
+impl Curve {
+    /// Blends from `start` to `end` over the whole life
+    pub fn new(start: f32, end: f32) -> Self {
+        Self {
+            keys: vec![(0.0, start), (1.0, end)],
+        }
+    }
+
+    pub fn constant(value: f32) -> Self {
+        Self {
+            keys: vec![(0.0, value)],
+        }
+    }
+
+    /// Adds a key at `t` in [0, 1], after any key already there
+    pub fn with_key(mut self, t: f32, value: f32) -> Self {
+        insert(&mut self.keys, t, value);
+        self
+    }
+
+    pub fn sample(&self, t: f32) -> f32 {
+        sample(&self.keys, t, |from, to, alpha| from + (to - from) * alpha)
+    }
+}
+
+/// An RGBA colour over a particle's life, from 0 when it spawns to 1 when it
+/// dies, blending linearly between keys
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gradient {
+    keys: Vec<(f32, [f32; 4])>,
+}
+
+impl Gradient {
+    /// Blends from `start` to `end` over the whole life
+    pub fn new(start: [f32; 4], end: [f32; 4]) -> Self {
+        Self {
+            keys: vec![(0.0, start), (1.0, end)],
+        }
+    }
+
+    pub fn constant(color: [f32; 4]) -> Self {
+        Self {
+            keys: vec![(0.0, color)],
+        }
+    }
+
+    /// Adds a key at `t` in [0, 1], after any key already there
+    pub fn with_key(mut self, t: f32, color: [f32; 4]) -> Self {
+        insert(&mut self.keys, t, color);
+        self
+    }
+
+    pub fn sample(&self, t: f32) -> [f32; 4] {
+        sample(&self.keys, t, |from, to, alpha| {
+            [0, 1, 2, 3].map(|i| from[i] + (to[i] - from[i]) * alpha)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::instance::WHITE;
+
+    #[test]
+    fn curves_blend_between_keys_and_hold_past_the_ends() {
+        let curve = Curve::new(1.0, 3.0).with_key(0.5, 5.0);
+
+        assert_eq!(curve.sample(0.0), 1.0);
+        assert_eq!(curve.sample(0.5), 5.0);
+        assert_eq!(curve.sample(1.0), 3.0);
+        assert_eq!(curve.sample(0.25), 3.0);
+        assert_eq!(curve.sample(0.75), 4.0);
+        assert_eq!(curve.sample(-1.0), 1.0);
+        assert_eq!(curve.sample(2.0), 3.0);
+
+        let constant = Curve::constant(2.0);
+
+        for t in [-1.0, 0.0, 0.5, 1.0, 2.0] {
+            assert_eq!(constant.sample(t), 2.0);
+        }
+    }
+
+    #[test]
+    fn keys_are_clamped_and_ordered() {
+        // Keys past [0, 1] land on the ends, after the keys already there
+        let curve = Curve::new(0.0, 1.0).with_key(-1.0, 4.0).with_key(2.0, 8.0);
+
+        assert_eq!(curve.sample(0.0), 4.0);
+        assert_eq!(curve.sample(0.5), 2.5);
+        assert_eq!(curve.sample(1.0), 8.0);
+
+        // The later of two keys at the same time wins from then on
+        let step = Curve::new(0.0, 0.0).with_key(0.5, 1.0).with_key(0.5, 2.0);
+
+        assert_eq!(step.sample(0.25), 0.5);
+        assert_eq!(step.sample(0.5), 2.0);
+        assert_eq!(step.sample(0.75), 1.0);
+    }
+
+    #[test]
+    fn gradients_blend_every_channel() {
+        let gradient = Gradient::new([0.0, 1.0, 0.0, 1.0], [1.0, 0.0, 0.0, 0.0])
+            .with_key(0.5, [1.0, 1.0, 1.0, 1.0]);
+
+        assert_eq!(gradient.sample(0.0), [0.0, 1.0, 0.0, 1.0]);
+        assert_eq!(gradient.sample(0.25), [0.5, 1.0, 0.5, 1.0]);
+        assert_eq!(gradient.sample(0.5), [1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(gradient.sample(0.75), [1.0, 0.5, 0.5, 0.5]);
+        assert_eq!(gradient.sample(-1.0), [0.0, 1.0, 0.0, 1.0]);
+        assert_eq!(gradient.sample(2.0), [1.0, 0.0, 0.0, 0.0]);
+        assert_eq!(Gradient::constant(WHITE).sample(0.3), WHITE);
+    }
+}
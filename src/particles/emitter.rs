@@ -0,0 +1,598 @@
+use std::f32::consts::TAU;
+use std::ops::Range;
+
+use cgmath::prelude::*;
+use cgmath::{Quaternion, Vector3};
+
+use super::curve::{Curve, Gradient};
+use crate::forces::{ForceField, PointMass};
+use crate::hash::{StateHash, StateHasher};
+use crate::model::instance::{InstanceRaw, WHITE};
+use crate::model::{Model, ModelHandle};
+use crate::random::Rng;
+
+// Emitter defaults, shared with GPU emitters
+const RATE_DEFAULT: f32 = 100.0;
+pub(super) const LIFETIME_DEFAULT: Range<f32> = 1.0..2.0;
+pub(super) const SPEED_DEFAULT: Range<f32> = 1.0..2.0;
+pub(super) const SIZE_DEFAULT: f32 = 0.05;
+const MAX_PARTICLES_DEFAULT: usize = 10_000;
+
+/// A particle alive in an [`Emitter`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Particle {
+    pub position: Vector3<f32>,
+    pub velocity: Vector3<f32>,
+    /// Seconds since it spawned
+    pub age: f32,
+    /// Seconds it lives for
+    pub lifetime: f32,
+    // Position as of the last snapshot, for interpolated drawing
+    previous: Vector3<f32>,
+}
+
+impl Particle {
+    /// How far through its lifetime it is, in [0, 1]
+    pub fn life(&self) -> f32 {
+        if self.lifetime > 0.0 {
+            (self.age / self.lifetime).min(1.0)
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Triangles of a model to spawn particles on, each picked in proportion to
+/// its area
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeshSurface {
+    triangles: Vec<[Vector3<f32>; 3]>,
+    // Running total of the triangles' areas
+    areas: Vec<f32>,
+}
+
+impl MeshSurface {
+    pub fn new(model: &Model) -> Self {
+        Self::from_triangles(model.triangles().map(|(_, triangle)| triangle).collect())
+    }
+
+    pub fn from_triangles(triangles: Vec<[Vector3<f32>; 3]>) -> Self {
+        let mut total = 0.0;
+        let areas = triangles
+            .iter()
+            .map(|[a, b, c]| {
+                total += (b - a).cross(c - a).magnitude() * 0.5;
+                total
+            })
+            .collect();
+
+        Self { triangles, areas }
+    }
+
+    pub fn area(&self) -> f32 {
+        self.areas.last().copied().unwrap_or(0.0)
+    }
+
+    // Each triangle with the running total of areas up to and including it
+    pub(super) fn triangles(&self) -> impl Iterator<Item = ([Vector3<f32>; 3], f32)> + '_ {
+        self.triangles
+            .iter()
+            .copied()
+            .zip(self.areas.iter().copied())
+    }
+
+    // Uniform point on the surface with the normal of its triangle, none if
+    // the surface has no area
+    fn sample(&self, rng: &mut Rng) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        let total = self.area();
+
+        if total <= 0.0 {
+            return None;
+        }
+
+        let target = rng.next_f32() * total;
+        let index = self
+            .areas
+            .partition_point(|area| *area <= target)
+            .min(self.triangles.len() - 1);
+        let [a, b, c] = self.triangles[index];
+
+        // Folding the unit square in half keeps points uniform on the triangle
+        let (mut u, mut v) = (rng.next_f32(), rng.next_f32());
+
+        if u + v > 1.0 {
+            u = 1.0 - u;
+            v = 1.0 - v;
+        }
+
+        Some((
+            a + (b - a) * u + (c - a) * v,
+            (b - a).cross(c - a).normalize(),
+        ))
+    }
+}
+
+/// Where particles spawn about their emitter and which way they set off, in
+/// the emitter's frame
+#[derive(Clone, Debug, PartialEq)]
+pub enum EmitterShape {
+    /// From the emitter's position in every direction
+    Point,
+    /// Anywhere inside the sphere, heading away from its centre
+    Sphere { radius: f32 },
+    /// From a disc of `radius` across the y axis, heading within `angle`
+    /// radians of the axis
+    Cone { angle: f32, radius: f32 },
+    /// From the surface of a model placed at the emitter, heading along its
+    /// normals
+    Mesh(MeshSurface),
+}
+
+// Uniform on the unit sphere
+fn random_direction(rng: &mut Rng) -> Vector3<f32> {
+    let z = rng.range_f32(-1.0..1.0);
+    let (sin, cos) = (rng.next_f32() * TAU).sin_cos();
+    let radius = (1.0 - z * z).max(0.0).sqrt();
+
+    Vector3::new(radius * cos, radius * sin, z)
+}
+
+impl EmitterShape {
+    // Spawn offset and heading of a new particle
+    fn sample(&self, rng: &mut Rng) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        match self {
+            EmitterShape::Point => Some((Vector3::zero(), random_direction(rng))),
+            EmitterShape::Sphere { radius } => {
+                let direction = random_direction(rng);
+
+                Some((direction * *radius * rng.next_f32().cbrt(), direction))
+            }
+            EmitterShape::Cone { angle, radius } => {
+                // Uniform over the cap of directions within the angle
+                let cos = 1.0 - rng.next_f32() * (1.0 - angle.cos());
+                let sin = (1.0 - cos * cos).max(0.0).sqrt();
+                let (turn_sin, turn_cos) = (rng.next_f32() * TAU).sin_cos();
+                let direction = Vector3::new(sin * turn_cos, cos, sin * turn_sin);
+
+                let distance = radius * rng.next_f32().sqrt();
+                let (turn_sin, turn_cos) = (rng.next_f32() * TAU).sin_cos();
+                let offset = Vector3::new(distance * turn_cos, 0.0, distance * turn_sin);
+
+                Some((offset, direction))
+            }
+            EmitterShape::Mesh(surface) => surface.sample(rng),
+        }
+    }
+}
+
+/// Spawns particles at a steady rate and in bursts, and holds them while
+/// they live. Add it to a [`super::ParticleSystem`] to step and draw it, and
+/// attach force fields to its [`super::EmitterId`] to push its particles.
+#[derive(Clone, Debug)]
+pub struct Emitter {
+    shape: EmitterShape,
+    position: Vector3<f32>,
+    rotation: Quaternion<f32>,
+    rate: f32,
+    lifetime: Range<f32>,
+    speed: Range<f32>,
+    mass: f32,
+    size: Curve,
+    color: Gradient,
+    model: ModelHandle,
+    max_particles: usize,
+    emitting: bool,
+    particles: Vec<Particle>,
+    // Part of a particle carried between steps at the spawn rate
+    pending: f32,
+    bursts: usize,
+}
+
+impl Emitter {
+    pub fn new(shape: EmitterShape) -> Self {
+        Self {
+            shape,
+            position: Vector3::zero(),
+            rotation: Quaternion::one(),
+            rate: RATE_DEFAULT,
+            lifetime: LIFETIME_DEFAULT,
+            speed: SPEED_DEFAULT,
+            mass: 1.0,
+            size: Curve::constant(SIZE_DEFAULT),
+            color: Gradient::constant(WHITE),
+            model: ModelHandle::default(),
+            max_particles: MAX_PARTICLES_DEFAULT,
+            emitting: true,
+            particles: Vec::new(),
+            pending: 0.0,
+            bursts: 0,
+        }
+    }
+
+    pub fn with_position(mut self, position: Vector3<f32>) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: Quaternion<f32>) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Particles spawned per second while emitting
+    pub fn with_rate(mut self, rate: f32) -> Self {
+        self.rate = rate.max(0.0);
+        self
+    }
+
+    /// Seconds each particle lives, picked uniformly from the range
+    pub fn with_lifetime(mut self, lifetime: Range<f32>) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+
+    /// Speed each particle sets off at, picked uniformly from the range
+    pub fn with_speed(mut self, speed: Range<f32>) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Mass of each particle, dividing the forces on it
+    pub fn with_mass(mut self, mass: f32) -> Self {
+        self.mass = mass;
+        self
+    }
+
+    /// Uniform scale of the model over each particle's life
+    pub fn with_size(mut self, size: Curve) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Tint of the model over each particle's life
+    pub fn with_color(mut self, color: Gradient) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Model drawn for each particle, the object model by default
+    pub fn with_model(mut self, model: ModelHandle) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// Most particles alive at once, spawns past it are dropped
+    pub fn with_max_particles(mut self, max_particles: usize) -> Self {
+        self.max_particles = max_particles;
+        self
+    }
+
+    pub fn shape(&self) -> &EmitterShape {
+        &self.shape
+    }
+
+    pub fn set_shape(&mut self, shape: EmitterShape) {
+        self.shape = shape;
+    }
+
+    pub fn position(&self) -> Vector3<f32> {
+        self.position
+    }
+
+    /// Moves where new particles spawn, live particles stay put
+    pub fn set_position(&mut self, position: Vector3<f32>) {
+        self.position = position;
+    }
+
+    pub fn rotation(&self) -> Quaternion<f32> {
+        self.rotation
+    }
+
+    pub fn set_rotation(&mut self, rotation: Quaternion<f32>) {
+        self.rotation = rotation;
+    }
+
+    pub fn rate(&self) -> f32 {
+        self.rate
+    }
+
+    pub fn set_rate(&mut self, rate: f32) {
+        self.rate = rate.max(0.0);
+    }
+
+    pub fn model(&self) -> ModelHandle {
+        self.model
+    }
+
+    /// Whether particles spawn at the rate, bursts spawn regardless
+    pub fn is_emitting(&self) -> bool {
+        self.emitting
+    }
+
+    pub fn set_emitting(&mut self, emitting: bool) {
+        self.emitting = emitting;
+    }
+
+    /// Spawns `count` extra particles in the next step
+    pub fn burst(&mut self, count: usize) {
+        self.bursts += count;
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    /// Removes every live particle
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    // Ages and retires particles, moves the rest under the fields, then
+    // spawns the particles due this step
+    pub(super) fn step(&mut self, fields: &[&dyn ForceField], rng: &mut Rng, dt: f32) {
+        for particle in &mut self.particles {
+            particle.age += dt;
+        }
+
+        self.particles
+            .retain(|particle| particle.age < particle.lifetime);
+
+        // Semi-implicit Euler, as for bodies
+        for particle in &mut self.particles {
+            if self.mass > 0.0 && !fields.is_empty() {
+                let point = PointMass {
+                    position: particle.position,
+                    velocity: particle.velocity,
+                    mass: self.mass,
+                };
+                let force = fields
+                    .iter()
+                    .fold(Vector3::zero(), |force, field| force + field.force(&point));
+
+                particle.velocity += force / self.mass * dt;
+            }
+
+            particle.position += particle.velocity * dt;
+        }
+
+        let mut count = std::mem::take(&mut self.bursts);
+
+        if self.emitting {
+            self.pending += self.rate * dt;
+
+            let due = self.pending.floor();
+
+            self.pending -= due;
+            count += due as usize;
+        }
+
+        for _ in 0..count.min(self.max_particles.saturating_sub(self.particles.len())) {
+            self.spawn(rng, dt);
+        }
+    }
+
+    fn spawn(&mut self, rng: &mut Rng, dt: f32) {
+        let Some((offset, direction)) = self.shape.sample(rng) else {
+            return;
+        };
+
+        let velocity = self.rotation.rotate_vector(direction) * rng.range_f32(self.speed.clone());
+
+        // Spread spawns through the step so steady streams don't leave in
+        // clumps
+        let head_start = rng.next_f32() * dt;
+        let position = self.position + self.rotation.rotate_vector(offset) + velocity * head_start;
+
+        self.particles.push(Particle {
+            position,
+            velocity,
+            age: head_start,
+            lifetime: rng.range_f32(self.lifetime.clone()),
+            previous: position,
+        });
+    }
+
+    // Records particle positions as the state interpolated from
+    pub(super) fn snapshot(&mut self) {
+        for particle in &mut self.particles {
+            particle.previous = particle.position;
+        }
+    }
+
+    // Particles blended between the snapshot and their current position,
+    // sized and tinted for their age
+    pub(crate) fn instance_data(&self, alpha: f32) -> impl Iterator<Item = InstanceRaw> + '_ {
+        self.particles.iter().map(move |particle| {
+            let life = particle.life();
+            let position = particle.previous.lerp(particle.position, alpha);
+
+            InstanceRaw::from_position_scale(
+                position,
+                self.size.sample(life),
+                self.color.sample(life),
+            )
+        })
+    }
+}
+
+impl StateHash for Particle {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        self.position.hash_state(hasher);
+        self.velocity.hash_state(hasher);
+        self.age.hash_state(hasher);
+        self.lifetime.hash_state(hasher);
+    }
+}
+
+impl StateHash for Emitter {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        self.position.hash_state(hasher);
+        self.emitting.hash_state(hasher);
+        self.rate.hash_state(hasher);
+        self.pending.hash_state(hasher);
+        self.bursts.hash_state(hasher);
+        self.particles.hash_state(hasher);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn steady(rate: f32) -> Emitter {
+        Emitter::new(EmitterShape::Point)
+            .with_rate(rate)
+            .with_lifetime(100.0..100.0)
+    }
+
+    fn hash_of(emitter: &Emitter) -> u64 {
+        let mut hasher = StateHasher::new();
+
+        hasher.update(emitter);
+        std::hash::Hasher::finish(&hasher)
+    }
+
+    #[test]
+    fn spawns_follow_the_rate_carrying_fractions() {
+        let mut emitter = steady(10.0);
+        let mut rng = Rng::new(1, 0);
+
+        // A quarter of a particle per step
+        for step in 1..=40 {
+            emitter.step(&[], &mut rng, 0.025);
+            assert_eq!(emitter.len(), step / 4);
+        }
+
+        emitter.set_emitting(false);
+        emitter.step(&[], &mut rng, 1.0);
+        assert_eq!(emitter.len(), 10);
+
+        // Bursts spawn whether or not the emitter is emitting, once
+        emitter.burst(3);
+        emitter.burst(2);
+        emitter.step(&[], &mut rng, 0.025);
+        assert_eq!(emitter.len(), 15);
+
+        emitter.step(&[], &mut rng, 0.025);
+        assert_eq!(emitter.len(), 15);
+    }
+
+    #[test]
+    fn particles_retire_at_their_lifetime() {
+        let mut emitter = steady(0.0).with_lifetime(0.5..0.5);
+        let mut rng = Rng::new(2, 0);
+
+        emitter.burst(5);
+        emitter.step(&[], &mut rng, 0.01);
+        emitter.step(&[], &mut rng, 0.45);
+        assert_eq!(emitter.len(), 5);
+
+        emitter.step(&[], &mut rng, 0.06);
+        assert!(emitter.is_empty());
+    }
+
+    #[test]
+    fn spawns_past_the_cap_are_dropped() {
+        let mut emitter = steady(1000.0).with_max_particles(5);
+        let mut rng = Rng::new(3, 0);
+
+        emitter.burst(8);
+        emitter.step(&[], &mut rng, 0.1);
+        assert_eq!(emitter.len(), 5);
+
+        emitter.step(&[], &mut rng, 0.1);
+        assert_eq!(emitter.len(), 5);
+    }
+
+    #[test]
+    fn fields_push_particles_by_force_over_mass() {
+        let mut emitter = steady(0.0).with_mass(2.0);
+        let mut rng = Rng::new(4, 0);
+        let down = |_: &PointMass| Vector3::new(0.0, -2.0, 0.0);
+
+        emitter.burst(3);
+        emitter.step(&[], &mut rng, 0.0);
+
+        let before = emitter.particles().to_vec();
+
+        emitter.step(&[&down], &mut rng, 0.5);
+
+        for (before, after) in before.iter().zip(emitter.particles()) {
+            let velocity = before.velocity - Vector3::new(0.0, 0.5, 0.0);
+
+            assert!((after.velocity - velocity).magnitude() < 1e-6);
+            assert!((after.position - (before.position + velocity * 0.5)).magnitude() < 1e-6);
+        }
+
+        // Massless particles ignore fields
+        let mut emitter = steady(0.0).with_mass(0.0);
+
+        emitter.burst(1);
+        emitter.step(&[], &mut rng, 0.0);
+
+        let before = emitter.particles()[0].velocity;
+
+        emitter.step(&[&down], &mut rng, 0.5);
+        assert_eq!(emitter.particles()[0].velocity, before);
+    }
+
+    #[test]
+    fn mesh_surfaces_skip_zero_area_triangles() {
+        let degenerate = [Vector3::new(100.0, 0.0, 0.0); 3];
+        let line = [
+            Vector3::new(100.0, 0.0, 0.0),
+            Vector3::new(101.0, 0.0, 0.0),
+            Vector3::new(102.0, 0.0, 0.0),
+        ];
+        let floor = [Vector3::zero(), Vector3::unit_z(), Vector3::unit_x()];
+        let wall = [Vector3::zero(), Vector3::unit_x(), Vector3::unit_y()];
+        let surface = MeshSurface::from_triangles(vec![degenerate, floor, line, wall, degenerate]);
+        let mut rng = Rng::new(5, 0);
+
+        assert_eq!(surface.area(), 1.0);
+
+        for _ in 0..1000 {
+            let (position, normal) = surface.sample(&mut rng).unwrap();
+
+            assert!(position.x <= 1.0, "Sampled a degenerate triangle");
+            assert!(normal == Vector3::unit_y() || normal == Vector3::unit_z());
+        }
+
+        assert_eq!(
+            MeshSurface::from_triangles(vec![degenerate, line]).sample(&mut rng),
+            None
+        );
+        assert_eq!(
+            MeshSurface::from_triangles(Vec::new()).sample(&mut rng),
+            None
+        );
+    }
+
+    #[test]
+    fn hash_covers_emission_state() {
+        let emitter = steady(10.0);
+        let hash = hash_of(&emitter);
+
+        let mut stopped = emitter.clone();
+        stopped.set_emitting(false);
+        assert_ne!(hash_of(&stopped), hash);
+
+        let mut faster = emitter.clone();
+        faster.set_rate(20.0);
+        assert_ne!(hash_of(&faster), hash);
+
+        let mut bursting = emitter.clone();
+        bursting.burst(1);
+        assert_ne!(hash_of(&bursting), hash);
+
+        assert_eq!(hash_of(&emitter.clone()), hash);
+    }
+}
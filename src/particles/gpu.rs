@@ -0,0 +1,732 @@
+use std::ops::Range;
+
+use cgmath::prelude::*;
+use cgmath::{Quaternion, Vector3};
+
+use wgpu::util::DeviceExt;
+
+use super::curve::{Curve, Gradient};
+use super::emitter::{EmitterShape, LIFETIME_DEFAULT, SIZE_DEFAULT, SPEED_DEFAULT};
+use crate::forces::{Attractor, Drag, Falloff, Gravity, Vortex};
+use crate::model::instance::WHITE;
+use crate::texture;
+use crate::window::pipeline::{create_compute_pipeline, create_render_pipeline};
+
+const COMPUTE_SHADER_STR: &str = include_str!("../shaders/particles_compute.wgsl");
+const RENDER_SHADER_STR: &str = include_str!("../shaders/particles.wgsl");
+
+// Must match the shaders
+const WORKGROUP_SIZE: u32 = 64;
+const CURVE_SAMPLES: usize = 16;
+const PARTICLE_SIZE: wgpu::BufferAddress = std::mem::size_of::<[f32; 8]>() as wgpu::BufferAddress;
+
+/// Most attractors and vortices a [`GpuEmitter`] takes
+pub const MAX_GPU_FIELDS: usize = 8;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct FieldRaw {
+    center: [f32; 4],
+    axis: [f32; 4],
+    softening: [f32; 4],
+    kinds: [u32; 4],
+}
+
+// Emitter and forces as the compute shader reads them
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ParamsRaw {
+    position: [f32; 4],
+    rotation: [f32; 4],
+    shape: [f32; 4],
+    ranges: [f32; 4],
+    gravity: [f32; 4],
+    drag: [f32; 4],
+    counts: [u32; 4],
+    fields: [FieldRaw; MAX_GPU_FIELDS],
+}
+
+// Colour and size over life as the render shader reads them
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct AppearanceRaw {
+    colors: [[f32; 4]; CURVE_SAMPLES],
+    sizes: [[f32; 4]; CURVE_SAMPLES / 4],
+}
+
+fn falloff_kind(falloff: Falloff) -> u32 {
+    match falloff {
+        Falloff::Constant => 0,
+        Falloff::Linear => 1,
+        Falloff::InverseSquare => 2,
+    }
+}
+
+impl From<&Attractor> for FieldRaw {
+    fn from(attractor: &Attractor) -> Self {
+        FieldRaw {
+            center: attractor.position.extend(attractor.strength).into(),
+            axis: [0.0, 0.0, 0.0, attractor.radius.min(f32::MAX)],
+            softening: [attractor.softening, 0.0, 0.0, 0.0],
+            kinds: [0, falloff_kind(attractor.falloff), 0, 0],
+        }
+    }
+}
+
+impl From<&Vortex> for FieldRaw {
+    fn from(vortex: &Vortex) -> Self {
+        FieldRaw {
+            center: vortex.center.extend(vortex.strength).into(),
+            axis: vortex
+                .unit_axis()
+                .extend(vortex.radius.min(f32::MAX))
+                .into(),
+            softening: [vortex.softening, 0.0, 0.0, 0.0],
+            kinds: [1, falloff_kind(vortex.falloff), 0, 0],
+        }
+    }
+}
+
+/// A fixed pool of particles simulated entirely on the GPU. Each particle
+/// respawns at the emitter as soon as it dies, so a full pool emits
+/// `capacity` over the mean lifetime particles per second. Forces are
+/// limited to gravity, drag and up to [`MAX_GPU_FIELDS`] attractors and
+/// vortices. Add it with [`crate::Context::add_gpu_particles`].
+#[derive(Clone, Debug)]
+pub struct GpuEmitter {
+    shape: EmitterShape,
+    capacity: u32,
+    position: Vector3<f32>,
+    rotation: Quaternion<f32>,
+    lifetime: Range<f32>,
+    speed: Range<f32>,
+    mass: f32,
+    size: Curve,
+    color: Gradient,
+    gravity: Gravity,
+    drag: Drag,
+    attractors: Vec<Attractor>,
+    vortices: Vec<Vortex>,
+    emitting: bool,
+}
+
+impl GpuEmitter {
+    /// Emitter of `capacity` particles, with no gravity or drag
+    pub fn new(shape: EmitterShape, capacity: u32) -> Self {
+        Self {
+            shape,
+            capacity,
+            position: Vector3::zero(),
+            rotation: Quaternion::one(),
+            lifetime: LIFETIME_DEFAULT,
+            speed: SPEED_DEFAULT,
+            mass: 1.0,
+            size: Curve::constant(SIZE_DEFAULT),
+            color: Gradient::constant(WHITE),
+            gravity: Gravity::new(Vector3::zero()),
+            drag: Drag::new(0.0, 0.0),
+            attractors: Vec::new(),
+            vortices: Vec::new(),
+            emitting: true,
+        }
+    }
+
+    pub fn with_position(mut self, position: Vector3<f32>) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: Quaternion<f32>) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Seconds each particle lives, picked uniformly from the range
+    pub fn with_lifetime(mut self, lifetime: Range<f32>) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+
+    /// Speed each particle sets off at, picked uniformly from the range
+    pub fn with_speed(mut self, speed: Range<f32>) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Mass of each particle, dividing the forces on it
+    pub fn with_mass(mut self, mass: f32) -> Self {
+        self.mass = mass;
+        self
+    }
+
+    /// Half width of each particle's quad over its life, sampled at 16
+    /// points
+    pub fn with_size(mut self, size: Curve) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Colour over each particle's life, sampled at 16 points
+    pub fn with_color(mut self, color: Gradient) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_gravity(mut self, gravity: Gravity) -> Self {
+        self.gravity = gravity;
+        self
+    }
+
+    pub fn with_drag(mut self, drag: Drag) -> Self {
+        self.drag = drag;
+        self
+    }
+
+    /// Panics past [`MAX_GPU_FIELDS`] attractors and vortices
+    pub fn with_attractor(mut self, attractor: Attractor) -> Self {
+        assert!(self.field_count() < MAX_GPU_FIELDS, "Too many GPU fields");
+
+        self.attractors.push(attractor);
+        self
+    }
+
+    /// Panics past [`MAX_GPU_FIELDS`] attractors and vortices
+    pub fn with_vortex(mut self, vortex: Vortex) -> Self {
+        assert!(self.field_count() < MAX_GPU_FIELDS, "Too many GPU fields");
+
+        self.vortices.push(vortex);
+        self
+    }
+
+    fn field_count(&self) -> usize {
+        self.attractors.len() + self.vortices.len()
+    }
+
+    pub fn shape(&self) -> &EmitterShape {
+        &self.shape
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    pub fn position(&self) -> Vector3<f32> {
+        self.position
+    }
+
+    /// Moves where new particles spawn, live particles stay put
+    pub fn set_position(&mut self, position: Vector3<f32>) {
+        self.position = position;
+    }
+
+    pub fn rotation(&self) -> Quaternion<f32> {
+        self.rotation
+    }
+
+    pub fn set_rotation(&mut self, rotation: Quaternion<f32>) {
+        self.rotation = rotation;
+    }
+
+    /// Whether dead particles respawn, they all respawn together once it
+    /// resumes
+    pub fn is_emitting(&self) -> bool {
+        self.emitting
+    }
+
+    pub fn set_emitting(&mut self, emitting: bool) {
+        self.emitting = emitting;
+    }
+
+    pub fn set_gravity(&mut self, gravity: Gravity) {
+        self.gravity = gravity;
+    }
+
+    pub fn set_drag(&mut self, drag: Drag) {
+        self.drag = drag;
+    }
+
+    /// Attractors added to the emitter, to move or retune
+    pub fn attractors_mut(&mut self) -> &mut [Attractor] {
+        &mut self.attractors
+    }
+
+    /// Vortices added to the emitter, to move or retune
+    pub fn vortices_mut(&mut self) -> &mut [Vortex] {
+        &mut self.vortices
+    }
+
+    fn params(&self, dt: f32, seed: u32) -> ParamsRaw {
+        let (shape_kind, shape) = match &self.shape {
+            EmitterShape::Point => (0, [0.0; 4]),
+            EmitterShape::Sphere { radius } => (1, [*radius, 0.0, 0.0, 0.0]),
+            EmitterShape::Cone { angle, radius } => (2, [*angle, *radius, 0.0, 0.0]),
+            EmitterShape::Mesh(_) => (3, [0.0; 4]),
+        };
+
+        let mut fields = [bytemuck::Zeroable::zeroed(); MAX_GPU_FIELDS];
+
+        for (raw, field) in fields.iter_mut().zip(
+            self.attractors
+                .iter()
+                .map(FieldRaw::from)
+                .chain(self.vortices.iter().map(FieldRaw::from)),
+        ) {
+            *raw = field;
+        }
+
+        ParamsRaw {
+            position: self.position.extend(dt).into(),
+            rotation: self.rotation.v.extend(self.rotation.s).into(),
+            shape,
+            ranges: [
+                self.lifetime.start,
+                self.lifetime.end,
+                self.speed.start,
+                self.speed.end,
+            ],
+            gravity: self
+                .gravity
+                .acceleration
+                .extend(self.mass.max(f32::EPSILON))
+                .into(),
+            drag: [self.drag.linear, self.drag.quadratic, 0.0, 0.0],
+            counts: [
+                shape_kind,
+                self.field_count() as u32,
+                seed,
+                self.emitting as u32,
+            ],
+            fields,
+        }
+    }
+
+    fn appearance(&self) -> AppearanceRaw {
+        let life = |index: usize| index as f32 / (CURVE_SAMPLES - 1) as f32;
+        let sizes: [f32; CURVE_SAMPLES] =
+            std::array::from_fn(|index| self.size.sample(life(index)));
+
+        AppearanceRaw {
+            colors: std::array::from_fn(|index| self.color.sample(life(index))),
+            sizes: std::array::from_fn(|index| {
+                [0, 1, 2, 3].map(|offset| sizes[index * 4 + offset])
+            }),
+        }
+    }
+
+    // Corners of the mesh's triangles, the first of each carrying the running
+    // total of areas. Other shapes get a placeholder, as bindings can't be
+    // empty.
+    fn triangles(&self) -> Vec<[f32; 4]> {
+        match &self.shape {
+            EmitterShape::Mesh(surface) if surface.area() > 0.0 => surface
+                .triangles()
+                .flat_map(|([a, b, c], area)| [a.extend(area), b.extend(0.0), c.extend(0.0)])
+                .map(Into::into)
+                .collect(),
+            _ => vec![[0.0, 0.0, 0.0, 1.0], [0.0; 4], [0.0; 4]],
+        }
+    }
+
+    // Particles waiting to be born in turn over the mean lifetime, so the
+    // pool starts as a steady stream
+    fn initial_particles(&self) -> Vec<[f32; 8]> {
+        let mean_lifetime = (self.lifetime.start + self.lifetime.end) * 0.5;
+
+        (0..self.capacity)
+            .map(|index| {
+                let delay = index as f32 / self.capacity as f32 * mean_lifetime;
+
+                [0.0, 0.0, 0.0, -delay, 0.0, 0.0, 0.0, 0.0]
+            })
+            .collect()
+    }
+}
+
+/// Handle to GPU particles added to a [`crate::Context`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GpuParticlesId(pub(crate) usize);
+
+/// GPU buffers and pipelines simulating and drawing a [`GpuEmitter`]'s
+/// particles. Particles never leave the GPU, so they aren't part of the
+/// state hash and are drawn as of the latest step rather than interpolated.
+pub struct GpuParticles {
+    emitter: GpuEmitter,
+    particle_buffer: wgpu::Buffer,
+    params_buffer: wgpu::Buffer,
+    appearance_buffer: wgpu::Buffer,
+    compute_bind_group: wgpu::BindGroup,
+    appearance_bind_group: wgpu::BindGroup,
+    compute_pipeline: wgpu::ComputePipeline,
+    render_pipeline: wgpu::RenderPipeline,
+}
+
+impl GpuParticles {
+    // Validation errors are raised through the device, callers should scope
+    // them. The capacity is clamped to what the device can bind and dispatch.
+    pub(crate) fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        mut emitter: GpuEmitter,
+    ) -> Self {
+        let limits = device.limits();
+        let max_capacity = (limits.max_storage_buffer_binding_size as u64 / PARTICLE_SIZE)
+            .min(limits.max_compute_workgroups_per_dimension as u64 * WORKGROUP_SIZE as u64);
+
+        emitter.capacity = emitter.capacity.clamp(1, max_capacity as u32);
+
+        let particle_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("GpuParticleBuffer"),
+            contents: bytemuck::cast_slice(&emitter.initial_particles()),
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::VERTEX
+                | wgpu::BufferUsages::COPY_SRC,
+        });
+
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("GpuParticleParamsBuffer"),
+            contents: bytemuck::cast_slice(&[emitter.params(0.0, 0)]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let appearance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("GpuParticleAppearanceBuffer"),
+            contents: bytemuck::cast_slice(&[emitter.appearance()]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let triangle_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("GpuParticleTriangleBuffer"),
+            contents: bytemuck::cast_slice(&emitter.triangles()),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        // Compute
+        let compute_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("GpuParticleComputeBindGroupLayout"),
+            });
+
+        let compute_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &compute_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: particle_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: triangle_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("GpuParticleComputeBindGroup"),
+        });
+
+        let compute_pipeline = create_compute_pipeline(
+            device,
+            &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("GpuParticleComputePipelineLayout"),
+                bind_group_layouts: &[&compute_bind_group_layout],
+                push_constant_ranges: &[],
+            }),
+            wgpu::ShaderModuleDescriptor {
+                label: Some("GpuParticleComputeShader"),
+                source: wgpu::ShaderSource::Wgsl(COMPUTE_SHADER_STR.into()),
+            },
+        );
+
+        // Render
+        let appearance_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("GpuParticleAppearanceBindGroupLayout"),
+            });
+
+        let appearance_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &appearance_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: appearance_buffer.as_entire_binding(),
+            }],
+            label: Some("GpuParticleAppearanceBindGroup"),
+        });
+
+        let render_pipeline = create_render_pipeline(
+            device,
+            &device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("GpuParticleRenderPipelineLayout"),
+                bind_group_layouts: &[camera_bind_group_layout, &appearance_bind_group_layout],
+                push_constant_ranges: &[],
+            }),
+            format,
+            Some(texture::Texture::DEPTH_FORMAT),
+            &[wgpu::VertexBufferLayout {
+                array_stride: PARTICLE_SIZE,
+                step_mode: wgpu::VertexStepMode::Instance,
+                attributes: &wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4],
+            }],
+            wgpu::ShaderModuleDescriptor {
+                label: Some("GpuParticleShader"),
+                source: wgpu::ShaderSource::Wgsl(RENDER_SHADER_STR.into()),
+            },
+        );
+
+        Self {
+            emitter,
+            particle_buffer,
+            params_buffer,
+            appearance_buffer,
+            compute_bind_group,
+            appearance_bind_group,
+            compute_pipeline,
+            render_pipeline,
+        }
+    }
+
+    /// The emitter, its capacity clamped to what the device supports
+    pub fn emitter(&self) -> &GpuEmitter {
+        &self.emitter
+    }
+
+    /// Changes take effect from the next step
+    pub fn emitter_mut(&mut self) -> &mut GpuEmitter {
+        &mut self.emitter
+    }
+
+    /// Particle storage buffer, eight floats each: position and age, then
+    /// velocity and lifetime. Age is negative until a particle is first
+    /// born and at least its lifetime once it's dead.
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.particle_buffer
+    }
+
+    // Integrates the particles by `dt` seconds, respawning dead ones from
+    // `seed`
+    pub(crate) fn step(&self, device: &wgpu::Device, queue: &wgpu::Queue, dt: f32, seed: u32) {
+        queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::cast_slice(&[self.emitter.params(dt, seed)]),
+        );
+        queue.write_buffer(
+            &self.appearance_buffer,
+            0,
+            bytemuck::cast_slice(&[self.emitter.appearance()]),
+        );
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("GpuParticleEncoder"),
+        });
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("GpuParticleComputePass"),
+            });
+
+            compute_pass.set_pipeline(&self.compute_pipeline);
+            compute_pass.set_bind_group(0, &self.compute_bind_group, &[]);
+            compute_pass.dispatch_workgroups(self.emitter.capacity.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    pub(crate) fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'a wgpu::BindGroup,
+    ) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.appearance_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.particle_buffer.slice(..));
+        render_pass.draw(0..6, 0..self.emitter.capacity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_std::task::block_on;
+
+    use super::*;
+    use crate::adapter::AdapterConfig;
+    use crate::config::EngineConfig;
+    use crate::error::EngineError;
+    use crate::headless::tests::headless;
+    use crate::headless::HeadlessContext;
+    use crate::simulation::Simulation;
+    use crate::window::Context;
+
+    const LIFETIME: f32 = 0.1;
+    const SPEED: f32 = 2.0;
+
+    struct Empty;
+
+    impl Simulation for Empty {}
+
+    // Headless context on the software fallback adapter with a fountain
+    // added, none when there's no fallback adapter or it can't compute
+    fn fountain(emitter: GpuEmitter) -> Option<(HeadlessContext, GpuParticlesId)> {
+        let engine_config = EngineConfig::default()
+            .with_seed(5)
+            .with_adapter(AdapterConfig {
+                force_fallback_adapter: true,
+                ..Default::default()
+            });
+        let mut headless = headless(&engine_config)?;
+
+        match block_on(headless.context_mut().add_gpu_particles(emitter)) {
+            Ok(id) => Some((headless, id)),
+            Err(EngineError::Unsupported { .. }) => {
+                eprintln!("No compute shaders on the fallback adapter, skipping");
+                None
+            }
+            Err(error) => panic!("{error}"),
+        }
+    }
+
+    fn emitter() -> GpuEmitter {
+        GpuEmitter::new(EmitterShape::Point, 1000)
+            .with_lifetime(LIFETIME..LIFETIME)
+            .with_speed(SPEED..SPEED)
+    }
+
+    fn read_particles(context: &Context, id: GpuParticlesId) -> Vec<[f32; 8]> {
+        let device = context.device();
+        let buffer = context.gpu_particles(id).unwrap().buffer();
+
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GpuParticleReadbackBuffer"),
+            size: buffer.size(),
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&Default::default());
+        encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, buffer.size());
+        context.queue().submit(std::iter::once(encoder.finish()));
+
+        let slice = staging.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |result| result.unwrap());
+        device.poll(wgpu::Maintain::Wait);
+
+        let particles = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+
+        particles
+    }
+
+    #[test]
+    fn ages_advance_and_particles_respawn() {
+        let Some((mut headless, id)) = fountain(emitter()) else {
+            return;
+        };
+        let dt = headless.context().fixed_timestep().step();
+
+        // Unborn particles wait their turn over the first lifetime
+        let initial = read_particles(headless.context(), id);
+
+        assert_eq!(initial.len(), 1000);
+        assert!(initial.iter().all(|particle| particle[3] <= 0.0));
+
+        for _ in 0..30 {
+            headless.step(&mut Empty);
+        }
+
+        let before = read_particles(headless.context(), id);
+
+        headless.step(&mut Empty);
+
+        let after = read_particles(headless.context(), id);
+
+        let mut aged = 0;
+        let mut respawned = 0;
+
+        for (before, after) in before.iter().zip(&after) {
+            let (age, lifetime) = (after[3], after[7]);
+            let distance = (after[0].powi(2) + after[1].powi(2) + after[2].powi(2)).sqrt();
+
+            // Every particle has been born by now and lives out its lifetime
+            // moving away from the emitter
+            assert_eq!(lifetime, LIFETIME);
+            assert!((0.0..LIFETIME).contains(&age), "age {age}");
+            assert!((distance - SPEED * age).abs() < 1e-3, "distance {distance}");
+
+            if (age - before[3] - dt).abs() < 1e-5 {
+                aged += 1;
+            } else if age < before[3] {
+                respawned += 1;
+            }
+        }
+
+        assert_eq!(aged + respawned, 1000);
+        assert!(aged > 0);
+        assert!(respawned > 0);
+    }
+
+    #[test]
+    fn stopped_emitters_leave_dead_particles() {
+        let Some((mut headless, id)) = fountain(emitter()) else {
+            return;
+        };
+
+        headless
+            .context_mut()
+            .gpu_particles_mut(id)
+            .unwrap()
+            .emitter_mut()
+            .set_emitting(false);
+
+        for _ in 0..30 {
+            headless.step(&mut Empty);
+        }
+
+        let particles = read_particles(headless.context(), id);
+
+        assert!(particles.iter().all(|particle| particle[3] >= particle[7]));
+    }
+}
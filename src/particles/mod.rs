@@ -0,0 +1,191 @@
+//! CPU simulated particles spawned by emitters, pushed by force fields and
+//! drawn as instances of a model, see [`ParticleSystem`]. Larger counts can
+//! be simulated and drawn on the GPU instead, see [`GpuEmitter`].
+
+use std::hash::Hasher;
+
+use crate::forces::{ForceField, ForceTarget, Forces};
+use crate::hash::{StateHash, StateHasher};
+use crate::random::Rng;
+
+mod curve;
+mod emitter;
+mod gpu;
+
+pub use curve::{Curve, Gradient};
+pub use emitter::{Emitter, EmitterShape, MeshSurface, Particle};
+pub use gpu::{GpuEmitter, GpuParticles, GpuParticlesId, MAX_GPU_FIELDS};
+
+/// Handle to an emitter in a [`ParticleSystem`], invalidated when it's
+/// removed. Attach force fields to it to act on its particles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EmitterId {
+    index: u32,
+    generation: u32,
+}
+
+struct Slot {
+    generation: u32,
+    emitter: Option<Emitter>,
+}
+
+/// Emitters and their particles, stepped by the context after the physics
+/// in every fixed step and drawn each frame through the instanced model
+/// path, one batch per model
+#[derive(Default)]
+pub struct ParticleSystem {
+    slots: Vec<Slot>,
+    free_indices: Vec<u32>,
+    len: usize,
+}
+
+impl ParticleSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of emitters
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of live particles across every emitter
+    pub fn particle_count(&self) -> usize {
+        self.iter().map(|(_, emitter)| emitter.len()).sum()
+    }
+
+    pub fn add(&mut self, emitter: Emitter) -> EmitterId {
+        self.len += 1;
+
+        match self.free_indices.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.emitter = Some(emitter);
+
+                EmitterId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    emitter: Some(emitter),
+                });
+
+                EmitterId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    /// Removes an emitter along with its particles
+    pub fn remove(&mut self, id: EmitterId) -> Option<Emitter> {
+        let slot = self
+            .slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)?;
+        let emitter = slot.emitter.take()?;
+
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_indices.push(id.index);
+        self.len -= 1;
+
+        Some(emitter)
+    }
+
+    pub fn contains(&self, id: EmitterId) -> bool {
+        self.get(id).is_some()
+    }
+
+    pub fn get(&self, id: EmitterId) -> Option<&Emitter> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.emitter.as_ref())
+    }
+
+    pub fn get_mut(&mut self, id: EmitterId) -> Option<&mut Emitter> {
+        self.slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.emitter.as_mut())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EmitterId, &Emitter)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.emitter.as_ref().map(|emitter| {
+                (
+                    EmitterId {
+                        index: index as u32,
+                        generation: slot.generation,
+                    },
+                    emitter,
+                )
+            })
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (EmitterId, &mut Emitter)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let generation = slot.generation;
+
+                slot.emitter.as_mut().map(|emitter| {
+                    (
+                        EmitterId {
+                            index: index as u32,
+                            generation,
+                        },
+                        emitter,
+                    )
+                })
+            })
+    }
+
+    /// Advances every emitter by `dt` seconds: retires particles past their
+    /// lifetime, moves the rest under the fields of `forces` attached to
+    /// their emitter, then spawns new ones from `rng`
+    pub fn step(&mut self, forces: &Forces, rng: &mut Rng, dt: f32) {
+        for (id, emitter) in self.iter_mut() {
+            let fields = forces
+                .fields_acting_on(ForceTarget::Emitter(id))
+                .collect::<Vec<&dyn ForceField>>();
+
+            emitter.step(&fields, rng, dt);
+        }
+    }
+
+    // Records particle positions as the state interpolated from
+    pub(crate) fn snapshot(&mut self) {
+        for (_, emitter) in self.iter_mut() {
+            emitter.snapshot();
+        }
+    }
+}
+
+impl StateHash for EmitterId {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u32(self.index);
+        hasher.write_u32(self.generation);
+    }
+}
+
+impl StateHash for ParticleSystem {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_usize(self.len());
+
+        for (id, emitter) in self.iter() {
+            id.hash_state(hasher);
+            emitter.hash_state(hasher);
+        }
+    }
+}
@@ -0,0 +1,462 @@
+use cgmath::prelude::*;
+use cgmath::{Matrix3, Quaternion, Vector3};
+
+use super::collision::Collider;
+use super::material::PhysicsMaterial;
+use crate::geometry::Pose;
+use crate::hash::{StateHash, StateHasher};
+use crate::model::instance_buffer::InstanceId;
+use crate::model::ModelHandle;
+
+/// How a body takes part in the simulation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BodyKind {
+    /// Moved by forces, impulses and contacts
+    Dynamic,
+    /// Moved only by its velocities, unaffected by forces and contacts, e.g.
+    /// a moving platform
+    Kinematic,
+    /// Never moves
+    Static,
+}
+
+/// Inertia tensor of a solid sphere
+pub fn sphere_inertia(mass: f32, radius: f32) -> Matrix3<f32> {
+    Matrix3::from_value(0.4 * mass * radius * radius)
+}
+
+/// Inertia tensor of a solid box
+pub fn cuboid_inertia(mass: f32, half_extents: Vector3<f32>) -> Matrix3<f32> {
+    let size = half_extents * 2.0;
+    let (x2, y2, z2) = (size.x * size.x, size.y * size.y, size.z * size.z);
+
+    Matrix3::from_diagonal(Vector3::new(y2 + z2, x2 + z2, x2 + y2) * (mass / 12.0))
+}
+
+/// Inertia tensor of a solid cylinder along the y axis
+pub fn cylinder_inertia(mass: f32, radius: f32, half_height: f32) -> Matrix3<f32> {
+    let height = half_height * 2.0;
+    let side = mass * (3.0 * radius * radius + height * height) / 12.0;
+
+    Matrix3::from_diagonal(Vector3::new(side, 0.5 * mass * radius * radius, side))
+}
+
+/// Mass, inertia, velocities and accumulated forces of a body, along with its
+/// collider, material and the instance its transform is written to
+#[derive(Clone, Debug)]
+pub struct RigidBody {
+    kind: BodyKind,
+    position: Vector3<f32>,
+    orientation: Quaternion<f32>,
+    linear_velocity: Vector3<f32>,
+    angular_velocity: Vector3<f32>,
+    inverse_mass: f32,
+    // Body space inertia tensor and its inverse
+    inertia: Matrix3<f32>,
+    inverse_inertia: Matrix3<f32>,
+    force: Vector3<f32>,
+    torque: Vector3<f32>,
+    instance: Option<(ModelHandle, InstanceId)>,
+    collider: Option<Collider>,
+    material: PhysicsMaterial,
+    can_sleep: bool,
+    sleeping: bool,
+    // Seconds the body has been below the sleep velocities
+    resting_time: f32,
+}
+
+impl RigidBody {
+    /// A dynamic body, see the `*_inertia` functions for common shapes. Panics
+    /// unless the mass is positive and finite, bodies that shouldn't respond
+    /// to forces are [`RigidBody::kinematic`] or [`RigidBody::fixed`].
+    pub fn dynamic(mass: f32, inertia: Matrix3<f32>) -> Self {
+        assert!(
+            mass > 0.0 && mass.is_finite(),
+            "Dynamic bodies need a positive, finite mass"
+        );
+
+        Self {
+            kind: BodyKind::Dynamic,
+            inverse_mass: 1.0 / mass,
+            inertia,
+            inverse_inertia: inertia.invert().unwrap_or(Matrix3::zero()),
+            ..Self::fixed()
+        }
+    }
+
+    pub fn kinematic() -> Self {
+        Self {
+            kind: BodyKind::Kinematic,
+            ..Self::fixed()
+        }
+    }
+
+    /// A static body with infinite mass
+    pub fn fixed() -> Self {
+        Self {
+            kind: BodyKind::Static,
+            position: Vector3::zero(),
+            orientation: Quaternion::one(),
+            linear_velocity: Vector3::zero(),
+            angular_velocity: Vector3::zero(),
+            inverse_mass: 0.0,
+            inertia: Matrix3::zero(),
+            inverse_inertia: Matrix3::zero(),
+            force: Vector3::zero(),
+            torque: Vector3::zero(),
+            instance: None,
+            collider: None,
+            material: PhysicsMaterial::default(),
+            can_sleep: true,
+            sleeping: false,
+            resting_time: 0.0,
+        }
+    }
+
+    pub fn with_position(mut self, position: Vector3<f32>) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_orientation(mut self, orientation: Quaternion<f32>) -> Self {
+        self.orientation = orientation.normalize();
+        self
+    }
+
+    pub fn with_linear_velocity(mut self, linear_velocity: Vector3<f32>) -> Self {
+        self.linear_velocity = linear_velocity;
+        self
+    }
+
+    pub fn with_angular_velocity(mut self, angular_velocity: Vector3<f32>) -> Self {
+        self.angular_velocity = angular_velocity;
+        self
+    }
+
+    /// Writes the body's transform into this instance every tick
+    pub fn with_instance(mut self, model: ModelHandle, instance: InstanceId) -> Self {
+        self.instance = Some((model, instance));
+        self
+    }
+
+    /// Shape the body collides with, bodies without one pass through everything
+    pub fn with_collider(mut self, collider: Collider) -> Self {
+        self.collider = Some(collider);
+        self
+    }
+
+    pub fn with_material(mut self, material: PhysicsMaterial) -> Self {
+        self.material = material;
+        self
+    }
+
+    /// Whether the body may sleep once it comes to rest, true by default
+    pub fn with_can_sleep(mut self, can_sleep: bool) -> Self {
+        self.can_sleep = can_sleep;
+        self
+    }
+
+    pub fn kind(&self) -> BodyKind {
+        self.kind
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        self.kind == BodyKind::Dynamic
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.kind == BodyKind::Static
+    }
+
+    pub fn position(&self) -> Vector3<f32> {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: Vector3<f32>) {
+        self.position = position;
+        self.wake();
+    }
+
+    pub fn orientation(&self) -> Quaternion<f32> {
+        self.orientation
+    }
+
+    pub fn set_orientation(&mut self, orientation: Quaternion<f32>) {
+        self.orientation = orientation.normalize();
+        self.wake();
+    }
+
+    pub fn pose(&self) -> Pose {
+        Pose::new(self.position, self.orientation)
+    }
+
+    pub fn collider(&self) -> Option<&Collider> {
+        self.collider.as_ref()
+    }
+
+    pub fn set_collider(&mut self, collider: Option<Collider>) {
+        self.collider = collider;
+        self.wake();
+    }
+
+    pub fn material(&self) -> &PhysicsMaterial {
+        &self.material
+    }
+
+    pub fn set_material(&mut self, material: PhysicsMaterial) {
+        self.material = material;
+    }
+
+    pub fn can_sleep(&self) -> bool {
+        self.can_sleep
+    }
+
+    pub fn set_can_sleep(&mut self, can_sleep: bool) {
+        self.can_sleep = can_sleep;
+
+        if !can_sleep {
+            self.wake();
+        }
+    }
+
+    /// Sleeping bodies are skipped by the solver until something touches
+    /// them, or they're moved or pushed
+    pub fn is_sleeping(&self) -> bool {
+        self.sleeping
+    }
+
+    pub fn wake(&mut self) {
+        self.sleeping = false;
+        self.resting_time = 0.0;
+    }
+
+    /// Puts the body to sleep immediately, stopping it
+    pub fn sleep(&mut self) {
+        if self.kind != BodyKind::Dynamic {
+            return;
+        }
+
+        self.sleeping = true;
+        self.linear_velocity = Vector3::zero();
+        self.angular_velocity = Vector3::zero();
+    }
+
+    pub fn linear_velocity(&self) -> Vector3<f32> {
+        self.linear_velocity
+    }
+
+    pub fn set_linear_velocity(&mut self, linear_velocity: Vector3<f32>) {
+        self.linear_velocity = linear_velocity;
+        self.wake();
+    }
+
+    /// World space angular velocity in radians per second
+    pub fn angular_velocity(&self) -> Vector3<f32> {
+        self.angular_velocity
+    }
+
+    pub fn set_angular_velocity(&mut self, angular_velocity: Vector3<f32>) {
+        self.angular_velocity = angular_velocity;
+        self.wake();
+    }
+
+    /// Infinite for static and kinematic bodies
+    pub fn mass(&self) -> f32 {
+        if self.inverse_mass > 0.0 {
+            1.0 / self.inverse_mass
+        } else {
+            f32::INFINITY
+        }
+    }
+
+    pub fn inverse_mass(&self) -> f32 {
+        self.inverse_mass
+    }
+
+    /// Body space inertia tensor
+    pub fn inertia(&self) -> Matrix3<f32> {
+        self.inertia
+    }
+
+    /// Inverse inertia tensor rotated into world space
+    pub fn world_inverse_inertia(&self) -> Matrix3<f32> {
+        let rotation = Matrix3::from(self.orientation);
+
+        rotation * self.inverse_inertia * rotation.transpose()
+    }
+
+    pub fn world_inertia(&self) -> Matrix3<f32> {
+        let rotation = Matrix3::from(self.orientation);
+
+        rotation * self.inertia * rotation.transpose()
+    }
+
+    pub fn instance(&self) -> Option<(ModelHandle, InstanceId)> {
+        self.instance
+    }
+
+    /// Force accumulated since the last step
+    pub fn force(&self) -> Vector3<f32> {
+        self.force
+    }
+
+    pub fn torque(&self) -> Vector3<f32> {
+        self.torque
+    }
+
+    /// Applies a force through the centre of mass until the next step
+    pub fn apply_force(&mut self, force: Vector3<f32>) {
+        self.force += force;
+        self.wake();
+    }
+
+    /// Applies a force at a world space point, which also adds a torque
+    pub fn apply_force_at_point(&mut self, force: Vector3<f32>, point: Vector3<f32>) {
+        self.force += force;
+        self.torque += (point - self.position).cross(force);
+        self.wake();
+    }
+
+    // Adds to the force without waking the body, so bodies resting in a
+    // standing field can sleep
+    pub(crate) fn accumulate_force(&mut self, force: Vector3<f32>) {
+        self.force += force;
+    }
+
+    pub fn apply_torque(&mut self, torque: Vector3<f32>) {
+        self.torque += torque;
+        self.wake();
+    }
+
+    /// Changes the velocity immediately, ignored by non-dynamic bodies
+    pub fn apply_impulse(&mut self, impulse: Vector3<f32>) {
+        self.linear_velocity += impulse * self.inverse_mass;
+        self.wake();
+    }
+
+    pub fn apply_impulse_at_point(&mut self, impulse: Vector3<f32>, point: Vector3<f32>) {
+        self.linear_velocity += impulse * self.inverse_mass;
+        self.angular_velocity +=
+            self.world_inverse_inertia() * (point - self.position).cross(impulse);
+        self.wake();
+    }
+
+    pub fn apply_angular_impulse(&mut self, impulse: Vector3<f32>) {
+        self.angular_velocity += self.world_inverse_inertia() * impulse;
+        self.wake();
+    }
+
+    /// Velocity of a world space point attached to the body
+    pub fn velocity_at_point(&self, point: Vector3<f32>) -> Vector3<f32> {
+        self.linear_velocity + self.angular_velocity.cross(point - self.position)
+    }
+
+    pub fn kinetic_energy(&self) -> f32 {
+        if self.inverse_mass == 0.0 {
+            return 0.0;
+        }
+
+        let linear = 0.5 * self.mass() * self.linear_velocity.magnitude2();
+        let angular = 0.5
+            * self
+                .angular_velocity
+                .dot(self.world_inertia() * self.angular_velocity);
+
+        linear + angular
+    }
+
+    pub fn clear_forces(&mut self) {
+        self.force = Vector3::zero();
+        self.torque = Vector3::zero();
+    }
+
+    // Whether the body moves this step and takes part in contacts as more
+    // than an immovable obstacle
+    pub(crate) fn is_awake(&self) -> bool {
+        self.kind != BodyKind::Static && !self.sleeping
+    }
+
+    // Velocities written back by the solver, which mustn't wake the body
+    pub(crate) fn set_solved_velocities(
+        &mut self,
+        linear_velocity: Vector3<f32>,
+        angular_velocity: Vector3<f32>,
+    ) {
+        self.linear_velocity = linear_velocity;
+        self.angular_velocity = angular_velocity;
+    }
+
+    // Adds `dt` to the time spent resting, or restarts it if the body moves
+    // faster than the given speeds. Returns the time spent resting.
+    pub(crate) fn update_resting_time(&mut self, linear: f32, angular: f32, dt: f32) -> f32 {
+        let resting = self.can_sleep
+            && self.linear_velocity.magnitude2() < linear * linear
+            && self.angular_velocity.magnitude2() < angular * angular;
+
+        self.resting_time = if resting { self.resting_time + dt } else { 0.0 };
+        self.resting_time
+    }
+
+    pub(crate) fn resting_time(&self) -> f32 {
+        self.resting_time
+    }
+
+    // Semi-implicit Euler on the velocities from the accumulated forces
+    pub(crate) fn integrate_velocities(&mut self, gravity: Vector3<f32>, dt: f32) {
+        if self.kind != BodyKind::Dynamic || self.sleeping {
+            return;
+        }
+
+        self.linear_velocity += (gravity + self.force * self.inverse_mass) * dt;
+
+        // Euler's equations, including the gyroscopic term so torque free
+        // bodies precess rather than spin about a fixed axis. The explicit
+        // update only approximates conservation of angular momentum.
+        let inertia = self.world_inertia();
+        let gyroscopic = self.angular_velocity.cross(inertia * self.angular_velocity);
+
+        self.angular_velocity += self.world_inverse_inertia() * (self.torque - gyroscopic) * dt;
+    }
+
+    // Moves the body along its velocities, renormalising the orientation
+    pub(crate) fn integrate_positions(&mut self, dt: f32) {
+        if !self.is_awake() {
+            return;
+        }
+
+        self.position += self.linear_velocity * dt;
+
+        let spin = Quaternion::from_sv(0.0, self.angular_velocity) * self.orientation * 0.5;
+
+        self.orientation = (self.orientation + spin * dt).normalize();
+    }
+}
+
+impl StateHash for RigidBody {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        self.position.hash_state(hasher);
+        self.orientation.hash_state(hasher);
+        self.linear_velocity.hash_state(hasher);
+        self.angular_velocity.hash_state(hasher);
+        self.sleeping.hash_state(hasher);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "positive, finite mass")]
+    fn dynamic_rejects_zero_mass() {
+        RigidBody::dynamic(0.0, Matrix3::identity());
+    }
+
+    #[test]
+    fn dynamic_inverts_mass() {
+        let body = RigidBody::dynamic(4.0, sphere_inertia(4.0, 1.0));
+
+        assert_eq!(body.inverse_mass(), 0.25);
+        assert_eq!(body.mass(), 4.0);
+    }
+}
@@ -0,0 +1,46 @@
+use crate::geometry::Bounds;
+
+/// Index pairs of overlapping bounds, lower index first and sorted. Sweeps
+/// along the axis the bounds' centres are most spread over, so only bounds
+/// overlapping on that axis are compared.
+pub fn sweep_and_prune(bounds: &[Bounds]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+
+    if bounds.len() < 2 {
+        return pairs;
+    }
+
+    let count = bounds.len() as f32;
+    let axis = (0..3)
+        .map(|axis| {
+            let mean = bounds.iter().map(|b| b.center()[axis]).sum::<f32>() / count;
+            let variance = bounds
+                .iter()
+                .map(|b| (b.center()[axis] - mean).powi(2))
+                .sum::<f32>();
+
+            (axis, variance)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap()
+        .0;
+
+    let mut order = (0..bounds.len()).collect::<Vec<_>>();
+    order.sort_by(|a, b| bounds[*a].min[axis].total_cmp(&bounds[*b].min[axis]));
+
+    for (position, &first) in order.iter().enumerate() {
+        let end = bounds[first].max[axis];
+
+        for &second in order[position + 1..]
+            .iter()
+            .take_while(|second| bounds[**second].min[axis] <= end)
+        {
+            if bounds[first].overlaps(&bounds[second]) {
+                pairs.push((first.min(second), first.max(second)));
+            }
+        }
+    }
+
+    pairs.sort_unstable();
+    pairs
+}
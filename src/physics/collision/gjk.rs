@@ -0,0 +1,530 @@
+// GJK distance and EPA penetration queries on the Minkowski difference of two
+// convex shapes, given as a support mapping returning the supporting points
+// of both shapes
+
+use cgmath::prelude::*;
+use cgmath::Vector3;
+
+const MAX_GJK_ITERATIONS: usize = 64;
+const MAX_EPA_ITERATIONS: usize = 64;
+// Squared distance from the origin below which the shapes are overlapping
+const OVERLAP_TOLERANCE: f32 = 1e-10;
+// Relative progress below which GJK has converged
+const GJK_TOLERANCE: f32 = 1e-4;
+// Volume relative to the cube of the longest edge below which a tetrahedron
+// is flat
+const FLAT_TOLERANCE: f32 = 1e-5;
+// Growth of the polytope below which EPA has found the boundary
+const EPA_TOLERANCE: f32 = 1e-4;
+const EPSILON: f32 = 1e-12;
+
+/// Point of the Minkowski difference `a - b` along with the shape points it
+/// came from
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SupportPoint {
+    pub w: Vector3<f32>,
+    pub a: Vector3<f32>,
+    pub b: Vector3<f32>,
+}
+
+pub(crate) enum GjkResult {
+    Separated {
+        distance: f32,
+        point_a: Vector3<f32>,
+        point_b: Vector3<f32>,
+    },
+    // The simplex enclosing or touching the origin, to start EPA from
+    Overlapping(Vec<SupportPoint>),
+}
+
+/// Penetration found by EPA, `normal` points from the first shape to the second
+pub(crate) struct Penetration {
+    pub normal: Vector3<f32>,
+    pub depth: f32,
+    pub point_a: Vector3<f32>,
+    pub point_b: Vector3<f32>,
+}
+
+pub(crate) fn gjk<F>(support: &F, direction: Vector3<f32>) -> GjkResult
+where
+    F: Fn(Vector3<f32>) -> SupportPoint,
+{
+    let direction = if direction.magnitude2() > EPSILON {
+        direction
+    } else {
+        Vector3::unit_x()
+    };
+    let mut simplex = vec![(support(direction), 1.0)];
+
+    for _ in 0..MAX_GJK_ITERATIONS {
+        let closest = combine(&simplex, |point| point.w);
+
+        if simplex.len() == 4 || closest.magnitude2() < OVERLAP_TOLERANCE {
+            return GjkResult::Overlapping(simplex.into_iter().map(|(point, _)| point).collect());
+        }
+
+        let next = support(-closest);
+        let progress = closest.magnitude2() - closest.dot(next.w);
+        let repeated = simplex
+            .iter()
+            .any(|(point, _)| (point.w - next.w).magnitude2() < EPSILON);
+
+        if repeated || progress <= GJK_TOLERANCE * closest.magnitude2() {
+            break;
+        }
+
+        let mut points = simplex.iter().map(|(point, _)| *point).collect::<Vec<_>>();
+        points.push(next);
+
+        let reduced = closest_on_simplex(&points);
+
+        // Rounding can stall just short of convergence, keep the last simplex
+        if reduced.len() < 4
+            && combine(&reduced, |point| point.w).magnitude2() >= closest.magnitude2()
+        {
+            break;
+        }
+
+        simplex = reduced;
+    }
+
+    let closest = combine(&simplex, |point| point.w);
+
+    GjkResult::Separated {
+        distance: closest.magnitude(),
+        point_a: combine(&simplex, |point| point.a),
+        point_b: combine(&simplex, |point| point.b),
+    }
+}
+
+fn combine(
+    simplex: &[(SupportPoint, f32)],
+    field: impl Fn(&SupportPoint) -> Vector3<f32>,
+) -> Vector3<f32> {
+    simplex
+        .iter()
+        .fold(Vector3::zero(), |sum, (point, weight)| {
+            sum + field(point) * *weight
+        })
+}
+
+// Sub-simplex nearest the origin with the barycentric weights of the nearest
+// point, all four points when the origin is inside a tetrahedron
+fn closest_on_simplex(points: &[SupportPoint]) -> Vec<(SupportPoint, f32)> {
+    match points {
+        [a] => vec![(*a, 1.0)],
+        [a, b] => closest_on_segment(*a, *b),
+        [a, b, c] => closest_on_triangle(*a, *b, *c),
+        [a, b, c, d] => closest_on_tetrahedron([*a, *b, *c, *d]),
+        _ => unreachable!("Simplex has at most four points"),
+    }
+}
+
+fn closest_on_segment(a: SupportPoint, b: SupportPoint) -> Vec<(SupportPoint, f32)> {
+    let ab = b.w - a.w;
+    let length2 = ab.magnitude2();
+
+    if length2 < EPSILON {
+        return vec![(a, 1.0)];
+    }
+
+    let t = (-a.w).dot(ab) / length2;
+
+    if t <= 0.0 {
+        vec![(a, 1.0)]
+    } else if t >= 1.0 {
+        vec![(b, 1.0)]
+    } else {
+        vec![(a, 1.0 - t), (b, t)]
+    }
+}
+
+fn closest_on_triangle(
+    a: SupportPoint,
+    b: SupportPoint,
+    c: SupportPoint,
+) -> Vec<(SupportPoint, f32)> {
+    // Voronoi regions of the vertices, then edges, then the face
+    let ab = b.w - a.w;
+    let ac = c.w - a.w;
+    let d1 = ab.dot(-a.w);
+    let d2 = ac.dot(-a.w);
+
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return vec![(a, 1.0)];
+    }
+
+    let d3 = ab.dot(-b.w);
+    let d4 = ac.dot(-b.w);
+
+    if d3 >= 0.0 && d4 <= d3 {
+        return vec![(b, 1.0)];
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        let v = d1 / (d1 - d3);
+        return vec![(a, 1.0 - v), (b, v)];
+    }
+
+    let d5 = ab.dot(-c.w);
+    let d6 = ac.dot(-c.w);
+
+    if d6 >= 0.0 && d5 <= d6 {
+        return vec![(c, 1.0)];
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        let w = d2 / (d2 - d6);
+        return vec![(a, 1.0 - w), (c, w)];
+    }
+
+    let va = d3 * d6 - d5 * d4;
+
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return vec![(b, 1.0 - w), (c, w)];
+    }
+
+    let area = va + vb + vc;
+
+    if area.abs() < EPSILON {
+        // Degenerate triangle, the nearest of its edges
+        return [
+            closest_on_segment(a, b),
+            closest_on_segment(a, c),
+            closest_on_segment(b, c),
+        ]
+        .into_iter()
+        .min_by(|x, y| {
+            combine(x, |point| point.w)
+                .magnitude2()
+                .total_cmp(&combine(y, |point| point.w).magnitude2())
+        })
+        .unwrap();
+    }
+
+    let v = vb / area;
+    let w = vc / area;
+
+    vec![(a, 1.0 - v - w), (b, v), (c, w)]
+}
+
+fn closest_on_tetrahedron(points: [SupportPoint; 4]) -> Vec<(SupportPoint, f32)> {
+    // Each face with the vertex opposite it
+    const FACES: [[usize; 4]; 4] = [[0, 1, 2, 3], [0, 2, 3, 1], [0, 3, 1, 2], [1, 3, 2, 0]];
+
+    let [a, b, c, d] = points.map(|point| point.w);
+    let volume = (b - a).cross(c - a).dot(d - a);
+    let longest = [b - a, c - a, d - a, c - b, d - b, d - c]
+        .iter()
+        .map(|edge| edge.magnitude())
+        .fold(0.0, f32::max);
+    let flat = volume.abs() <= FLAT_TOLERANCE * longest.powi(3);
+
+    let mut closest: Option<(Vec<(SupportPoint, f32)>, f32)> = None;
+
+    for [i, j, k, opposite] in FACES {
+        let origin = points[i].w;
+        let normal = (points[j].w - origin).cross(points[k].w - origin);
+        let origin_side = (-origin).dot(normal);
+        let opposite_side = (points[opposite].w - origin).dot(normal);
+
+        // Only faces with the origin on their far side can hold the nearest point
+        if !flat && origin_side * opposite_side >= 0.0 {
+            continue;
+        }
+
+        let candidate = closest_on_triangle(points[i], points[j], points[k]);
+        let distance = combine(&candidate, |point| point.w).magnitude2();
+
+        if closest.as_ref().is_none_or(|(_, best)| distance < *best) {
+            closest = Some((candidate, distance));
+        }
+    }
+
+    match closest {
+        Some((simplex, _)) => simplex,
+        None => points.into_iter().map(|point| (point, 0.25)).collect(),
+    }
+}
+
+struct Face {
+    indices: [usize; 3],
+    normal: Vector3<f32>,
+    distance: f32,
+}
+
+pub(crate) fn epa<F>(support: &F, simplex: Vec<SupportPoint>) -> Option<Penetration>
+where
+    F: Fn(Vector3<f32>) -> SupportPoint,
+{
+    let mut points = complete_simplex(support, simplex)?;
+
+    // Faces are wound outwards from a point that stays inside the polytope
+    let interior = points
+        .iter()
+        .fold(Vector3::zero(), |sum, point| sum + point.w)
+        / 4.0;
+    let make_face = |points: &[SupportPoint], i: usize, j: usize, k: usize| -> Option<Face> {
+        let (a, b, c) = (points[i].w, points[j].w, points[k].w);
+        let normal = (b - a).cross(c - a);
+        let length = normal.magnitude();
+
+        if length < 1e-10 {
+            return None;
+        }
+
+        let (indices, normal) = if normal.dot(a - interior) >= 0.0 {
+            ([i, j, k], normal / length)
+        } else {
+            ([i, k, j], -normal / length)
+        };
+
+        Some(Face {
+            indices,
+            normal,
+            distance: normal.dot(a),
+        })
+    };
+
+    let mut faces = [[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]]
+        .into_iter()
+        .filter_map(|[i, j, k]| make_face(&points, i, j, k))
+        .collect::<Vec<_>>();
+
+    for _ in 0..MAX_EPA_ITERATIONS {
+        let nearest = faces
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.distance.total_cmp(&b.distance))?
+            .0;
+        let face = &faces[nearest];
+        let next = support(face.normal);
+
+        if next.w.dot(face.normal) - face.distance < EPA_TOLERANCE {
+            break;
+        }
+
+        // Replace the faces the new point can see, stitching it to their horizon
+        let index = points.len();
+        points.push(next);
+
+        let mut horizon: Vec<(usize, usize)> = Vec::new();
+
+        faces.retain(|face| {
+            let visible = face.normal.dot(next.w - points[face.indices[0]].w) > 0.0;
+
+            if visible {
+                let [i, j, k] = face.indices;
+
+                for (from, to) in [(i, j), (j, k), (k, i)] {
+                    match horizon.iter().position(|edge| *edge == (to, from)) {
+                        Some(shared) => {
+                            horizon.swap_remove(shared);
+                        }
+                        None => horizon.push((from, to)),
+                    }
+                }
+            }
+
+            !visible
+        });
+
+        faces.extend(
+            horizon
+                .into_iter()
+                .filter_map(|(from, to)| make_face(&points, from, to, index)),
+        );
+
+        if faces.is_empty() {
+            return None;
+        }
+    }
+
+    let face = faces
+        .iter()
+        .min_by(|a, b| a.distance.total_cmp(&b.distance))?;
+    let [i, j, k] = face.indices.map(|index| points[index]);
+    let (u, v, w) = barycentric(face.normal * face.distance, i.w, j.w, k.w);
+
+    Some(Penetration {
+        normal: face.normal,
+        depth: face.distance.max(0.0),
+        point_a: i.a * u + j.a * v + k.a * w,
+        point_b: i.b * u + j.b * v + k.b * w,
+    })
+}
+
+// Grows a simplex that touches the origin into a tetrahedron, or `None` when
+// the Minkowski difference is flat
+fn complete_simplex<F>(support: &F, mut simplex: Vec<SupportPoint>) -> Option<Vec<SupportPoint>>
+where
+    F: Fn(Vector3<f32>) -> SupportPoint,
+{
+    const AXES: [Vector3<f32>; 6] = [
+        Vector3::new(1.0, 0.0, 0.0),
+        Vector3::new(-1.0, 0.0, 0.0),
+        Vector3::new(0.0, 1.0, 0.0),
+        Vector3::new(0.0, -1.0, 0.0),
+        Vector3::new(0.0, 0.0, 1.0),
+        Vector3::new(0.0, 0.0, -1.0),
+    ];
+
+    if simplex.len() == 1 {
+        let first = simplex[0].w;
+        let point = AXES
+            .iter()
+            .map(|axis| support(*axis))
+            .find(|point| (point.w - first).magnitude2() > 1e-8)?;
+
+        simplex.push(point);
+    }
+
+    if simplex.len() == 2 {
+        let line = (simplex[1].w - simplex[0].w).normalize();
+        // Search around the line for a point off it
+        let least_aligned = AXES
+            .iter()
+            .step_by(2)
+            .min_by(|a, b| a.dot(line).abs().total_cmp(&b.dot(line).abs()))
+            .unwrap();
+        let perpendicular = line.cross(*least_aligned).normalize();
+        let point = (0..6)
+            .map(|step| {
+                let angle = cgmath::Rad(step as f32 * std::f32::consts::FRAC_PI_3);
+                let rotation = cgmath::Quaternion::from_axis_angle(line, angle);
+
+                support(rotation.rotate_vector(perpendicular))
+            })
+            .find(|point| (point.w - simplex[0].w).cross(line).magnitude2() > 1e-8)?;
+
+        simplex.push(point);
+    }
+
+    if simplex.len() == 3 {
+        let normal = (simplex[1].w - simplex[0].w).cross(simplex[2].w - simplex[0].w);
+
+        let point = [support(normal), support(-normal)]
+            .into_iter()
+            .max_by(|a, b| {
+                (a.w - simplex[0].w)
+                    .dot(normal)
+                    .abs()
+                    .total_cmp(&(b.w - simplex[0].w).dot(normal).abs())
+            })
+            .unwrap();
+
+        if (point.w - simplex[0].w).dot(normal).abs() < 1e-8 {
+            return None;
+        }
+
+        simplex.push(point);
+    }
+
+    let [a, b, c, d] = [0, 1, 2, 3].map(|index| simplex[index].w);
+
+    ((b - a).cross(c - a).dot(d - a).abs() > 1e-10).then_some(simplex)
+}
+
+fn barycentric(
+    point: Vector3<f32>,
+    a: Vector3<f32>,
+    b: Vector3<f32>,
+    c: Vector3<f32>,
+) -> (f32, f32, f32) {
+    let ab = b - a;
+    let ac = c - a;
+    let ap = point - a;
+    let (d00, d01, d11) = (ab.dot(ab), ab.dot(ac), ac.dot(ac));
+    let (d20, d21) = (ap.dot(ab), ap.dot(ac));
+    let denominator = d00 * d11 - d01 * d01;
+
+    if denominator.abs() < EPSILON {
+        return (1.0, 0.0, 0.0);
+    }
+
+    let v = (d11 * d20 - d01 * d21) / denominator;
+    let w = (d00 * d21 - d01 * d20) / denominator;
+
+    (1.0 - v - w, v, w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Support mapping of two axis aligned cubes of half extent 1
+    fn cubes(
+        center_a: Vector3<f32>,
+        center_b: Vector3<f32>,
+    ) -> impl Fn(Vector3<f32>) -> SupportPoint {
+        let corner = |center: Vector3<f32>, direction: Vector3<f32>| {
+            center
+                + Vector3::new(
+                    1.0_f32.copysign(direction.x),
+                    1.0_f32.copysign(direction.y),
+                    1.0_f32.copysign(direction.z),
+                )
+        };
+
+        move |direction| {
+            let a = corner(center_a, direction);
+            let b = corner(center_b, -direction);
+
+            SupportPoint { w: a - b, a, b }
+        }
+    }
+
+    #[test]
+    fn separated_distance_and_closest_points() {
+        let support = cubes(Vector3::zero(), Vector3::new(3.0, 0.5, 0.0));
+
+        let GjkResult::Separated {
+            distance,
+            point_a,
+            point_b,
+        } = gjk(&support, Vector3::new(-3.0, -0.5, 0.0))
+        else {
+            panic!("cubes 1 apart overlap");
+        };
+
+        assert!((distance - 1.0).abs() < 1e-4);
+        assert!((point_a.x - 1.0).abs() < 1e-4);
+        assert!((point_b.x - 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn overlapping_penetration_depth_and_normal() {
+        let support = cubes(Vector3::zero(), Vector3::new(1.5, 0.2, 0.1));
+
+        let GjkResult::Overlapping(simplex) = gjk(&support, Vector3::new(-1.5, -0.2, -0.1)) else {
+            panic!("cubes 0.5 deep are separated");
+        };
+
+        let penetration = epa(&support, simplex).unwrap();
+
+        assert!((penetration.normal - Vector3::unit_x()).magnitude() < 1e-3);
+        assert!((penetration.depth - 0.5).abs() < 1e-3);
+        assert!((penetration.point_a.x - 1.0).abs() < 1e-3);
+        assert!((penetration.point_b.x - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn touching_origin_still_finds_penetration() {
+        // Centres coincide, so GJK starts from a degenerate direction
+        let support = cubes(Vector3::zero(), Vector3::zero());
+
+        let GjkResult::Overlapping(simplex) = gjk(&support, Vector3::zero()) else {
+            panic!("coincident cubes are separated");
+        };
+
+        let penetration = epa(&support, simplex).unwrap();
+
+        assert!((penetration.depth - 2.0).abs() < 1e-3);
+        assert!((penetration.normal.magnitude() - 1.0).abs() < 1e-4);
+    }
+}
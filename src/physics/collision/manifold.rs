@@ -0,0 +1,108 @@
+use cgmath::prelude::*;
+use cgmath::Vector3;
+
+// Most points a manifold keeps, enough to hold a face resting on a face
+const MAX_POINTS: usize = 4;
+
+/// One point where two shapes touch
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ContactPoint {
+    /// Point on the surface of the first shape
+    pub point_a: Vector3<f32>,
+    /// Point on the surface of the second shape
+    pub point_b: Vector3<f32>,
+    /// Overlap along the normal, positive when the shapes interpenetrate
+    pub depth: f32,
+}
+
+impl ContactPoint {
+    /// Midway between the two surface points
+    pub fn position(&self) -> Vector3<f32> {
+        (self.point_a + self.point_b) * 0.5
+    }
+}
+
+/// Contact points between two shapes sharing one normal
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContactManifold {
+    /// Unit normal pointing from the first shape towards the second
+    pub normal: Vector3<f32>,
+    pub points: Vec<ContactPoint>,
+}
+
+impl ContactManifold {
+    pub(crate) fn new(normal: Vector3<f32>) -> Self {
+        Self {
+            normal,
+            points: Vec::new(),
+        }
+    }
+
+    pub fn max_depth(&self) -> f32 {
+        self.points
+            .iter()
+            .map(|point| point.depth)
+            .fold(f32::NEG_INFINITY, f32::max)
+    }
+
+    // The same manifold seen from the second shape
+    pub(crate) fn flipped(mut self) -> Self {
+        self.normal = -self.normal;
+
+        for point in &mut self.points {
+            std::mem::swap(&mut point.point_a, &mut point.point_b);
+        }
+
+        self
+    }
+
+    // Keeps the deepest point and those spanning the largest area around it
+    pub(crate) fn reduce(&mut self) {
+        if self.points.len() <= MAX_POINTS {
+            return;
+        }
+
+        let normal = self.normal;
+        let points = std::mem::take(&mut self.points);
+        let position = |index: usize| points[index].position();
+        let area = |a: usize, b: usize, c: usize| {
+            (position(b) - position(a))
+                .cross(position(c) - position(a))
+                .dot(normal)
+        };
+
+        let deepest = (0..points.len())
+            .max_by(|a, b| points[*a].depth.total_cmp(&points[*b].depth))
+            .unwrap();
+        let furthest = (0..points.len())
+            .max_by(|a, b| {
+                let distance = |i: usize| (position(i) - position(deepest)).magnitude2();
+                distance(*a).total_cmp(&distance(*b))
+            })
+            .unwrap();
+        let widest = (0..points.len())
+            .max_by(|a, b| {
+                area(deepest, furthest, *a)
+                    .abs()
+                    .total_cmp(&area(deepest, furthest, *b).abs())
+            })
+            .unwrap();
+        // The last point adds the most area outside the triangle so far
+        let triangle = area(deepest, furthest, widest).abs();
+        let outside = |i: usize| {
+            area(deepest, furthest, i).abs()
+                + area(furthest, widest, i).abs()
+                + area(widest, deepest, i).abs()
+                - triangle
+        };
+        let last = (0..points.len())
+            .max_by(|a, b| outside(*a).total_cmp(&outside(*b)))
+            .unwrap();
+
+        let mut kept = vec![deepest, furthest, widest, last];
+        kept.sort_unstable();
+        kept.dedup();
+
+        self.points = kept.into_iter().map(|index| points[index]).collect();
+    }
+}
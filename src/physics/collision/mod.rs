@@ -0,0 +1,24 @@
+//! Colliders and contact generation, see [`collide`] and
+//! [`crate::physics::PhysicsWorld::contacts`]
+
+mod broadphase;
+mod gjk;
+mod manifold;
+mod narrowphase;
+mod shape;
+
+pub use broadphase::sweep_and_prune;
+pub use manifold::{ContactManifold, ContactPoint};
+pub use narrowphase::collide;
+pub use shape::{Collider, ConvexHull, FitShape, Shape};
+
+use super::BodyId;
+
+/// Contact manifold between two bodies' colliders, the normal points from
+/// `body_a` to `body_b`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Contact {
+    pub body_a: BodyId,
+    pub body_b: BodyId,
+    pub manifold: ContactManifold,
+}
@@ -0,0 +1,815 @@
+use cgmath::prelude::*;
+use cgmath::Vector3;
+
+use super::gjk::{self, GjkResult, SupportPoint};
+use super::manifold::{ContactManifold, ContactPoint};
+use super::shape::{box_axes, Collider, ConvexHull, Shape};
+use crate::geometry::{closest_point_on_segment, closest_points_on_segments, Pose};
+
+// Squared distances below this leave no direction to take a normal from
+const EPSILON: f32 = 1e-10;
+// Contact points closer than this are merged
+const MERGE_DISTANCE: f32 = 1e-3;
+// Edge axes must beat the best face axis by this much to be chosen, which
+// keeps resting boxes on stable face contacts
+const EDGE_RELATIVE_TOLERANCE: f32 = 0.95;
+const EDGE_ABSOLUTE_TOLERANCE: f32 = 1e-3;
+// How closely a capsule end's normal must match to add it to the manifold
+const END_CAP_ALIGNMENT: f32 = 0.95;
+
+/// Contact manifold between two colliders on bodies at the given poses, with
+/// the normal pointing from `a` to `b`, or `None` if they don't touch
+pub fn collide(
+    a: &Collider,
+    body_pose_a: &Pose,
+    b: &Collider,
+    body_pose_b: &Pose,
+) -> Option<ContactManifold> {
+    let pose_a = a.world_pose(body_pose_a);
+    let pose_b = b.world_pose(body_pose_b);
+
+    collide_shapes(
+        &WorldShape::new(a.shape(), &pose_a),
+        &WorldShape::new(b.shape(), &pose_b),
+    )
+}
+
+// A shape placed in the world. Spheres and capsules are a point or segment
+// core with a radius, the others have no radius.
+#[derive(Clone, Copy)]
+enum WorldShape<'a> {
+    Sphere {
+        center: Vector3<f32>,
+        radius: f32,
+    },
+    Box {
+        center: Vector3<f32>,
+        axes: [Vector3<f32>; 3],
+        half_extents: Vector3<f32>,
+    },
+    Capsule {
+        start: Vector3<f32>,
+        end: Vector3<f32>,
+        radius: f32,
+    },
+    Hull {
+        hull: &'a ConvexHull,
+        pose: Pose,
+    },
+}
+
+impl<'a> WorldShape<'a> {
+    fn new(shape: &'a Shape, pose: &Pose) -> Self {
+        match shape {
+            Shape::Sphere { radius } => WorldShape::Sphere {
+                center: pose.position,
+                radius: *radius,
+            },
+            Shape::Aabb { half_extents } => WorldShape::Box {
+                center: pose.position,
+                axes: [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()],
+                half_extents: *half_extents,
+            },
+            Shape::Obb { half_extents } => WorldShape::Box {
+                center: pose.position,
+                axes: box_axes(pose.orientation),
+                half_extents: *half_extents,
+            },
+            Shape::Capsule {
+                half_height,
+                radius,
+            } => {
+                let axis = pose.transform_vector(Vector3::unit_y() * *half_height);
+
+                WorldShape::Capsule {
+                    start: pose.position - axis,
+                    end: pose.position + axis,
+                    radius: *radius,
+                }
+            }
+            Shape::ConvexHull(hull) => WorldShape::Hull { hull, pose: *pose },
+        }
+    }
+
+    fn center(&self) -> Vector3<f32> {
+        match self {
+            WorldShape::Sphere { center, .. } | WorldShape::Box { center, .. } => *center,
+            WorldShape::Capsule { start, end, .. } => (start + end) * 0.5,
+            WorldShape::Hull { pose, .. } => pose.position,
+        }
+    }
+
+    fn radius(&self) -> f32 {
+        match self {
+            WorldShape::Sphere { radius, .. } | WorldShape::Capsule { radius, .. } => *radius,
+            _ => 0.0,
+        }
+    }
+
+    // Point or segment core of the rounded shapes
+    fn segment(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        match self {
+            WorldShape::Sphere { center, .. } => Some((*center, *center)),
+            WorldShape::Capsule { start, end, .. } => Some((*start, *end)),
+            _ => None,
+        }
+    }
+
+    // Furthest point of the core along `direction`
+    fn core_support(&self, direction: Vector3<f32>) -> Vector3<f32> {
+        match self {
+            WorldShape::Sphere { center, .. } => *center,
+            WorldShape::Box {
+                center,
+                axes,
+                half_extents,
+            } => (0..3).fold(*center, |point, i| {
+                point + axes[i] * half_extents[i].copysign(axes[i].dot(direction))
+            }),
+            WorldShape::Capsule { start, end, .. } => {
+                if (end - start).dot(direction) >= 0.0 {
+                    *end
+                } else {
+                    *start
+                }
+            }
+            WorldShape::Hull { hull, pose } => {
+                pose.transform_point(hull.support(pose.inverse_transform_vector(direction)))
+            }
+        }
+    }
+}
+
+fn collide_shapes(a: &WorldShape, b: &WorldShape) -> Option<ContactManifold> {
+    let mut manifold = match (a, b) {
+        (
+            WorldShape::Box {
+                center: center_a,
+                axes: axes_a,
+                half_extents: half_extents_a,
+            },
+            WorldShape::Box {
+                center: center_b,
+                axes: axes_b,
+                half_extents: half_extents_b,
+            },
+        ) => box_box(
+            &BoxFrame {
+                center: *center_a,
+                axes: *axes_a,
+                half_extents: *half_extents_a,
+            },
+            &BoxFrame {
+                center: *center_b,
+                axes: *axes_b,
+                half_extents: *half_extents_b,
+            },
+        )?,
+        _ => match (a.segment(), b.segment()) {
+            (Some(segment_a), Some(segment_b)) => {
+                segment_segment(segment_a, a.radius(), segment_b, b.radius())?
+            }
+            _ => general(a, b)?,
+        },
+    };
+
+    manifold.reduce();
+
+    Some(manifold)
+}
+
+// Contact between the surfaces around two core points a distance apart along `normal`
+fn surface_contact(
+    core_a: Vector3<f32>,
+    radius_a: f32,
+    core_b: Vector3<f32>,
+    radius_b: f32,
+    normal: Vector3<f32>,
+) -> ContactPoint {
+    ContactPoint {
+        point_a: core_a + normal * radius_a,
+        point_b: core_b - normal * radius_b,
+        depth: radius_a + radius_b - (core_b - core_a).dot(normal),
+    }
+}
+
+fn push_unique(manifold: &mut ContactManifold, contact: ContactPoint) {
+    let duplicate = manifold.points.iter().any(|point| {
+        (point.position() - contact.position()).magnitude2() < MERGE_DISTANCE * MERGE_DISTANCE
+    });
+
+    if !duplicate {
+        manifold.points.push(contact);
+    }
+}
+
+// Spheres and capsules, as segments with radii
+fn segment_segment(
+    (start_a, end_a): (Vector3<f32>, Vector3<f32>),
+    radius_a: f32,
+    (start_b, end_b): (Vector3<f32>, Vector3<f32>),
+    radius_b: f32,
+) -> Option<ContactManifold> {
+    let radius = radius_a + radius_b;
+    let (core_a, core_b) = closest_points_on_segments(start_a, end_a, start_b, end_b);
+    let delta = core_b - core_a;
+    let distance2 = delta.magnitude2();
+
+    if distance2 > radius * radius {
+        return None;
+    }
+
+    let direction_a = end_a - start_a;
+    let direction_b = end_b - start_b;
+    let normal = if distance2 > EPSILON {
+        delta / distance2.sqrt()
+    } else {
+        // Cores cross, push apart across both of them
+        let across = direction_a.cross(direction_b);
+        let fallback = if across.magnitude2() > EPSILON {
+            across
+        } else {
+            any_perpendicular(direction_a + direction_b)
+        }
+        .normalize();
+
+        if fallback.dot((start_b + end_b) - (start_a + end_a)) < 0.0 {
+            -fallback
+        } else {
+            fallback
+        }
+    };
+
+    let mut manifold = ContactManifold::new(normal);
+    manifold
+        .points
+        .push(surface_contact(core_a, radius_a, core_b, radius_b, normal));
+
+    // Capsules lying alongside each other touch along the overlap of their
+    // segments, add its ends
+    let parallel = direction_a.cross(direction_b).magnitude2()
+        < 1e-4 * direction_a.magnitude2() * direction_b.magnitude2();
+
+    if parallel && direction_a.magnitude2() > EPSILON && direction_b.magnitude2() > EPSILON {
+        // Only ends facing the other segment straight across bound the overlap
+        let across = |from: Vector3<f32>, to: Vector3<f32>| {
+            let offset = to - from;
+            let lateral = offset - normal * offset.dot(normal);
+
+            offset.magnitude2() <= radius * radius && lateral.magnitude2() < 1e-6
+        };
+
+        for end in [start_a, end_a] {
+            let (other, _) = closest_point_on_segment(end, start_b, end_b);
+
+            if across(end, other) {
+                push_unique(
+                    &mut manifold,
+                    surface_contact(end, radius_a, other, radius_b, normal),
+                );
+            }
+        }
+
+        for end in [start_b, end_b] {
+            let (other, _) = closest_point_on_segment(end, start_a, end_a);
+
+            if across(other, end) {
+                push_unique(
+                    &mut manifold,
+                    surface_contact(other, radius_a, end, radius_b, normal),
+                );
+            }
+        }
+    }
+
+    Some(manifold)
+}
+
+fn any_perpendicular(vector: Vector3<f32>) -> Vector3<f32> {
+    let axis = if vector.x.abs() < 0.9 {
+        Vector3::unit_x()
+    } else {
+        Vector3::unit_y()
+    };
+    let perpendicular = vector.cross(axis);
+
+    if perpendicular.magnitude2() > EPSILON {
+        perpendicular
+    } else {
+        Vector3::unit_y()
+    }
+}
+
+// Closest points between the cores by GJK, falling back to EPA when the
+// cores overlap, then pushed out to the surfaces by the radii
+fn core_contact(a: &WorldShape, b: &WorldShape) -> Option<(Vector3<f32>, ContactPoint)> {
+    let support = |direction: Vector3<f32>| {
+        let point_a = a.core_support(direction);
+        let point_b = b.core_support(-direction);
+
+        SupportPoint {
+            w: point_a - point_b,
+            a: point_a,
+            b: point_b,
+        }
+    };
+    let radius_a = a.radius();
+    let radius_b = b.radius();
+
+    match gjk::gjk(&support, a.center() - b.center()) {
+        GjkResult::Separated {
+            distance,
+            point_a,
+            point_b,
+        } => {
+            if distance >= radius_a + radius_b || distance * distance < EPSILON {
+                return None;
+            }
+
+            let normal = (point_b - point_a) / distance;
+
+            Some((
+                normal,
+                surface_contact(point_a, radius_a, point_b, radius_b, normal),
+            ))
+        }
+        GjkResult::Overlapping(simplex) => {
+            let penetration = gjk::epa(&support, simplex)?;
+            let normal = penetration.normal;
+
+            Some((
+                normal,
+                ContactPoint {
+                    point_a: penetration.point_a + normal * radius_a,
+                    point_b: penetration.point_b - normal * radius_b,
+                    depth: penetration.depth + radius_a + radius_b,
+                },
+            ))
+        }
+    }
+}
+
+// Any pair including a hull, or a box against a sphere or capsule
+fn general(a: &WorldShape, b: &WorldShape) -> Option<ContactManifold> {
+    let (normal, contact) = core_contact(a, b)?;
+
+    let mut manifold = ContactManifold::new(normal);
+    manifold.points.push(contact);
+
+    // A capsule lying on a face touches it along its length, add its ends
+    for (shape, first) in [(a, true), (b, false)] {
+        let WorldShape::Capsule { start, end, radius } = *shape else {
+            continue;
+        };
+
+        for center in [start, end] {
+            let cap = WorldShape::Sphere { center, radius };
+            let cap_contact = if first {
+                core_contact(&cap, b)
+            } else {
+                core_contact(a, &cap)
+            };
+
+            if let Some((cap_normal, contact)) = cap_contact {
+                if cap_normal.dot(normal) > END_CAP_ALIGNMENT {
+                    push_unique(&mut manifold, contact);
+                }
+            }
+        }
+    }
+
+    Some(manifold)
+}
+
+struct BoxFrame {
+    center: Vector3<f32>,
+    axes: [Vector3<f32>; 3],
+    half_extents: Vector3<f32>,
+}
+
+impl BoxFrame {
+    // Half the box's extent projected onto `axis`
+    fn projected_radius(&self, axis: Vector3<f32>) -> f32 {
+        (0..3)
+            .map(|i| self.half_extents[i] * self.axes[i].dot(axis).abs())
+            .sum()
+    }
+}
+
+// Separating axis test over both boxes' face normals and the cross products
+// of their edges, then clipping the incident face against the reference face
+// for face contacts
+fn box_box(a: &BoxFrame, b: &BoxFrame) -> Option<ContactManifold> {
+    let delta = b.center - a.center;
+    let overlap = |axis: Vector3<f32>| {
+        a.projected_radius(axis) + b.projected_radius(axis) - delta.dot(axis).abs()
+    };
+
+    // (overlap, reference is a, axis index)
+    let mut best_face = (f32::INFINITY, true, 0);
+
+    for (frame, is_a) in [(a, true), (b, false)] {
+        for i in 0..3 {
+            let depth = overlap(frame.axes[i]);
+
+            if depth < 0.0 {
+                return None;
+            }
+
+            if depth < best_face.0 {
+                best_face = (depth, is_a, i);
+            }
+        }
+    }
+
+    let mut best_edge: Option<(f32, usize, usize, Vector3<f32>)> = None;
+
+    for i in 0..3 {
+        for j in 0..3 {
+            let axis = a.axes[i].cross(b.axes[j]);
+
+            // Parallel edges, covered by the face axes
+            if axis.magnitude2() < 1e-6 {
+                continue;
+            }
+
+            let axis = axis.normalize();
+            let depth = overlap(axis);
+
+            if depth < 0.0 {
+                return None;
+            }
+
+            if best_edge.is_none_or(|(best, ..)| depth < best) {
+                best_edge = Some((depth, i, j, axis));
+            }
+        }
+    }
+
+    if let Some((depth, i, j, axis)) = best_edge {
+        if depth < best_face.0 * EDGE_RELATIVE_TOLERANCE - EDGE_ABSOLUTE_TOLERANCE {
+            return Some(edge_contact(a, b, i, j, facing(axis, delta), depth));
+        }
+    }
+
+    let (_, reference_is_a, axis) = best_face;
+
+    let manifold = if reference_is_a {
+        let normal = facing(a.axes[axis], delta);
+
+        face_contact(a, b, axis, normal)
+    } else {
+        let normal = facing(b.axes[axis], -delta);
+
+        face_contact(b, a, axis, normal).flipped()
+    };
+
+    (!manifold.points.is_empty()).then_some(manifold)
+}
+
+// `vector` flipped if needed to point along `direction`
+fn facing(vector: Vector3<f32>, direction: Vector3<f32>) -> Vector3<f32> {
+    if vector.dot(direction) < 0.0 {
+        -vector
+    } else {
+        vector
+    }
+}
+
+// Clips the face of `incident` most opposed to `normal` against the side
+// planes of the face of `reference` along it, reference to incident
+fn face_contact(
+    reference: &BoxFrame,
+    incident: &BoxFrame,
+    axis: usize,
+    normal: Vector3<f32>,
+) -> ContactManifold {
+    let face_center = reference.center + normal * reference.half_extents[axis];
+
+    let incident_axis = (0..3)
+        .max_by(|i, j| {
+            incident.axes[*i]
+                .dot(normal)
+                .abs()
+                .total_cmp(&incident.axes[*j].dot(normal).abs())
+        })
+        .unwrap();
+    let incident_normal = facing(incident.axes[incident_axis], -normal);
+    let incident_center = incident.center + incident_normal * incident.half_extents[incident_axis];
+    let u = incident.axes[(incident_axis + 1) % 3] * incident.half_extents[(incident_axis + 1) % 3];
+    let v = incident.axes[(incident_axis + 2) % 3] * incident.half_extents[(incident_axis + 2) % 3];
+
+    let mut polygon = vec![
+        incident_center + u + v,
+        incident_center + u - v,
+        incident_center - u - v,
+        incident_center - u + v,
+    ];
+
+    for side in [(axis + 1) % 3, (axis + 2) % 3] {
+        for sign in [1.0, -1.0] {
+            let plane_normal = reference.axes[side] * sign;
+            let offset = plane_normal.dot(reference.center) + reference.half_extents[side];
+
+            polygon = clip(&polygon, plane_normal, offset);
+        }
+    }
+
+    let mut manifold = ContactManifold::new(normal);
+
+    for point in polygon {
+        let separation = (point - face_center).dot(normal);
+
+        if separation <= 0.0 {
+            push_unique(
+                &mut manifold,
+                ContactPoint {
+                    point_a: point - normal * separation,
+                    point_b: point,
+                    depth: -separation,
+                },
+            );
+        }
+    }
+
+    manifold
+}
+
+// Sutherland-Hodgman, keeping the part of the polygon behind the plane
+fn clip(polygon: &[Vector3<f32>], normal: Vector3<f32>, offset: f32) -> Vec<Vector3<f32>> {
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+
+    for (index, start) in polygon.iter().enumerate() {
+        let end = polygon[(index + 1) % polygon.len()];
+        let start_distance = normal.dot(*start) - offset;
+        let end_distance = normal.dot(end) - offset;
+
+        if start_distance <= 0.0 {
+            clipped.push(*start);
+        }
+
+        if (start_distance <= 0.0) != (end_distance <= 0.0) {
+            let t = start_distance / (start_distance - end_distance);
+            clipped.push(start + (end - start) * t);
+        }
+    }
+
+    clipped
+}
+
+// Closest points between the supporting edges of each box along the normal
+fn edge_contact(
+    a: &BoxFrame,
+    b: &BoxFrame,
+    axis_a: usize,
+    axis_b: usize,
+    normal: Vector3<f32>,
+    depth: f32,
+) -> ContactManifold {
+    let supporting_edge = |frame: &BoxFrame, axis: usize, direction: Vector3<f32>| {
+        let middle = (0..3)
+            .filter(|i| *i != axis)
+            .fold(frame.center, |point, i| {
+                point + frame.axes[i] * frame.half_extents[i].copysign(frame.axes[i].dot(direction))
+            });
+        let along = frame.axes[axis] * frame.half_extents[axis];
+
+        (middle - along, middle + along)
+    };
+
+    let (start_a, end_a) = supporting_edge(a, axis_a, normal);
+    let (start_b, end_b) = supporting_edge(b, axis_b, -normal);
+    let (point_a, point_b) = closest_points_on_segments(start_a, end_a, start_b, end_b);
+
+    let mut manifold = ContactManifold::new(normal);
+    manifold.points.push(ContactPoint {
+        point_a,
+        point_b,
+        depth,
+    });
+
+    manifold
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_4;
+
+    use cgmath::{Quaternion, Rad};
+
+    use super::*;
+
+    const TOLERANCE: f32 = 1e-3;
+
+    fn at(position: Vector3<f32>) -> Pose {
+        Pose::new(position, Quaternion::one())
+    }
+
+    fn turned(position: Vector3<f32>, orientation: Quaternion<f32>) -> Pose {
+        Pose::new(position, orientation)
+    }
+
+    fn assert_close(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!(
+            (actual - expected).magnitude() < TOLERANCE,
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    fn assert_depth(manifold: &ContactManifold, depth: f32) {
+        for point in &manifold.points {
+            assert!(
+                (point.depth - depth).abs() < TOLERANCE,
+                "depth {}",
+                point.depth
+            );
+        }
+    }
+
+    fn cube_hull(half: f32) -> Collider {
+        let corners = (0..8)
+            .map(|i| {
+                Vector3::new(
+                    if i & 1 == 0 { -half } else { half },
+                    if i & 2 == 0 { -half } else { half },
+                    if i & 4 == 0 { -half } else { half },
+                )
+            })
+            .collect();
+
+        Collider::convex_hull(corners)
+    }
+
+    #[test]
+    fn sphere_box() {
+        let sphere = Collider::sphere(0.5);
+        let cube = Collider::obb(Vector3::new(1.0, 1.0, 1.0));
+
+        let manifold = collide(
+            &sphere,
+            &at(Vector3::new(0.2, 1.4, 0.0)),
+            &cube,
+            &at(Vector3::zero()),
+        )
+        .unwrap();
+
+        assert_close(manifold.normal, -Vector3::unit_y());
+        assert_eq!(manifold.points.len(), 1);
+        assert_depth(&manifold, 0.1);
+        assert_close(manifold.points[0].point_b, Vector3::new(0.2, 1.0, 0.0));
+
+        // Swapping the shapes flips the normal
+        let flipped = collide(
+            &cube,
+            &at(Vector3::zero()),
+            &sphere,
+            &at(Vector3::new(0.2, 1.4, 0.0)),
+        )
+        .unwrap();
+
+        assert_close(flipped.normal, Vector3::unit_y());
+        assert_depth(&flipped, 0.1);
+
+        assert!(collide(
+            &sphere,
+            &at(Vector3::new(0.0, 1.6, 0.0)),
+            &cube,
+            &at(Vector3::zero())
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn box_box_face() {
+        let floor = Collider::obb(Vector3::new(1.0, 1.0, 1.0));
+        let cube = Collider::obb(Vector3::new(0.5, 0.5, 0.5));
+
+        let manifold = collide(
+            &floor,
+            &at(Vector3::zero()),
+            &cube,
+            &at(Vector3::new(0.1, 1.4, -0.1)),
+        )
+        .unwrap();
+
+        assert_close(manifold.normal, Vector3::unit_y());
+        assert_eq!(manifold.points.len(), 4);
+        assert_depth(&manifold, 0.1);
+
+        for point in &manifold.points {
+            assert!((point.point_a.y - 1.0).abs() < TOLERANCE);
+            assert!((point.point_b.y - 0.9).abs() < TOLERANCE);
+        }
+
+        assert!(collide(
+            &floor,
+            &at(Vector3::zero()),
+            &cube,
+            &at(Vector3::new(0.0, 1.6, 0.0))
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn box_box_edge() {
+        let cube = Collider::obb(Vector3::new(1.0, 1.0, 1.0));
+        let diagonal = 2.0_f32.sqrt();
+
+        // A ridge along x under a ridge along z, crossing at one point
+        let below = turned(Vector3::zero(), Quaternion::from_angle_x(Rad(FRAC_PI_4)));
+        let above = turned(
+            Vector3::new(0.0, 2.0 * diagonal - 0.1, 0.0),
+            Quaternion::from_angle_z(Rad(FRAC_PI_4)),
+        );
+
+        let manifold = collide(&cube, &below, &cube, &above).unwrap();
+
+        assert_close(manifold.normal, Vector3::unit_y());
+        assert_eq!(manifold.points.len(), 1);
+        assert_depth(&manifold, 0.1);
+        assert_close(
+            manifold.points[0].position(),
+            Vector3::new(0.0, diagonal - 0.05, 0.0),
+        );
+    }
+
+    #[test]
+    fn capsule_box() {
+        let capsule = Collider::capsule(1.0, 0.25);
+        let floor = Collider::obb(Vector3::new(2.0, 1.0, 2.0));
+
+        // Lying down it touches along its length, ends included
+        let lying = turned(
+            Vector3::new(0.0, 1.2, 0.0),
+            Quaternion::from_angle_z(Rad(2.0 * FRAC_PI_4)),
+        );
+        let manifold = collide(&capsule, &lying, &floor, &at(Vector3::zero())).unwrap();
+
+        assert_close(manifold.normal, -Vector3::unit_y());
+        assert!(manifold.points.len() >= 2);
+        assert_depth(&manifold, 0.05);
+
+        let mut ends = manifold
+            .points
+            .iter()
+            .map(|point| point.point_b.x)
+            .collect::<Vec<_>>();
+        ends.sort_by(f32::total_cmp);
+
+        assert!((ends[0] + 1.0).abs() < TOLERANCE);
+        assert!((ends[ends.len() - 1] - 1.0).abs() < TOLERANCE);
+
+        // Standing up it touches at its lower end
+        let standing = at(Vector3::new(0.0, 2.2, 0.0));
+        let manifold = collide(&capsule, &standing, &floor, &at(Vector3::zero())).unwrap();
+
+        assert_close(manifold.normal, -Vector3::unit_y());
+        assert_eq!(manifold.points.len(), 1);
+        assert_depth(&manifold, 0.05);
+        assert_close(manifold.points[0].point_b, Vector3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn hull_plane() {
+        let hull = cube_hull(0.5);
+        let plane = Collider::obb(Vector3::new(10.0, 1.0, 10.0));
+
+        let manifold = collide(
+            &hull,
+            &at(Vector3::new(0.0, 1.4, 0.0)),
+            &plane,
+            &at(Vector3::zero()),
+        )
+        .unwrap();
+
+        assert_close(manifold.normal, -Vector3::unit_y());
+        assert!(!manifold.points.is_empty());
+        assert_depth(&manifold, 0.1);
+
+        for point in &manifold.points {
+            assert!((point.point_b.y - 1.0).abs() < TOLERANCE);
+        }
+
+        // Tipped onto an edge, only the lowest corners touch
+        let tipped = turned(
+            Vector3::new(0.0, 1.0 + 0.5 * 2.0_f32.sqrt() - 0.1, 0.0),
+            Quaternion::from_angle_z(Rad(FRAC_PI_4)),
+        );
+        let manifold = collide(&hull, &tipped, &plane, &at(Vector3::zero())).unwrap();
+
+        assert_close(manifold.normal, -Vector3::unit_y());
+        assert_depth(&manifold, 0.1);
+
+        for point in &manifold.points {
+            assert!(point.point_a.x.abs() < TOLERANCE);
+        }
+
+        assert!(collide(
+            &hull,
+            &at(Vector3::new(0.0, 1.6, 0.0)),
+            &plane,
+            &at(Vector3::zero())
+        )
+        .is_none());
+    }
+}
@@ -0,0 +1,316 @@
+use cgmath::prelude::*;
+use cgmath::{Quaternion, Vector3};
+
+use crate::geometry::{Bounds, Pose};
+use crate::model::Model;
+
+/// Geometry of a collider in its own frame
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    Sphere {
+        radius: f32,
+    },
+    /// Box that stays aligned to the world axes whatever the body's orientation
+    Aabb {
+        half_extents: Vector3<f32>,
+    },
+    /// Box that turns with the body
+    Obb {
+        half_extents: Vector3<f32>,
+    },
+    /// Segment along the y axis swept by a sphere
+    Capsule {
+        half_height: f32,
+        radius: f32,
+    },
+    ConvexHull(ConvexHull),
+}
+
+impl Shape {
+    /// The shape stretched by `scale` along its axes. Spheres and capsule
+    /// radii take the largest of the scales they span. Negative scales mirror
+    /// the shape, which only changes convex hulls.
+    pub fn scaled(&self, scale: Vector3<f32>) -> Shape {
+        let size = scale.map(f32::abs);
+
+        match self {
+            Shape::Sphere { radius } => Shape::Sphere {
+                radius: radius * size.x.max(size.y).max(size.z),
+            },
+            Shape::Aabb { half_extents } => Shape::Aabb {
+                half_extents: half_extents.mul_element_wise(size),
+            },
+            Shape::Obb { half_extents } => Shape::Obb {
+                half_extents: half_extents.mul_element_wise(size),
+            },
+            Shape::Capsule {
+                half_height,
+                radius,
+            } => Shape::Capsule {
+                half_height: half_height * size.y,
+                radius: radius * size.x.max(size.z),
+            },
+            Shape::ConvexHull(hull) => Shape::ConvexHull(ConvexHull::new(
+                hull.points
+                    .iter()
+                    .map(|point| point.mul_element_wise(scale))
+                    .collect(),
+            )),
+        }
+    }
+
+    /// World space bounds with the shape placed at `pose`
+    pub fn bounds(&self, pose: &Pose) -> Bounds {
+        match self {
+            Shape::Sphere { radius } => {
+                Bounds::from_center(pose.position, Vector3::from_value(*radius))
+            }
+            Shape::Aabb { half_extents } => Bounds::from_center(pose.position, *half_extents),
+            Shape::Obb { half_extents } => {
+                // Project the rotated box onto each world axis
+                let axes = box_axes(pose.orientation);
+                let extents = (0..3)
+                    .map(|axis| {
+                        (0..3)
+                            .map(|i| (axes[i][axis] * half_extents[i]).abs())
+                            .sum::<f32>()
+                    })
+                    .collect::<Vec<_>>();
+
+                Bounds::from_center(
+                    pose.position,
+                    Vector3::new(extents[0], extents[1], extents[2]),
+                )
+            }
+            Shape::Capsule {
+                half_height,
+                radius,
+            } => {
+                let axis = pose.transform_vector(Vector3::unit_y() * *half_height);
+
+                Bounds::from_points([pose.position - axis, pose.position + axis]).expanded(*radius)
+            }
+            Shape::ConvexHull(hull) => {
+                Bounds::from_points(hull.points.iter().map(|point| pose.transform_point(*point)))
+            }
+        }
+    }
+}
+
+// World space unit axes of a box turned by `orientation`
+pub(crate) fn box_axes(orientation: Quaternion<f32>) -> [Vector3<f32>; 3] {
+    [
+        orientation.rotate_vector(Vector3::unit_x()),
+        orientation.rotate_vector(Vector3::unit_y()),
+        orientation.rotate_vector(Vector3::unit_z()),
+    ]
+}
+
+/// Convex hull of a point cloud, kept as the points themselves. Interior
+/// points cost time in queries but don't change the result.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConvexHull {
+    points: Vec<Vector3<f32>>,
+}
+
+impl ConvexHull {
+    /// Panics if `points` is empty. Duplicate points are dropped.
+    pub fn new(mut points: Vec<Vector3<f32>>) -> Self {
+        assert!(!points.is_empty(), "Convex hull needs at least one point");
+
+        points.sort_by(|a, b| {
+            a.x.total_cmp(&b.x)
+                .then(a.y.total_cmp(&b.y))
+                .then(a.z.total_cmp(&b.z))
+        });
+        points.dedup();
+
+        Self { points }
+    }
+
+    pub fn points(&self) -> &[Vector3<f32>] {
+        &self.points
+    }
+
+    /// Point furthest along `direction`, in the hull's frame
+    pub fn support(&self, direction: Vector3<f32>) -> Vector3<f32> {
+        self.points
+            .iter()
+            .copied()
+            .max_by(|a, b| a.dot(direction).total_cmp(&b.dot(direction)))
+            .unwrap()
+    }
+}
+
+/// Shape to fit to a model's vertices with [`Collider::fit`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FitShape {
+    /// Smallest sphere about the centre of the bounds
+    Sphere,
+    Aabb,
+    Obb,
+    /// Capsule along the y axis
+    Capsule,
+    /// Hull of every vertex
+    ConvexHull,
+}
+
+/// A shape attached to a rigid body, offset from the body's origin
+#[derive(Clone, Debug, PartialEq)]
+pub struct Collider {
+    shape: Shape,
+    offset: Pose,
+}
+
+impl Collider {
+    pub fn new(shape: Shape) -> Self {
+        Self {
+            shape,
+            offset: Pose::identity(),
+        }
+    }
+
+    pub fn sphere(radius: f32) -> Self {
+        Self::new(Shape::Sphere { radius })
+    }
+
+    pub fn aabb(half_extents: Vector3<f32>) -> Self {
+        Self::new(Shape::Aabb { half_extents })
+    }
+
+    pub fn obb(half_extents: Vector3<f32>) -> Self {
+        Self::new(Shape::Obb { half_extents })
+    }
+
+    pub fn capsule(half_height: f32, radius: f32) -> Self {
+        Self::new(Shape::Capsule {
+            half_height,
+            radius,
+        })
+    }
+
+    pub fn convex_hull(points: Vec<Vector3<f32>>) -> Self {
+        Self::new(Shape::ConvexHull(ConvexHull::new(points)))
+    }
+
+    /// Fits a shape to the model's vertices, offset to the centre of its
+    /// bounds. Returns `None` if the model has no vertices.
+    pub fn fit(model: &Model, shape: FitShape) -> Option<Self> {
+        let bounds = model.bounds();
+
+        if bounds.is_empty() {
+            return None;
+        }
+
+        let center = bounds.center();
+        let half_extents = bounds.half_extents();
+
+        let collider = match shape {
+            FitShape::Sphere => Self::sphere(
+                model
+                    .positions()
+                    .map(|position| (position - center).magnitude2())
+                    .fold(0.0, f32::max)
+                    .sqrt(),
+            ),
+            FitShape::Aabb => Self::aabb(half_extents),
+            FitShape::Obb => Self::obb(half_extents),
+            FitShape::Capsule => {
+                let radius = half_extents.x.max(half_extents.z);
+
+                Self::capsule((half_extents.y - radius).max(0.0), radius)
+            }
+            FitShape::ConvexHull => return Some(Self::convex_hull(model.positions().collect())),
+        };
+
+        Some(collider.with_offset(center))
+    }
+
+    /// Position of the shape in the body's frame
+    pub fn with_offset(mut self, offset: Vector3<f32>) -> Self {
+        self.offset.position = offset;
+        self
+    }
+
+    /// Orientation of the shape in the body's frame
+    pub fn with_rotation(mut self, rotation: Quaternion<f32>) -> Self {
+        self.offset.orientation = rotation.normalize();
+        self
+    }
+
+    /// The collider stretched by `scale` in the body's frame, to match a
+    /// scaled instance
+    pub fn scaled(mut self, scale: Vector3<f32>) -> Self {
+        self.shape = self.shape.scaled(scale);
+        self.offset.position = self.offset.position.mul_element_wise(scale);
+        self
+    }
+
+    pub fn shape(&self) -> &Shape {
+        &self.shape
+    }
+
+    pub fn offset(&self) -> &Pose {
+        &self.offset
+    }
+
+    /// Pose of the shape for a body at `body_pose`
+    pub fn world_pose(&self, body_pose: &Pose) -> Pose {
+        body_pose.then(&self.offset)
+    }
+
+    /// World space bounds for a body at `body_pose`
+    pub fn bounds(&self, body_pose: &Pose) -> Bounds {
+        self.shape.bounds(&self.world_pose(body_pose))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scaled_shapes_ignore_the_sign_of_the_scale() {
+        let mirrored = Vector3::new(-3.0, 1.0, 0.5);
+
+        assert_eq!(
+            Shape::Sphere { radius: 2.0 }.scaled(mirrored),
+            Shape::Sphere { radius: 6.0 }
+        );
+        assert_eq!(
+            Shape::Obb {
+                half_extents: Vector3::new(1.0, 2.0, 4.0)
+            }
+            .scaled(mirrored),
+            Shape::Obb {
+                half_extents: Vector3::new(3.0, 2.0, 2.0)
+            }
+        );
+        assert_eq!(
+            Shape::Capsule {
+                half_height: 1.0,
+                radius: 1.0
+            }
+            .scaled(Vector3::new(1.0, -2.0, -3.0)),
+            Shape::Capsule {
+                half_height: 2.0,
+                radius: 3.0
+            }
+        );
+
+        // Hulls are mirrored
+        let hull = ConvexHull::new(vec![
+            Vector3::unit_x(),
+            Vector3::unit_y(),
+            Vector3::unit_z(),
+        ]);
+        let Shape::ConvexHull(scaled) = Shape::ConvexHull(hull).scaled(mirrored) else {
+            unreachable!();
+        };
+
+        assert_eq!(
+            scaled.support(-Vector3::unit_x()),
+            Vector3::new(-3.0, 0.0, 0.0)
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+
+// Solver defaults
+const SOLVER_ITERATIONS_DEFAULT: u32 = 10;
+const JOINT_ITERATIONS_DEFAULT: u32 = 10;
+const POSITION_CORRECTION_DEFAULT: f32 = 0.2;
+const PENETRATION_SLOP_DEFAULT: f32 = 0.005;
+const RESTITUTION_THRESHOLD_DEFAULT: f32 = 0.5;
+
+// Sleeping defaults
+const SLEEPING_DEFAULT: bool = true;
+const SLEEP_LINEAR_VELOCITY_DEFAULT: f32 = 0.05;
+const SLEEP_ANGULAR_VELOCITY_DEFAULT: f32 = 0.05;
+const TIME_TO_SLEEP_DEFAULT: f32 = 0.5;
+
+/// Contact and joint solver and sleeping settings of a [`super::PhysicsWorld`], a
+/// `[physics]` table in TOML
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PhysicsConfig {
+    /// Passes over the contacts per step, more stack taller piles stably
+    pub solver_iterations: u32,
+    /// Passes over the joints per step, more keep long chains from stretching
+    pub joint_iterations: u32,
+    /// Fraction of the penetration beyond the slop, and of joints' drift,
+    /// corrected each step
+    pub position_correction: f32,
+    /// Penetration left uncorrected so resting contacts persist between steps
+    pub penetration_slop: f32,
+    /// Approach speed below which contacts don't bounce
+    pub restitution_threshold: f32,
+    /// Whether bodies that come to rest stop being simulated until disturbed
+    pub sleeping: bool,
+    /// Linear speed below which a body counts as resting
+    pub sleep_linear_velocity: f32,
+    /// Angular speed below which a body counts as resting
+    pub sleep_angular_velocity: f32,
+    /// Seconds a group of touching bodies must rest before they sleep
+    pub time_to_sleep: f32,
+}
+
+impl PhysicsConfig {
+    pub fn new() -> Self {
+        Self {
+            solver_iterations: SOLVER_ITERATIONS_DEFAULT,
+            joint_iterations: JOINT_ITERATIONS_DEFAULT,
+            position_correction: POSITION_CORRECTION_DEFAULT,
+            penetration_slop: PENETRATION_SLOP_DEFAULT,
+            restitution_threshold: RESTITUTION_THRESHOLD_DEFAULT,
+            sleeping: SLEEPING_DEFAULT,
+            sleep_linear_velocity: SLEEP_LINEAR_VELOCITY_DEFAULT,
+            sleep_angular_velocity: SLEEP_ANGULAR_VELOCITY_DEFAULT,
+            time_to_sleep: TIME_TO_SLEEP_DEFAULT,
+        }
+    }
+
+    pub fn with_solver_iterations(mut self, solver_iterations: u32) -> Self {
+        self.solver_iterations = solver_iterations;
+        self
+    }
+
+    pub fn with_joint_iterations(mut self, joint_iterations: u32) -> Self {
+        self.joint_iterations = joint_iterations;
+        self
+    }
+
+    pub fn with_position_correction(mut self, position_correction: f32) -> Self {
+        self.position_correction = position_correction;
+        self
+    }
+
+    pub fn with_penetration_slop(mut self, penetration_slop: f32) -> Self {
+        self.penetration_slop = penetration_slop;
+        self
+    }
+
+    pub fn with_restitution_threshold(mut self, restitution_threshold: f32) -> Self {
+        self.restitution_threshold = restitution_threshold;
+        self
+    }
+
+    pub fn with_sleeping(mut self, sleeping: bool) -> Self {
+        self.sleeping = sleeping;
+        self
+    }
+
+    pub fn with_sleep_velocities(mut self, linear: f32, angular: f32) -> Self {
+        self.sleep_linear_velocity = linear;
+        self.sleep_angular_velocity = angular;
+        self
+    }
+
+    pub fn with_time_to_sleep(mut self, time_to_sleep: f32) -> Self {
+        self.time_to_sleep = time_to_sleep;
+        self
+    }
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,581 @@
+use std::f32::consts::PI;
+
+use cgmath::prelude::*;
+use cgmath::{Quaternion, Vector3};
+
+use super::solver::{tangent_basis, JointRow};
+use super::{BodyId, RigidBody};
+
+/// Impulse rows a joint can solve, the most of any kind
+pub(crate) const JOINT_ROWS: usize = 7;
+
+// Row slots of hinges and sliders for their limit and motor
+const LIMIT_ROW: usize = 5;
+const MOTOR_ROW: usize = 6;
+
+/// How a joint restricts the bodies it joins
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JointKind {
+    /// Keeps the anchors `length` apart, leaving rotation free
+    Distance { length: f32 },
+    /// Joins the anchors, leaving rotation free
+    Ball,
+    /// Joins the anchors and lets the bodies turn only about the axis
+    Hinge,
+    /// Locks the relative orientation and lets the bodies slide only along
+    /// the axis
+    Slider,
+}
+
+/// Drives a hinge or slider at `speed`, in radians or units per second,
+/// with at most `max_force` of torque or force
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct JointMotor {
+    pub speed: f32,
+    pub max_force: f32,
+}
+
+// The joint in each body's frame, taken from the bodies' poses when it's
+// added to a world
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct JointFrame {
+    anchor_a: Vector3<f32>,
+    anchor_b: Vector3<f32>,
+    axis_a: Vector3<f32>,
+    axis_b: Vector3<f32>,
+    // Orientation of body b in body a's frame
+    rest: Quaternion<f32>,
+}
+
+/// A constraint between two bodies, placed in world space as the bodies are
+/// when it's added with [`super::PhysicsWorld::add_joint`]. Attach a body to
+/// the world by joining it to a static body.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Joint {
+    body_a: BodyId,
+    body_b: BodyId,
+    kind: JointKind,
+    anchor_a: Vector3<f32>,
+    anchor_b: Vector3<f32>,
+    axis: Vector3<f32>,
+    limits: Option<(f32, f32)>,
+    motor: Option<JointMotor>,
+    collisions: bool,
+    frame: Option<JointFrame>,
+    // Accumulated impulses from the last step, to warm start the next
+    impulses: [f32; JOINT_ROWS],
+}
+
+impl Joint {
+    fn new(body_a: BodyId, body_b: BodyId, kind: JointKind, anchor: Vector3<f32>) -> Self {
+        Self {
+            body_a,
+            body_b,
+            kind,
+            anchor_a: anchor,
+            anchor_b: anchor,
+            axis: Vector3::unit_y(),
+            limits: None,
+            motor: None,
+            collisions: false,
+            frame: None,
+            impulses: [0.0; JOINT_ROWS],
+        }
+    }
+
+    /// Keeps the world space anchors at their current distance apart, like a
+    /// rigid rod between them
+    pub fn distance(
+        body_a: BodyId,
+        body_b: BodyId,
+        anchor_a: Vector3<f32>,
+        anchor_b: Vector3<f32>,
+    ) -> Self {
+        Self {
+            anchor_b,
+            ..Self::new(
+                body_a,
+                body_b,
+                JointKind::Distance {
+                    length: (anchor_b - anchor_a).magnitude(),
+                },
+                anchor_a,
+            )
+        }
+    }
+
+    /// Ball and socket at a world space point
+    pub fn ball(body_a: BodyId, body_b: BodyId, anchor: Vector3<f32>) -> Self {
+        Self::new(body_a, body_b, JointKind::Ball, anchor)
+    }
+
+    /// Hinge through a world space point about a world space axis
+    pub fn hinge(body_a: BodyId, body_b: BodyId, anchor: Vector3<f32>, axis: Vector3<f32>) -> Self {
+        Self {
+            axis: axis.normalize(),
+            ..Self::new(body_a, body_b, JointKind::Hinge, anchor)
+        }
+    }
+
+    /// Slider through a world space point along a world space axis
+    pub fn slider(
+        body_a: BodyId,
+        body_b: BodyId,
+        anchor: Vector3<f32>,
+        axis: Vector3<f32>,
+    ) -> Self {
+        Self {
+            axis: axis.normalize(),
+            ..Self::new(body_a, body_b, JointKind::Slider, anchor)
+        }
+    }
+
+    /// Overrides the length a distance joint keeps
+    pub fn with_length(mut self, length: f32) -> Self {
+        self.set_length(length);
+        self
+    }
+
+    /// Range of a hinge's angle in radians, or a slider's travel, from where
+    /// the bodies were placed. Ignored by distance and ball joints.
+    pub fn with_limits(mut self, min: f32, max: f32) -> Self {
+        self.set_limits(Some((min, max)));
+        self
+    }
+
+    pub fn with_motor(mut self, speed: f32, max_force: f32) -> Self {
+        self.set_motor(Some(JointMotor { speed, max_force }));
+        self
+    }
+
+    /// Whether the joined bodies' colliders still touch, off by default so
+    /// overlapping links don't push apart
+    pub fn with_collisions(mut self, collisions: bool) -> Self {
+        self.collisions = collisions;
+        self
+    }
+
+    pub fn body_a(&self) -> BodyId {
+        self.body_a
+    }
+
+    pub fn body_b(&self) -> BodyId {
+        self.body_b
+    }
+
+    pub fn kind(&self) -> JointKind {
+        self.kind
+    }
+
+    /// World space anchors as placed, the same point unless it's a distance
+    /// joint
+    pub fn anchors(&self) -> (Vector3<f32>, Vector3<f32>) {
+        (self.anchor_a, self.anchor_b)
+    }
+
+    /// World space axis as placed
+    pub fn axis(&self) -> Vector3<f32> {
+        self.axis
+    }
+
+    /// Does nothing unless it's a distance joint
+    pub fn set_length(&mut self, length: f32) {
+        if let JointKind::Distance { length: current } = &mut self.kind {
+            *current = length;
+        }
+    }
+
+    pub fn limits(&self) -> Option<(f32, f32)> {
+        self.limits
+    }
+
+    pub fn set_limits(&mut self, limits: Option<(f32, f32)>) {
+        self.limits = limits;
+    }
+
+    pub fn motor(&self) -> Option<JointMotor> {
+        self.motor
+    }
+
+    /// Does nothing to distance and ball joints
+    pub fn set_motor(&mut self, motor: Option<JointMotor>) {
+        self.motor = motor;
+    }
+
+    pub fn collisions(&self) -> bool {
+        self.collisions
+    }
+
+    /// Hinge angle in radians or slider travel from where the bodies were
+    /// placed, 0 for other joints or before the joint's added
+    pub fn position(&self, a: &RigidBody, b: &RigidBody) -> f32 {
+        let Some(frame) = &self.frame else {
+            return 0.0;
+        };
+
+        match self.kind {
+            JointKind::Hinge => {
+                let deviation = deviation(frame, a, b);
+                let angle = 2.0 * deviation.v.dot(frame.axis_a).atan2(deviation.s);
+
+                // Wrap into -PI..PI
+                (angle + PI).rem_euclid(2.0 * PI) - PI
+            }
+            JointKind::Slider => {
+                let (point_a, point_b) = world_anchors(frame, a, b);
+
+                (point_b - point_a).dot(a.orientation().rotate_vector(frame.axis_a))
+            }
+            _ => 0.0,
+        }
+    }
+
+    // Takes the joint's frame in each body from their current poses
+    pub(crate) fn attach(&mut self, a: &RigidBody, b: &RigidBody) {
+        self.frame = Some(JointFrame {
+            anchor_a: a.pose().inverse_transform_point(self.anchor_a),
+            anchor_b: b.pose().inverse_transform_point(self.anchor_b),
+            axis_a: a.pose().inverse_transform_vector(self.axis),
+            axis_b: b.pose().inverse_transform_vector(self.axis),
+            rest: a.orientation().invert() * b.orientation(),
+        });
+        self.impulses = [0.0; JOINT_ROWS];
+    }
+
+    pub(crate) fn set_impulses(&mut self, impulses: [f32; JOINT_ROWS]) {
+        self.impulses = impulses;
+    }
+
+    // Rows the solver keeps at zero velocity, each pushing towards zero
+    // error at `correction` times the error per second
+    pub(crate) fn rows(
+        &self,
+        a: &RigidBody,
+        b: &RigidBody,
+        correction: f32,
+        dt: f32,
+    ) -> Vec<JointRow> {
+        let Some(frame) = &self.frame else {
+            return Vec::new();
+        };
+
+        let (point_a, point_b) = world_anchors(frame, a, b);
+        let (offset_a, offset_b) = (point_a - a.position(), point_b - b.position());
+        let separation = point_b - point_a;
+        let mut rows = Vec::with_capacity(JOINT_ROWS);
+
+        // Anchors held together along each world axis
+        let point_rows = |rows: &mut Vec<JointRow>| {
+            for (slot, axis) in [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()]
+                .into_iter()
+                .enumerate()
+            {
+                rows.push(JointRow::linear(
+                    slot,
+                    axis,
+                    offset_a,
+                    offset_b,
+                    -correction * separation.dot(axis),
+                ));
+            }
+        };
+
+        match self.kind {
+            JointKind::Distance { length } => {
+                let distance = separation.magnitude();
+
+                if distance > f32::EPSILON {
+                    let direction = separation / distance;
+
+                    rows.push(JointRow::linear(
+                        0,
+                        direction,
+                        offset_a,
+                        offset_b,
+                        -correction * (distance - length),
+                    ));
+                }
+            }
+            JointKind::Ball => point_rows(&mut rows),
+            JointKind::Hinge => {
+                point_rows(&mut rows);
+
+                // Body b's axis kept perpendicular to two directions across
+                // body a's
+                let axis_a = a.orientation().rotate_vector(frame.axis_a);
+                let axis_b = b.orientation().rotate_vector(frame.axis_b);
+
+                for (slot, across) in tangent_basis(axis_a).into_iter().enumerate() {
+                    rows.push(JointRow::angular(
+                        3 + slot,
+                        axis_b.cross(across),
+                        -correction * axis_b.dot(across),
+                    ));
+                }
+
+                self.axial_rows(&mut rows, a, b, correction, dt, |target| {
+                    JointRow::angular(0, axis_a, target)
+                });
+            }
+            JointKind::Slider => {
+                // Relative orientation locked about each world axis
+                let deviation = deviation(frame, a, b);
+                let sign = if deviation.s < 0.0 { -1.0 } else { 1.0 };
+                let error = a.orientation().rotate_vector(deviation.v) * (2.0 * sign);
+
+                for (slot, axis) in [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()]
+                    .into_iter()
+                    .enumerate()
+                {
+                    rows.push(JointRow::angular(slot, axis, -correction * error.dot(axis)));
+                }
+
+                // Anchors held on the axis, which turns with body a, so the
+                // impulse acts on a at body b's anchor
+                let axis_a = a.orientation().rotate_vector(frame.axis_a);
+                let lever_a = point_b - a.position();
+
+                for (slot, across) in tangent_basis(axis_a).into_iter().enumerate() {
+                    rows.push(JointRow::linear(
+                        3 + slot,
+                        across,
+                        lever_a,
+                        offset_b,
+                        -correction * separation.dot(across),
+                    ));
+                }
+
+                self.axial_rows(&mut rows, a, b, correction, dt, |target| {
+                    JointRow::linear(0, axis_a, lever_a, offset_b, target)
+                });
+            }
+        }
+
+        for row in &mut rows {
+            row.impulse = self.impulses[row.slot].clamp(row.lower, row.upper);
+        }
+
+        rows
+    }
+
+    // Limit and motor rows along a hinge or slider's axis, built by `row`
+    // from the target velocity
+    fn axial_rows(
+        &self,
+        rows: &mut Vec<JointRow>,
+        a: &RigidBody,
+        b: &RigidBody,
+        correction: f32,
+        dt: f32,
+        row: impl Fn(f32) -> JointRow,
+    ) {
+        if let Some((min, max)) = self.limits {
+            let position = self.position(a, b);
+
+            if position <= min {
+                rows.push(
+                    row(-correction * (position - min))
+                        .with_slot(LIMIT_ROW)
+                        .with_bounds(0.0, f32::INFINITY),
+                );
+            } else if position >= max {
+                rows.push(
+                    row(-correction * (position - max))
+                        .with_slot(LIMIT_ROW)
+                        .with_bounds(f32::NEG_INFINITY, 0.0),
+                );
+            }
+        }
+
+        if let Some(motor) = self.motor {
+            let max_impulse = motor.max_force * dt;
+
+            rows.push(
+                row(motor.speed)
+                    .with_slot(MOTOR_ROW)
+                    .with_bounds(-max_impulse, max_impulse),
+            );
+        }
+    }
+}
+
+// Rotation of body b away from its rest orientation, in body a's frame
+fn deviation(frame: &JointFrame, a: &RigidBody, b: &RigidBody) -> Quaternion<f32> {
+    a.orientation().invert() * b.orientation() * frame.rest.invert()
+}
+
+fn world_anchors(frame: &JointFrame, a: &RigidBody, b: &RigidBody) -> (Vector3<f32>, Vector3<f32>) {
+    (
+        a.pose().transform_point(frame.anchor_a),
+        b.pose().transform_point(frame.anchor_b),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::{cuboid_inertia, JointId, PhysicsWorld};
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn cube(position: Vector3<f32>) -> RigidBody {
+        RigidBody::dynamic(1.0, cuboid_inertia(1.0, Vector3::from_value(0.25)))
+            .with_position(position)
+            .with_can_sleep(false)
+    }
+
+    // Runs for `seconds`, handing the joint and its bodies to `each` step
+    fn run(
+        world: &mut PhysicsWorld,
+        joint: JointId,
+        seconds: f32,
+        mut each: impl FnMut(&Joint, &RigidBody, &RigidBody),
+    ) {
+        for _ in 0..(seconds / DT).round() as usize {
+            world.step(DT);
+
+            let joint = world.joint(joint).unwrap();
+
+            each(
+                joint,
+                world.get(joint.body_a()).unwrap(),
+                world.get(joint.body_b()).unwrap(),
+            );
+        }
+    }
+
+    fn anchor_gap(joint: &Joint, a: &RigidBody, b: &RigidBody) -> f32 {
+        let (point_a, point_b) = world_anchors(joint.frame.as_ref().unwrap(), a, b);
+
+        (point_b - point_a).magnitude()
+    }
+
+    #[test]
+    fn pendulum_keeps_its_length() {
+        let mut world = PhysicsWorld::new();
+        let pivot = world.add(RigidBody::fixed());
+        let bob = world.add(cube(Vector3::new(2.0, 0.0, 0.0)));
+        let joint = world.add_joint(Joint::distance(
+            pivot,
+            bob,
+            Vector3::zero(),
+            Vector3::new(2.0, 0.0, 0.0),
+        ));
+        let mut lowest = 0.0f32;
+
+        run(&mut world, joint, 4.0, |joint, pivot, bob| {
+            assert!((anchor_gap(joint, pivot, bob) - 2.0).abs() < 0.02);
+            lowest = lowest.min(bob.position().y);
+        });
+
+        assert!(lowest < -1.9);
+    }
+
+    #[test]
+    fn ball_joint_keeps_its_anchors_together() {
+        let mut world = PhysicsWorld::new();
+        let pivot = world.add(RigidBody::fixed());
+        let body = world.add(
+            cube(Vector3::new(0.5, -0.5, 0.0))
+                .with_angular_velocity(Vector3::new(1.0, 4.0, 2.0))
+                .with_linear_velocity(Vector3::new(0.0, 0.0, 2.0)),
+        );
+        let joint = world.add_joint(Joint::ball(pivot, body, Vector3::zero()));
+
+        run(&mut world, joint, 4.0, |joint, pivot, body| {
+            assert!(anchor_gap(joint, pivot, body) < 0.01);
+        });
+    }
+
+    fn hinged(joint: impl FnOnce(BodyId, BodyId) -> Joint) -> (PhysicsWorld, JointId) {
+        let mut world = PhysicsWorld::new();
+        let frame = world.add(RigidBody::fixed());
+        let door = world.add(cube(Vector3::new(1.0, 0.0, 0.0)));
+        let joint = world.add_joint(joint(frame, door));
+
+        world.set_gravity(Vector3::zero());
+
+        (world, joint)
+    }
+
+    #[test]
+    fn hinge_stays_within_its_limits() {
+        let (mut world, joint) = hinged(|frame, door| {
+            Joint::hinge(frame, door, Vector3::zero(), Vector3::unit_y()).with_limits(-0.5, 0.5)
+        });
+        let door = world.get_mut(world.joint(joint).unwrap().body_b()).unwrap();
+
+        // Swinging about the hinge, with a wobble off its axis
+        door.set_angular_velocity(Vector3::new(0.5, 2.0, 0.0));
+        door.set_linear_velocity(Vector3::new(0.0, 0.0, -2.0));
+
+        let mut reached = 0.0f32;
+
+        run(&mut world, joint, 2.0, |joint, frame, door| {
+            let angle = joint.position(frame, door);
+            let axis = door.orientation().rotate_vector(Vector3::unit_y());
+
+            // Hitting the stops briefly strains the joint
+            assert!((-0.55..=0.55).contains(&angle), "angle {angle}");
+            assert!(anchor_gap(joint, frame, door) < 0.05);
+            assert!(axis.dot(Vector3::unit_y()) > 0.999);
+            reached = reached.max(angle.abs());
+        });
+
+        assert!(reached > 0.45);
+    }
+
+    #[test]
+    fn hinge_motor_reaches_its_speed() {
+        let (mut world, joint) = hinged(|frame, door| {
+            Joint::hinge(frame, door, Vector3::zero(), Vector3::unit_y()).with_motor(2.0, 100.0)
+        });
+
+        run(&mut world, joint, 1.0, |_, _, _| {});
+
+        let door = world.get(world.joint(joint).unwrap().body_b()).unwrap();
+
+        assert!((door.angular_velocity().y - 2.0).abs() < 0.02);
+        assert!(world.joint_position(joint).unwrap() > 1.5);
+
+        // A weak motor only gets part of the way in the same time
+        let (mut world, joint) = hinged(|frame, door| {
+            Joint::hinge(frame, door, Vector3::zero(), Vector3::unit_y()).with_motor(2.0, 0.1)
+        });
+
+        run(&mut world, joint, 1.0, |_, _, _| {});
+
+        let door = world.get(world.joint(joint).unwrap().body_b()).unwrap();
+
+        assert!(door.angular_velocity().y < 1.0);
+    }
+
+    #[test]
+    fn slider_stays_on_its_axis() {
+        let mut world = PhysicsWorld::new();
+        let rail = world.add(RigidBody::fixed());
+        let carriage = world.add(
+            cube(Vector3::zero())
+                .with_linear_velocity(Vector3::new(1.0, 2.0, 1.0))
+                .with_angular_velocity(Vector3::new(1.0, 1.0, 1.0)),
+        );
+        let joint = world.add_joint(
+            Joint::slider(rail, carriage, Vector3::zero(), Vector3::unit_x())
+                .with_limits(-1.0, 1.0),
+        );
+        let mut travelled = 0.0f32;
+
+        run(&mut world, joint, 3.0, |joint, rail, carriage| {
+            let position = carriage.position();
+            let travel = joint.position(rail, carriage);
+
+            assert!(position.y.abs() < 0.01 && position.z.abs() < 0.01);
+            assert!(carriage.angular_velocity().magnitude() < 0.01);
+            assert!((travel - position.x).abs() < 1e-4);
+            assert!(travel < 1.02, "travel {travel}");
+            travelled = travelled.max(travel);
+        });
+
+        assert!(travelled > 0.95);
+    }
+}
@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+
+// Material defaults, roughly wood on wood
+const RESTITUTION_DEFAULT: f32 = 0.2;
+const STATIC_FRICTION_DEFAULT: f32 = 0.6;
+const DYNAMIC_FRICTION_DEFAULT: f32 = 0.4;
+
+/// Surface properties of a body. Where two bodies touch the higher
+/// restitution is used and friction coefficients are combined by geometric
+/// mean.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PhysicsMaterial {
+    /// Fraction of the approach speed kept when bouncing, 0 to 1
+    pub restitution: f32,
+    /// Friction coefficient holding resting contacts in place
+    pub static_friction: f32,
+    /// Friction coefficient opposing sliding contacts
+    pub dynamic_friction: f32,
+}
+
+impl PhysicsMaterial {
+    pub fn new(restitution: f32, static_friction: f32, dynamic_friction: f32) -> Self {
+        Self {
+            restitution,
+            static_friction,
+            dynamic_friction,
+        }
+    }
+
+    pub fn with_restitution(mut self, restitution: f32) -> Self {
+        self.restitution = restitution;
+        self
+    }
+
+    pub fn with_static_friction(mut self, static_friction: f32) -> Self {
+        self.static_friction = static_friction;
+        self
+    }
+
+    pub fn with_dynamic_friction(mut self, dynamic_friction: f32) -> Self {
+        self.dynamic_friction = dynamic_friction;
+        self
+    }
+
+    // Material used at a contact between the two
+    pub(crate) fn combine(&self, other: &PhysicsMaterial) -> PhysicsMaterial {
+        PhysicsMaterial {
+            restitution: self.restitution.max(other.restitution),
+            static_friction: (self.static_friction * other.static_friction).sqrt(),
+            dynamic_friction: (self.dynamic_friction * other.dynamic_friction).sqrt(),
+        }
+    }
+}
+
+impl Default for PhysicsMaterial {
+    fn default() -> Self {
+        Self::new(
+            RESTITUTION_DEFAULT,
+            STATIC_FRICTION_DEFAULT,
+            DYNAMIC_FRICTION_DEFAULT,
+        )
+    }
+}
@@ -0,0 +1,799 @@
+//! Rigid body dynamics driving instance transforms, see [`PhysicsWorld`]
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hasher;
+
+use cgmath::prelude::*;
+use cgmath::Vector3;
+
+use crate::hash::{StateHash, StateHasher};
+
+mod body;
+pub mod collision;
+mod config;
+mod joint;
+mod material;
+mod solver;
+
+pub use body::{cuboid_inertia, cylinder_inertia, sphere_inertia, BodyKind, RigidBody};
+pub use collision::{Collider, Contact, ContactManifold, ContactPoint, FitShape, Shape};
+pub use config::PhysicsConfig;
+pub use joint::{Joint, JointKind, JointMotor};
+pub use material::PhysicsMaterial;
+
+use solver::{ContactCache, ContactSolver, JointSolver, SolverBodies};
+
+// Gravity default, in units per second squared
+const GRAVITY_DEFAULT: Vector3<f32> = Vector3::new(0.0, -9.81, 0.0);
+
+/// Handle to a body in a [`PhysicsWorld`], invalidated when the body is removed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BodyId {
+    index: u32,
+    generation: u32,
+}
+
+impl StateHash for BodyId {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u32(self.index);
+        hasher.write_u32(self.generation);
+    }
+}
+
+/// Handle to a joint in a [`PhysicsWorld`], invalidated when the joint or
+/// either of its bodies is removed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JointId {
+    index: u32,
+    generation: u32,
+}
+
+struct Slot {
+    generation: u32,
+    body: Option<RigidBody>,
+}
+
+struct JointSlot {
+    generation: u32,
+    joint: Option<Joint>,
+}
+
+/// Rigid bodies stepped by the context every fixed update, after
+/// [`crate::Simulation::fixed_update`]. Forces applied during the update act
+/// for that step and are then cleared. Contacts between colliders are
+/// resolved with sequential impulses along with the joints, and bodies that
+/// come to rest sleep.
+pub struct PhysicsWorld {
+    slots: Vec<Slot>,
+    free_indices: Vec<u32>,
+    len: usize,
+    joint_slots: Vec<JointSlot>,
+    free_joint_indices: Vec<u32>,
+    gravity: Vector3<f32>,
+    config: PhysicsConfig,
+    contacts: Vec<Contact>,
+    // Impulses from the last step's contacts, kept for sleeping pairs
+    contact_cache: ContactCache,
+}
+
+impl PhysicsWorld {
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            free_indices: Vec::new(),
+            len: 0,
+            joint_slots: Vec::new(),
+            free_joint_indices: Vec::new(),
+            gravity: GRAVITY_DEFAULT,
+            config: PhysicsConfig::default(),
+            contacts: Vec::new(),
+            contact_cache: HashMap::new(),
+        }
+    }
+
+    pub fn with_config(mut self, config: PhysicsConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn config(&self) -> &PhysicsConfig {
+        &self.config
+    }
+
+    pub fn config_mut(&mut self) -> &mut PhysicsConfig {
+        &mut self.config
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn gravity(&self) -> Vector3<f32> {
+        self.gravity
+    }
+
+    pub fn set_gravity(&mut self, gravity: Vector3<f32>) {
+        self.gravity = gravity;
+    }
+
+    pub fn add(&mut self, body: RigidBody) -> BodyId {
+        self.len += 1;
+
+        match self.free_indices.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.body = Some(body);
+
+                BodyId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    body: Some(body),
+                });
+
+                BodyId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: BodyId) -> Option<RigidBody> {
+        let slot = self
+            .slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)?;
+        let body = slot.body.take()?;
+
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_indices.push(id.index);
+        self.len -= 1;
+
+        // Wake whatever was resting on the body
+        let touching = self
+            .contact_cache
+            .keys()
+            .filter_map(|(a, b)| match (*a == id, *b == id) {
+                (true, _) => Some(*b),
+                (_, true) => Some(*a),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        for other in touching {
+            if let Some(body) = self.get_mut(other) {
+                body.wake();
+            }
+        }
+
+        self.contact_cache.retain(|(a, b), _| *a != id && *b != id);
+        self.contacts
+            .retain(|contact| contact.body_a != id && contact.body_b != id);
+
+        let joined = self
+            .joints()
+            .filter(|(_, joint)| joint.body_a() == id || joint.body_b() == id)
+            .map(|(joint_id, _)| joint_id)
+            .collect::<Vec<_>>();
+
+        for joint_id in joined {
+            self.remove_joint(joint_id);
+        }
+
+        Some(body)
+    }
+
+    pub fn contains(&self, id: BodyId) -> bool {
+        self.get(id).is_some()
+    }
+
+    pub fn get(&self, id: BodyId) -> Option<&RigidBody> {
+        self.slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.body.as_ref())
+    }
+
+    pub fn get_mut(&mut self, id: BodyId) -> Option<&mut RigidBody> {
+        self.slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.body.as_mut())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (BodyId, &RigidBody)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.body.as_ref().map(|body| {
+                (
+                    BodyId {
+                        index: index as u32,
+                        generation: slot.generation,
+                    },
+                    body,
+                )
+            })
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (BodyId, &mut RigidBody)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let generation = slot.generation;
+
+                slot.body.as_mut().map(|body| {
+                    (
+                        BodyId {
+                            index: index as u32,
+                            generation,
+                        },
+                        body,
+                    )
+                })
+            })
+    }
+
+    /// Adds a joint placed as its bodies are now. Panics if either body isn't
+    /// in the world or they're the same body.
+    pub fn add_joint(&mut self, mut joint: Joint) -> JointId {
+        assert_ne!(
+            joint.body_a(),
+            joint.body_b(),
+            "Joint between a body and itself"
+        );
+
+        let (Some(a), Some(b)) = (self.get(joint.body_a()), self.get(joint.body_b())) else {
+            panic!("Joint between bodies not in the world");
+        };
+
+        joint.attach(a, b);
+
+        for id in [joint.body_a(), joint.body_b()] {
+            self.get_mut(id).unwrap().wake();
+        }
+
+        match self.free_joint_indices.pop() {
+            Some(index) => {
+                let slot = &mut self.joint_slots[index as usize];
+                slot.joint = Some(joint);
+
+                JointId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.joint_slots.push(JointSlot {
+                    generation: 0,
+                    joint: Some(joint),
+                });
+
+                JointId {
+                    index: self.joint_slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        }
+    }
+
+    /// Removes a joint, waking the bodies it held
+    pub fn remove_joint(&mut self, id: JointId) -> Option<Joint> {
+        let slot = self
+            .joint_slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)?;
+        let joint = slot.joint.take()?;
+
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_joint_indices.push(id.index);
+
+        for body in [joint.body_a(), joint.body_b()] {
+            if let Some(body) = self.get_mut(body) {
+                body.wake();
+            }
+        }
+
+        Some(joint)
+    }
+
+    pub fn joint(&self, id: JointId) -> Option<&Joint> {
+        self.joint_slots
+            .get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.joint.as_ref())
+    }
+
+    /// Changing a joint's limits or motor doesn't wake its bodies
+    pub fn joint_mut(&mut self, id: JointId) -> Option<&mut Joint> {
+        self.joint_slots
+            .get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.joint.as_mut())
+    }
+
+    pub fn joints(&self) -> impl Iterator<Item = (JointId, &Joint)> {
+        self.joint_slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                slot.joint.as_ref().map(|joint| {
+                    (
+                        JointId {
+                            index: index as u32,
+                            generation: slot.generation,
+                        },
+                        joint,
+                    )
+                })
+            })
+    }
+
+    /// Hinge angle or slider travel of a joint, see [`Joint::position`]
+    pub fn joint_position(&self, id: JointId) -> Option<f32> {
+        let joint = self.joint(id)?;
+
+        Some(joint.position(self.get(joint.body_a())?, self.get(joint.body_b())?))
+    }
+
+    /// Advances every body by `dt` seconds and clears their forces. Contacts
+    /// are found and resolved between the velocity and position updates.
+    pub fn step(&mut self, dt: f32) {
+        if dt <= 0.0 {
+            return;
+        }
+
+        let gravity = self.gravity;
+
+        for (_, body) in self.iter_mut() {
+            body.integrate_velocities(gravity, dt);
+        }
+
+        self.detect_collisions();
+        self.wake_touched();
+        self.solve(dt);
+
+        for (_, body) in self.iter_mut() {
+            body.integrate_positions(dt);
+            body.clear_forces();
+        }
+
+        self.update_sleeping(dt);
+    }
+
+    /// Replaces the contacts with those between every overlapping pair of
+    /// colliders where at least one body is dynamic and one is awake, other
+    /// than bodies joined by a joint without collisions
+    pub fn detect_collisions(&mut self) {
+        let joined = self
+            .joints()
+            .filter(|(_, joint)| !joint.collisions())
+            .map(|(_, joint)| ordered(joint.body_a(), joint.body_b()))
+            .collect::<HashSet<_>>();
+        let colliders = self
+            .iter()
+            .filter_map(|(id, body)| {
+                body.collider()
+                    .map(|collider| (id, body, collider, collider.bounds(&body.pose())))
+            })
+            .collect::<Vec<_>>();
+        let bounds = colliders
+            .iter()
+            .map(|(.., bounds)| *bounds)
+            .collect::<Vec<_>>();
+
+        let contacts = collision::sweep_and_prune(&bounds)
+            .into_iter()
+            .filter_map(|(first, second)| {
+                let (body_a, a, collider_a, _) = colliders[first];
+                let (body_b, b, collider_b, _) = colliders[second];
+
+                if !(a.is_dynamic() || b.is_dynamic())
+                    || !(a.is_awake() || b.is_awake())
+                    || joined.contains(&ordered(body_a, body_b))
+                {
+                    return None;
+                }
+
+                let (pose_a, pose_b) = (a.pose(), b.pose());
+                let mut manifold = collision::collide(collider_a, &pose_a, collider_b, &pose_b)?;
+
+                if manifold.points.len() == 1 {
+                    if let Some(cached) = self.contact_cache.get(&(body_a, body_b)) {
+                        solver::merge_cached(&mut manifold, cached, &pose_a, &pose_b);
+                    }
+                }
+
+                Some(Contact {
+                    body_a,
+                    body_b,
+                    manifold,
+                })
+            })
+            .collect();
+
+        self.contacts = contacts;
+    }
+
+    // Wakes sleeping bodies hit by or joined to moving ones, so they respond
+    // this step
+    fn wake_touched(&mut self) {
+        let moving = |body: &RigidBody| match body.kind() {
+            BodyKind::Dynamic => !body.is_sleeping() && body.resting_time() == 0.0,
+            BodyKind::Kinematic => {
+                body.linear_velocity().magnitude2() > 0.0
+                    || body.angular_velocity().magnitude2() > 0.0
+            }
+            BodyKind::Static => false,
+        };
+
+        let woken = self
+            .contacts
+            .iter()
+            .map(|contact| (contact.body_a, contact.body_b))
+            .chain(
+                self.joints()
+                    .map(|(_, joint)| (joint.body_a(), joint.body_b())),
+            )
+            .flat_map(|(a, b)| [(a, b), (b, a)])
+            .filter(|(sleeper, other)| {
+                self.get(*sleeper).unwrap().is_sleeping() && moving(self.get(*other).unwrap())
+            })
+            .map(|(sleeper, _)| sleeper)
+            .collect::<Vec<_>>();
+
+        for id in woken {
+            self.get_mut(id).unwrap().wake();
+        }
+    }
+
+    // Runs the contact and joint solvers and writes the velocities back
+    fn solve(&mut self, dt: f32) {
+        let mut bodies = SolverBodies::default();
+        let mut contacts = ContactSolver::new(
+            &self.contacts,
+            |id| self.get(id).unwrap(),
+            &mut bodies,
+            &self.contact_cache,
+            &self.config,
+            dt,
+        );
+
+        let mut joints = JointSolver::new(
+            self.joints().filter(|(_, joint)| {
+                [joint.body_a(), joint.body_b()]
+                    .map(|id| self.get(id).unwrap())
+                    .into_iter()
+                    .any(|body| body.is_dynamic() && !body.is_sleeping())
+            }),
+            |id| self.get(id).unwrap(),
+            &mut bodies,
+            &self.config,
+            dt,
+        );
+
+        contacts.warm_start(&mut bodies);
+        joints.warm_start(&mut bodies);
+
+        let iterations = self
+            .config
+            .solver_iterations
+            .max(self.config.joint_iterations);
+
+        for iteration in 0..iterations {
+            if iteration < self.config.solver_iterations {
+                contacts.solve(&mut bodies);
+            }
+
+            if iteration < self.config.joint_iterations {
+                joints.solve(&mut bodies);
+            }
+        }
+
+        let impulses = joints.impulses().collect::<Vec<_>>();
+
+        for (id, impulses) in impulses {
+            self.joint_mut(id).unwrap().set_impulses(impulses);
+        }
+
+        // Pairs with a sleeping body weren't solved, keep their impulses
+        // for when they wake
+        let mut cache = contacts.cache();
+
+        for (pair, points) in std::mem::take(&mut self.contact_cache) {
+            let sleeping = |id: BodyId| self.get(id).is_some_and(RigidBody::is_sleeping);
+
+            if sleeping(pair.0) || sleeping(pair.1) {
+                cache.entry(pair).or_insert(points);
+            }
+        }
+
+        self.contact_cache = cache;
+
+        for (id, solved) in bodies.iter() {
+            let body = self.get_mut(*id).unwrap();
+
+            if body.is_dynamic() && !body.is_sleeping() {
+                body.set_solved_velocities(solved.linear_velocity, solved.angular_velocity);
+            }
+        }
+    }
+
+    // Puts groups of touching or joined dynamic bodies to sleep once all of them have
+    // rested long enough
+    fn update_sleeping(&mut self, dt: f32) {
+        if !self.config.sleeping {
+            return;
+        }
+
+        let (linear, angular) = (
+            self.config.sleep_linear_velocity,
+            self.config.sleep_angular_velocity,
+        );
+
+        for (_, body) in self.iter_mut() {
+            if body.is_dynamic() && !body.is_sleeping() {
+                body.update_resting_time(linear, angular, dt);
+            }
+        }
+
+        // Union find over the slots linked by contacts and joints
+        let mut parents = (0..self.slots.len()).collect::<Vec<_>>();
+
+        fn root(parents: &mut [usize], mut index: usize) -> usize {
+            while parents[index] != index {
+                parents[index] = parents[parents[index]];
+                index = parents[index];
+            }
+
+            index
+        }
+
+        let links = self
+            .contacts
+            .iter()
+            .map(|contact| (contact.body_a, contact.body_b))
+            .chain(
+                self.joints()
+                    .map(|(_, joint)| (joint.body_a(), joint.body_b())),
+            )
+            .collect::<Vec<_>>();
+
+        for (a, b) in links {
+            if self.get(a).unwrap().is_dynamic() && self.get(b).unwrap().is_dynamic() {
+                let root_a = root(&mut parents, a.index as usize);
+                let root_b = root(&mut parents, b.index as usize);
+
+                parents[root_a] = root_b;
+            }
+        }
+
+        let mut restless = vec![false; self.slots.len()];
+
+        for (id, body) in self.iter() {
+            let ready = body.is_sleeping() || body.resting_time() >= self.config.time_to_sleep;
+
+            if body.is_dynamic() && !ready {
+                restless[root(&mut parents, id.index as usize)] = true;
+            }
+        }
+
+        for index in 0..self.slots.len() {
+            let island = root(&mut parents, index);
+
+            if let Some(body) = self.slots[index].body.as_mut() {
+                if body.is_dynamic() && !body.is_sleeping() && !restless[island] {
+                    body.sleep();
+                }
+            }
+        }
+    }
+
+    /// Contacts found in the last step
+    pub fn contacts(&self) -> &[Contact] {
+        &self.contacts
+    }
+
+    /// Contacts involving `id` found in the last step
+    pub fn contacts_with(&self, id: BodyId) -> impl Iterator<Item = &Contact> {
+        self.contacts
+            .iter()
+            .filter(move |contact| contact.body_a == id || contact.body_b == id)
+    }
+}
+
+// The pair with the lower id first
+fn ordered(a: BodyId, b: BodyId) -> (BodyId, BodyId) {
+    (a.min(b), a.max(b))
+}
+
+impl Default for PhysicsWorld {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StateHash for PhysicsWorld {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_usize(self.len);
+
+        for (id, body) in self.iter() {
+            id.hash_state(hasher);
+            body.hash_state(hasher);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, Quaternion};
+
+    use super::*;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    // Static floor with its top face at y = 0
+    fn floor(world: &mut PhysicsWorld, material: PhysicsMaterial) -> BodyId {
+        world.add(
+            RigidBody::fixed()
+                .with_position(Vector3::new(0.0, -0.5, 0.0))
+                .with_collider(Collider::aabb(Vector3::new(20.0, 0.5, 20.0)))
+                .with_material(material),
+        )
+    }
+
+    fn unit_box(position: Vector3<f32>) -> RigidBody {
+        let half_extents = Vector3::from_value(0.5);
+
+        RigidBody::dynamic(1.0, cuboid_inertia(1.0, half_extents))
+            .with_position(position)
+            .with_collider(Collider::obb(half_extents))
+    }
+
+    fn steps(world: &mut PhysicsWorld, seconds: f32, mut each: impl FnMut(&PhysicsWorld)) {
+        for _ in 0..(seconds / DT).round() as usize {
+            world.step(DT);
+            each(world);
+        }
+    }
+
+    #[test]
+    fn box_settles_on_the_floor_and_sleeps() {
+        let mut world = PhysicsWorld::new();
+        floor(&mut world, PhysicsMaterial::default());
+        let body = world.add(unit_box(Vector3::new(0.0, 2.0, 0.0)));
+        let mut lowest = f32::MAX;
+
+        // Let the landing play out, then watch it rest
+        steps(&mut world, 1.0, |_| {});
+        steps(&mut world, 2.0, |world| {
+            lowest = lowest.min(world.get(body).unwrap().position().y);
+        });
+
+        let body = world.get(body).unwrap();
+
+        assert!(lowest > 0.5 - 0.01, "sank to {lowest}");
+        assert!((body.position().y - 0.5).abs() < 0.01);
+        assert!(body.linear_velocity().magnitude() < 0.05);
+        assert!(body.is_sleeping());
+    }
+
+    // Highest the ball's bottom rises after its first bounce off the floor
+    fn bounce_height(restitution: f32) -> f32 {
+        let mut world = PhysicsWorld::new().with_config(PhysicsConfig::new().with_sleeping(false));
+        floor(&mut world, PhysicsMaterial::default().with_restitution(0.0));
+        let ball = world.add(
+            RigidBody::dynamic(1.0, sphere_inertia(1.0, 0.5))
+                .with_position(Vector3::new(0.0, 2.5, 0.0))
+                .with_collider(Collider::sphere(0.5))
+                .with_material(PhysicsMaterial::default().with_restitution(restitution)),
+        );
+        let mut bounced = false;
+        let mut height = 0.0f32;
+
+        steps(&mut world, 1.5, |world| {
+            let ball = world.get(ball).unwrap();
+
+            bounced |= ball.linear_velocity().y > 0.0;
+
+            if bounced {
+                height = height.max(ball.position().y - 0.5);
+            }
+        });
+
+        height
+    }
+
+    #[test]
+    fn bounce_height_scales_with_restitution() {
+        let heights = [0.0, 0.5, 0.8].map(|restitution| (restitution, bounce_height(restitution)));
+
+        assert!(heights[0].1 < 0.05);
+
+        // Dropped from a height of 2, bounces reach about e² of it
+        for (restitution, height) in &heights[1..] {
+            let expected = restitution * restitution * 2.0;
+
+            assert!(
+                (height - expected).abs() < 0.2 * expected,
+                "restitution {restitution} bounced to {height}, expected {expected}"
+            );
+        }
+    }
+
+    // How far a box slides along a 20° slope in two seconds
+    fn slide(friction: f32) -> f32 {
+        let mut world = PhysicsWorld::new();
+        let tilt = Quaternion::from_axis_angle(Vector3::unit_z(), Deg(20.0));
+        let up = tilt.rotate_vector(Vector3::unit_y());
+        let material = PhysicsMaterial::new(0.0, friction, friction);
+
+        world.add(
+            RigidBody::fixed()
+                .with_orientation(tilt)
+                .with_position(-up * 0.5)
+                .with_collider(Collider::obb(Vector3::new(20.0, 0.5, 20.0)))
+                .with_material(material),
+        );
+
+        let start = up * 0.5;
+        let body = world.add(
+            unit_box(start)
+                .with_orientation(tilt)
+                .with_material(material),
+        );
+
+        steps(&mut world, 2.0, |_| {});
+
+        (world.get(body).unwrap().position() - start).magnitude()
+    }
+
+    #[test]
+    fn friction_holds_or_releases_a_box_on_a_slope() {
+        // tan 20° is about 0.36
+        assert!(slide(0.8) < 0.02);
+        assert!(slide(0.1) > 1.0);
+    }
+
+    #[test]
+    fn sleeping_stack_wakes_when_hit() {
+        let mut world = PhysicsWorld::new();
+        floor(&mut world, PhysicsMaterial::default());
+        let stack = (0..3)
+            .map(|i| world.add(unit_box(Vector3::new(0.0, 0.5 + i as f32, 0.0))))
+            .collect::<Vec<_>>();
+
+        steps(&mut world, 3.0, |_| {});
+
+        assert!(stack.iter().all(|id| world.get(*id).unwrap().is_sleeping()));
+
+        let resting = world.get(stack[1]).unwrap().position();
+
+        world.add(
+            RigidBody::dynamic(1.0, sphere_inertia(1.0, 0.25))
+                .with_position(Vector3::new(-2.0, 1.5, 0.0))
+                .with_linear_velocity(Vector3::new(10.0, 0.0, 0.0))
+                .with_collider(Collider::sphere(0.25)),
+        );
+
+        let mut woken = [false; 3];
+
+        steps(&mut world, 0.5, |world| {
+            for (woken, id) in woken.iter_mut().zip(&stack) {
+                *woken |= !world.get(*id).unwrap().is_sleeping();
+            }
+        });
+
+        assert!(woken.iter().all(|woken| *woken), "woken {woken:?}");
+        assert!(world.get(stack[1]).unwrap().position().x - resting.x > 0.05);
+    }
+}
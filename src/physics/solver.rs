@@ -0,0 +1,594 @@
+use std::collections::HashMap;
+
+use cgmath::prelude::*;
+use cgmath::{Matrix3, Vector3};
+
+use super::collision::{Contact, ContactManifold, ContactPoint};
+use super::config::PhysicsConfig;
+use super::joint::{Joint, JOINT_ROWS};
+use super::{BodyId, JointId, RigidBody};
+use crate::geometry::Pose;
+
+// Sliding speed below which contacts hold with static friction
+const STATIC_FRICTION_SPEED: f32 = 0.1;
+// Distance in body space within which a new contact point takes over a
+// cached point's impulses
+const MATCH_DISTANCE: f32 = 0.05;
+// Separation or drift beyond which cached points stop topping up single
+// point manifolds
+const PERSISTENT_MARGIN: f32 = 0.02;
+
+/// Velocities and inverse mass of a body while constraints are solved.
+/// Sleeping and non-dynamic bodies have no inverse mass.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SolverBody {
+    pub position: Vector3<f32>,
+    pub linear_velocity: Vector3<f32>,
+    pub angular_velocity: Vector3<f32>,
+    pub inverse_mass: f32,
+    pub inverse_inertia: Matrix3<f32>,
+}
+
+impl SolverBody {
+    fn new(body: &RigidBody) -> Self {
+        let movable = body.is_dynamic() && !body.is_sleeping();
+
+        Self {
+            position: body.position(),
+            linear_velocity: body.linear_velocity(),
+            angular_velocity: body.angular_velocity(),
+            inverse_mass: if movable { body.inverse_mass() } else { 0.0 },
+            inverse_inertia: if movable {
+                body.world_inverse_inertia()
+            } else {
+                Matrix3::zero()
+            },
+        }
+    }
+
+    pub fn velocity_at(&self, offset: Vector3<f32>) -> Vector3<f32> {
+        self.linear_velocity + self.angular_velocity.cross(offset)
+    }
+
+    pub fn apply_impulse(&mut self, impulse: Vector3<f32>, offset: Vector3<f32>) {
+        self.linear_velocity += impulse * self.inverse_mass;
+        self.angular_velocity += self.inverse_inertia * offset.cross(impulse);
+    }
+
+    // Applies an impulse with separate linear and angular parts, as joint
+    // rows push
+    pub fn apply_split_impulse(&mut self, linear: Vector3<f32>, angular: Vector3<f32>) {
+        self.linear_velocity += linear * self.inverse_mass;
+        self.angular_velocity += self.inverse_inertia * angular;
+    }
+
+    // Inverse of the mass felt by an impulse along `direction` at `offset`
+    pub fn inverse_mass_along(&self, offset: Vector3<f32>, direction: Vector3<f32>) -> f32 {
+        let angular = self.inverse_inertia * offset.cross(direction);
+
+        self.inverse_mass + angular.cross(offset).dot(direction)
+    }
+}
+
+/// The bodies touched by this step's constraints, indexed in the order they
+/// were first seen
+#[derive(Default)]
+pub(crate) struct SolverBodies {
+    indices: HashMap<BodyId, usize>,
+    bodies: Vec<(BodyId, SolverBody)>,
+}
+
+impl SolverBodies {
+    pub fn index(&mut self, id: BodyId, body: &RigidBody) -> usize {
+        *self.indices.entry(id).or_insert_with(|| {
+            self.bodies.push((id, SolverBody::new(body)));
+            self.bodies.len() - 1
+        })
+    }
+
+    pub fn get(&self, index: usize) -> &SolverBody {
+        &self.bodies[index].1
+    }
+
+    pub fn pair_mut(&mut self, a: usize, b: usize) -> (&mut SolverBody, &mut SolverBody) {
+        assert_ne!(a, b, "Constraint between a body and itself");
+
+        if a < b {
+            let (low, high) = self.bodies.split_at_mut(b);
+            (&mut low[a].1, &mut high[0].1)
+        } else {
+            let (low, high) = self.bodies.split_at_mut(a);
+            (&mut high[0].1, &mut low[b].1)
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(BodyId, SolverBody)> {
+        self.bodies.iter()
+    }
+}
+
+/// Impulses of a contact point kept between steps to warm start the solver
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct CachedPoint {
+    local_a: Vector3<f32>,
+    local_b: Vector3<f32>,
+    normal_impulse: f32,
+    tangent_impulse: [f32; 2],
+}
+
+pub(crate) type ContactCache = HashMap<(BodyId, BodyId), Vec<CachedPoint>>;
+
+/// Tops up a single point manifold, as found for convex hulls, with the
+/// cached points from earlier steps that still touch. Faces resting on
+/// faces build up a stable manifold over a few steps this way.
+pub(crate) fn merge_cached(
+    manifold: &mut ContactManifold,
+    cached: &[CachedPoint],
+    pose_a: &Pose,
+    pose_b: &Pose,
+) {
+    for point in cached {
+        let point_a = pose_a.transform_point(point.local_a);
+        let point_b = pose_b.transform_point(point.local_b);
+        let offset = point_a - point_b;
+        let depth = offset.dot(manifold.normal);
+        let drift = (offset - manifold.normal * depth).magnitude();
+        let position = (point_a + point_b) * 0.5;
+        let repeated = manifold
+            .points
+            .iter()
+            .any(|existing| (existing.position() - position).magnitude() < MATCH_DISTANCE);
+
+        if depth > -PERSISTENT_MARGIN && drift < PERSISTENT_MARGIN && !repeated {
+            manifold.points.push(ContactPoint {
+                point_a,
+                point_b,
+                depth,
+            });
+        }
+    }
+
+    manifold.reduce();
+}
+
+struct PointConstraint {
+    offset_a: Vector3<f32>,
+    offset_b: Vector3<f32>,
+    local_a: Vector3<f32>,
+    local_b: Vector3<f32>,
+    normal_mass: f32,
+    tangent_mass: [f32; 2],
+    // Normal speed the solver drives the contact towards
+    target_velocity: f32,
+    friction: f32,
+    normal_impulse: f32,
+    tangent_impulse: [f32; 2],
+}
+
+struct ContactConstraint {
+    pair: (BodyId, BodyId),
+    a: usize,
+    b: usize,
+    normal: Vector3<f32>,
+    tangents: [Vector3<f32>; 2],
+    points: Vec<PointConstraint>,
+}
+
+/// Sequential impulse solver for one step's contacts
+pub(crate) struct ContactSolver {
+    constraints: Vec<ContactConstraint>,
+}
+
+impl ContactSolver {
+    pub fn new<'a>(
+        contacts: &[Contact],
+        body: impl Fn(BodyId) -> &'a RigidBody,
+        bodies: &mut SolverBodies,
+        cache: &ContactCache,
+        config: &PhysicsConfig,
+        dt: f32,
+    ) -> Self {
+        let constraints = contacts
+            .iter()
+            .map(|contact| {
+                let body_a = body(contact.body_a);
+                let body_b = body(contact.body_b);
+                let a = bodies.index(contact.body_a, body_a);
+                let b = bodies.index(contact.body_b, body_b);
+                let solver_a = *bodies.get(a);
+                let solver_b = *bodies.get(b);
+
+                let material = body_a.material().combine(body_b.material());
+                let normal = contact.manifold.normal;
+                let tangents = tangent_basis(normal);
+                let cached = cache
+                    .get(&(contact.body_a, contact.body_b))
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                let (pose_a, pose_b) = (body_a.pose(), body_b.pose());
+
+                let points = contact
+                    .manifold
+                    .points
+                    .iter()
+                    .map(|point| {
+                        let position = point.position();
+                        let offset_a = position - solver_a.position;
+                        let offset_b = position - solver_b.position;
+                        let local_a = pose_a.inverse_transform_point(point.point_a);
+                        let local_b = pose_b.inverse_transform_point(point.point_b);
+                        let mass = |direction: Vector3<f32>| {
+                            let inverse = solver_a.inverse_mass_along(offset_a, direction)
+                                + solver_b.inverse_mass_along(offset_b, direction);
+
+                            if inverse > 0.0 {
+                                1.0 / inverse
+                            } else {
+                                0.0
+                            }
+                        };
+
+                        let relative =
+                            solver_b.velocity_at(offset_b) - solver_a.velocity_at(offset_a);
+                        let normal_velocity = relative.dot(normal);
+                        let sliding = (relative - normal * normal_velocity).magnitude();
+
+                        // Bounce off fast approaches, push out penetration
+                        // beyond the slop and let separated points close
+                        let bounce = if normal_velocity < -config.restitution_threshold {
+                            -material.restitution * normal_velocity
+                        } else {
+                            0.0
+                        };
+                        let correction = if point.depth > config.penetration_slop {
+                            config.position_correction * (point.depth - config.penetration_slop)
+                                / dt
+                        } else {
+                            point.depth.min(0.0) / dt
+                        };
+
+                        let (normal_impulse, tangent_impulse) = cached
+                            .iter()
+                            .map(|cached| (cached, (cached.local_a - local_a).magnitude2()))
+                            .filter(|(_, distance)| *distance < MATCH_DISTANCE * MATCH_DISTANCE)
+                            .min_by(|a, b| a.1.total_cmp(&b.1))
+                            .map_or((0.0, [0.0; 2]), |(cached, _)| {
+                                (cached.normal_impulse, cached.tangent_impulse)
+                            });
+
+                        PointConstraint {
+                            offset_a,
+                            offset_b,
+                            local_a,
+                            local_b,
+                            normal_mass: mass(normal),
+                            tangent_mass: tangents.map(mass),
+                            target_velocity: bounce.max(correction),
+                            friction: if sliding < STATIC_FRICTION_SPEED {
+                                material.static_friction
+                            } else {
+                                material.dynamic_friction
+                            },
+                            normal_impulse,
+                            tangent_impulse,
+                        }
+                    })
+                    .collect();
+
+                ContactConstraint {
+                    pair: (contact.body_a, contact.body_b),
+                    a,
+                    b,
+                    normal,
+                    tangents,
+                    points,
+                }
+            })
+            .collect();
+
+        Self { constraints }
+    }
+
+    /// Applies the impulses carried over from the last step
+    pub fn warm_start(&self, bodies: &mut SolverBodies) {
+        for constraint in &self.constraints {
+            let (a, b) = bodies.pair_mut(constraint.a, constraint.b);
+
+            for point in &constraint.points {
+                let impulse = constraint.normal * point.normal_impulse
+                    + constraint.tangents[0] * point.tangent_impulse[0]
+                    + constraint.tangents[1] * point.tangent_impulse[1];
+
+                a.apply_impulse(-impulse, point.offset_a);
+                b.apply_impulse(impulse, point.offset_b);
+            }
+        }
+    }
+
+    /// One pass over every contact point, friction then the normal
+    pub fn solve(&mut self, bodies: &mut SolverBodies) {
+        for constraint in &mut self.constraints {
+            let (a, b) = bodies.pair_mut(constraint.a, constraint.b);
+
+            for point in &mut constraint.points {
+                // Friction, clamped to the cone of the current normal impulse
+                let relative = b.velocity_at(point.offset_b) - a.velocity_at(point.offset_a);
+                let previous = point.tangent_impulse;
+                let mut tangent_impulse = [0, 1].map(|i| {
+                    previous[i] - relative.dot(constraint.tangents[i]) * point.tangent_mass[i]
+                });
+
+                let limit = point.friction * point.normal_impulse;
+                let magnitude = (tangent_impulse[0].powi(2) + tangent_impulse[1].powi(2)).sqrt();
+
+                if magnitude > limit {
+                    let scale = if magnitude > 0.0 {
+                        limit / magnitude
+                    } else {
+                        0.0
+                    };
+                    tangent_impulse = tangent_impulse.map(|impulse| impulse * scale);
+                }
+
+                point.tangent_impulse = tangent_impulse;
+
+                let impulse = constraint.tangents[0] * (tangent_impulse[0] - previous[0])
+                    + constraint.tangents[1] * (tangent_impulse[1] - previous[1]);
+
+                a.apply_impulse(-impulse, point.offset_a);
+                b.apply_impulse(impulse, point.offset_b);
+
+                // Normal, accumulated impulse never pulls the bodies together
+                let relative = b.velocity_at(point.offset_b) - a.velocity_at(point.offset_a);
+                let normal_velocity = relative.dot(constraint.normal);
+                let delta = point.normal_mass * (point.target_velocity - normal_velocity);
+                let previous = point.normal_impulse;
+
+                point.normal_impulse = (previous + delta).max(0.0);
+
+                let impulse = constraint.normal * (point.normal_impulse - previous);
+
+                a.apply_impulse(-impulse, point.offset_a);
+                b.apply_impulse(impulse, point.offset_b);
+            }
+        }
+    }
+
+    /// Impulses to warm start the next step with
+    pub fn cache(&self) -> ContactCache {
+        self.constraints
+            .iter()
+            .map(|constraint| {
+                let points = constraint
+                    .points
+                    .iter()
+                    .map(|point| CachedPoint {
+                        local_a: point.local_a,
+                        local_b: point.local_b,
+                        normal_impulse: point.normal_impulse,
+                        tangent_impulse: point.tangent_impulse,
+                    })
+                    .collect();
+
+                (constraint.pair, points)
+            })
+            .collect()
+    }
+}
+
+/// One degree of freedom a joint removes. The row's velocity is
+/// `linear . (v_b - v_a) + angular_b . w_b - angular_a . w_a`, and the solver
+/// drives it to `target_velocity` with an accumulated impulse kept within
+/// `lower..upper`.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct JointRow {
+    // Index into the joint's impulses the row is warm started from
+    pub slot: usize,
+    pub linear: Vector3<f32>,
+    pub angular_a: Vector3<f32>,
+    pub angular_b: Vector3<f32>,
+    pub target_velocity: f32,
+    pub lower: f32,
+    pub upper: f32,
+    pub impulse: f32,
+}
+
+impl JointRow {
+    /// Row along `direction` between points at the given offsets from each
+    /// body's centre
+    pub fn linear(
+        slot: usize,
+        direction: Vector3<f32>,
+        offset_a: Vector3<f32>,
+        offset_b: Vector3<f32>,
+        target_velocity: f32,
+    ) -> Self {
+        Self {
+            slot,
+            linear: direction,
+            angular_a: offset_a.cross(direction),
+            angular_b: offset_b.cross(direction),
+            target_velocity,
+            lower: f32::NEG_INFINITY,
+            upper: f32::INFINITY,
+            impulse: 0.0,
+        }
+    }
+
+    /// Row of the relative angular velocity about `axis`
+    pub fn angular(slot: usize, axis: Vector3<f32>, target_velocity: f32) -> Self {
+        Self {
+            slot,
+            linear: Vector3::zero(),
+            angular_a: axis,
+            angular_b: axis,
+            target_velocity,
+            lower: f32::NEG_INFINITY,
+            upper: f32::INFINITY,
+            impulse: 0.0,
+        }
+    }
+
+    pub fn with_slot(mut self, slot: usize) -> Self {
+        self.slot = slot;
+        self
+    }
+
+    pub fn with_bounds(mut self, lower: f32, upper: f32) -> Self {
+        self.lower = lower;
+        self.upper = upper;
+        self
+    }
+
+    fn velocity(&self, a: &SolverBody, b: &SolverBody) -> f32 {
+        self.linear.dot(b.linear_velocity - a.linear_velocity)
+            + self.angular_b.dot(b.angular_velocity)
+            - self.angular_a.dot(a.angular_velocity)
+    }
+
+    fn apply(&self, a: &mut SolverBody, b: &mut SolverBody, impulse: f32) {
+        a.apply_split_impulse(-self.linear * impulse, -self.angular_a * impulse);
+        b.apply_split_impulse(self.linear * impulse, self.angular_b * impulse);
+    }
+}
+
+struct JointConstraint {
+    id: JointId,
+    a: usize,
+    b: usize,
+    // Rows with their effective masses
+    rows: Vec<(JointRow, f32)>,
+}
+
+/// Sequential impulse solver for one step's joints
+pub(crate) struct JointSolver {
+    constraints: Vec<JointConstraint>,
+}
+
+impl JointSolver {
+    pub fn new<'a>(
+        joints: impl Iterator<Item = (JointId, &'a Joint)>,
+        body: impl Fn(BodyId) -> &'a RigidBody,
+        bodies: &mut SolverBodies,
+        config: &PhysicsConfig,
+        dt: f32,
+    ) -> Self {
+        let constraints = joints
+            .map(|(id, joint)| {
+                let body_a = body(joint.body_a());
+                let body_b = body(joint.body_b());
+                let a = bodies.index(joint.body_a(), body_a);
+                let b = bodies.index(joint.body_b(), body_b);
+                let (solver_a, solver_b) = (bodies.get(a), bodies.get(b));
+
+                let rows = joint
+                    .rows(body_a, body_b, config.position_correction / dt, dt)
+                    .into_iter()
+                    .filter_map(|row| {
+                        let inverse = row.linear.magnitude2()
+                            * (solver_a.inverse_mass + solver_b.inverse_mass)
+                            + row.angular_a.dot(solver_a.inverse_inertia * row.angular_a)
+                            + row.angular_b.dot(solver_b.inverse_inertia * row.angular_b);
+
+                        (inverse > f32::EPSILON).then(|| (row, 1.0 / inverse))
+                    })
+                    .collect();
+
+                JointConstraint { id, a, b, rows }
+            })
+            .collect();
+
+        Self { constraints }
+    }
+
+    /// Applies the impulses carried over from the last step
+    pub fn warm_start(&self, bodies: &mut SolverBodies) {
+        for constraint in &self.constraints {
+            let (a, b) = bodies.pair_mut(constraint.a, constraint.b);
+
+            for (row, _) in &constraint.rows {
+                row.apply(a, b, row.impulse);
+            }
+        }
+    }
+
+    /// One pass over every joint's rows
+    pub fn solve(&mut self, bodies: &mut SolverBodies) {
+        for constraint in &mut self.constraints {
+            let (a, b) = bodies.pair_mut(constraint.a, constraint.b);
+
+            for (row, mass) in &mut constraint.rows {
+                let delta = *mass * (row.target_velocity - row.velocity(a, b));
+                let previous = row.impulse;
+
+                row.impulse = (previous + delta).clamp(row.lower, row.upper);
+                row.apply(a, b, row.impulse - previous);
+            }
+        }
+    }
+
+    /// Accumulated impulses to warm start each joint's next step with
+    pub fn impulses(&self) -> impl Iterator<Item = (JointId, [f32; JOINT_ROWS])> + '_ {
+        self.constraints.iter().map(|constraint| {
+            let mut impulses = [0.0; JOINT_ROWS];
+
+            for (row, _) in &constraint.rows {
+                impulses[row.slot] = row.impulse;
+            }
+
+            (constraint.id, impulses)
+        })
+    }
+}
+
+// Two unit vectors perpendicular to the normal and each other, always the
+// same for the same normal so cached friction impulses line up
+pub(crate) fn tangent_basis(normal: Vector3<f32>) -> [Vector3<f32>; 2] {
+    let axis = if normal.x.abs() < 0.57 {
+        Vector3::unit_x()
+    } else if normal.y.abs() < 0.57 {
+        Vector3::unit_y()
+    } else {
+        Vector3::unit_z()
+    };
+    let first = normal.cross(axis).normalize();
+
+    [first, normal.cross(first)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::{cuboid_inertia, Collider, PhysicsWorld};
+
+    #[test]
+    fn resting_contacts_cache_the_impulse_holding_the_body_up() {
+        let config = PhysicsConfig::new().with_sleeping(false);
+        let mut world = PhysicsWorld::new().with_config(config);
+        let half_extents = Vector3::from_value(0.5);
+        let dt = 1.0 / 60.0;
+
+        let floor = world.add(
+            RigidBody::fixed()
+                .with_position(Vector3::new(0.0, -0.5, 0.0))
+                .with_collider(Collider::aabb(Vector3::new(20.0, 0.5, 20.0))),
+        );
+        let body = world.add(
+            RigidBody::dynamic(2.0, cuboid_inertia(2.0, half_extents))
+                .with_position(Vector3::new(0.0, 0.5, 0.0))
+                .with_collider(Collider::obb(half_extents)),
+        );
+
+        for _ in 0..120 {
+            world.step(dt);
+        }
+
+        // Four corners share the weight, and start each step with it
+        let cached = &world.contact_cache[&(floor.min(body), floor.max(body))];
+        let normal_impulse = cached.iter().map(|point| point.normal_impulse).sum::<f32>();
+
+        assert_eq!(cached.len(), 4);
+        assert!((normal_impulse - 2.0 * 9.81 * dt).abs() < 0.02 * 2.0 * 9.81 * dt);
+        assert!(world.get(body).unwrap().linear_velocity().magnitude() < 1e-3);
+    }
+}
@@ -0,0 +1,39 @@
+//! Commonly used types, glob import with `use simulation_engine::prelude::*`
+
+pub use crate::adapter::{AdapterConfig, Backend, PowerPreference};
+pub use crate::config::{EngineConfig, PresentMode};
+pub use crate::ecs::{Entity, Mass, Velocity, World};
+pub use crate::error::EngineError;
+pub use crate::forces::{
+    Attractor, Drag, Falloff, ForceField, ForceId, ForceTarget, Forces, Gravity, PointMass, Spring,
+    Vortex,
+};
+pub use crate::geometry::{Bounds, Pose, Ray};
+pub use crate::hash::{StateHash, StateHasher};
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::headless::HeadlessContext;
+pub use crate::integrate::{DormandPrince, Integrator, IntegratorKind, PhaseState, StateVector};
+pub use crate::model::instance::Instance;
+pub use crate::model::instance_buffer::{InstanceBuffer, InstanceId};
+pub use crate::model::{
+    DrawLight, DrawModel, Material, Mesh, Model, ModelHandle, ModelRegistry, ModelVertex, Vertex,
+};
+pub use crate::particles::{
+    Curve, Emitter, EmitterId, EmitterShape, GpuEmitter, GpuParticles, GpuParticlesId, Gradient,
+    MeshSurface, Particle, ParticleSystem, MAX_GPU_FIELDS,
+};
+pub use crate::physics::{
+    BodyId, BodyKind, Collider, Contact, ContactManifold, FitShape, Joint, JointId, PhysicsConfig,
+    PhysicsMaterial, PhysicsWorld, RigidBody, Shape,
+};
+pub use crate::random::{EngineRng, Rng};
+pub use crate::scene::{NodeId, SceneGraph};
+pub use crate::simulation::ode::{Component, InstanceMapping, Plot};
+pub use crate::simulation::{OdeSimulation, OdeSystem, Simulation};
+pub use crate::spatial::{Bvh, HashGrid, Octree, TriangleBvh, TriangleHit};
+pub use crate::texture::Texture;
+pub use crate::time::{FixedTimestep, SimulationClock};
+pub use crate::window::camera::{Axis, Camera};
+pub use crate::window::light::LightUniform;
+pub use crate::window::{Context, Window};
+pub use crate::{run, run_simulation};
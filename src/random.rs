@@ -0,0 +1,159 @@
+//! Seeded random numbers for reproducible simulations, see [`EngineRng`]
+
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::ops::Range;
+
+use crate::hash::{StateHash, StateHasher};
+
+// PCG32 (XSH RR) constants
+const PCG_MULTIPLIER: u64 = 6364136223846793005;
+
+/// Small, fast PCG32 generator. Generators with the same seed but different
+/// streams produce independent sequences.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+    increment: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old_state = self.state;
+
+        self.state = old_state
+            .wrapping_mul(PCG_MULTIPLIER)
+            .wrapping_add(self.increment);
+
+        let xor_shifted = (((old_state >> 18) ^ old_state) >> 27) as u32;
+        let rotation = (old_state >> 59) as u32;
+
+        xor_shifted.rotate_right(rotation)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        ((self.next_u32() as u64) << 32) | self.next_u32() as u64
+    }
+
+    /// Uniform in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    pub fn next_bool(&mut self) -> bool {
+        self.next_u32() >> 31 == 1
+    }
+
+    /// Uniform in [range.start, range.end)
+    pub fn range_f32(&mut self, range: Range<f32>) -> f32 {
+        range.start + (range.end - range.start) * self.next_f32()
+    }
+
+    /// Uniform in [range.start, range.end) without modulo bias. Panics if the
+    /// range is empty.
+    pub fn range_u32(&mut self, range: Range<u32>) -> u32 {
+        assert!(range.start < range.end, "Empty range");
+
+        let bound = range.end - range.start;
+        let threshold = bound.wrapping_neg() % bound;
+
+        loop {
+            let value = self.next_u32();
+
+            if value >= threshold {
+                return range.start + value % bound;
+            }
+        }
+    }
+}
+
+/// Engine wide generator handing out one [`Rng`] stream per named system, so
+/// adding draws to one system doesn't shift the numbers another sees
+#[derive(Clone, Debug)]
+pub struct EngineRng {
+    seed: u64,
+    streams: HashMap<String, Rng>,
+}
+
+impl EngineRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            streams: HashMap::new(),
+        }
+    }
+
+    /// Seeded from the system time, for runs that don't need reproducing
+    pub fn from_entropy() -> Self {
+        Self::new(Self::entropy())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn entropy() -> u64 {
+        js_sys::Date::now().to_bits()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn entropy() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or_default()
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Restarts every stream from a new seed
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.streams.clear();
+    }
+
+    /// The stream for `name`, which depends only on the seed and the name
+    pub fn stream(&mut self, name: &str) -> &mut Rng {
+        let seed = self.seed;
+
+        self.streams
+            .entry(String::from(name))
+            .or_insert_with(|| Rng::new(seed, StateHasher::hash_str(name)))
+    }
+}
+
+impl StateHash for Rng {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_u64(self.state);
+        hasher.write_u64(self.increment);
+    }
+}
+
+// Streams are hashed in name order, as map order varies between runs
+impl StateHash for EngineRng {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        let mut streams = self.streams.iter().collect::<Vec<_>>();
+
+        streams.sort_unstable_by_key(|(name, _)| *name);
+
+        hasher.write_u64(self.seed);
+        hasher.write_usize(streams.len());
+
+        for (name, rng) in streams {
+            hasher.write(name.as_bytes());
+            rng.hash_state(hasher);
+        }
+    }
+}
@@ -1,3 +1,5 @@
+//! Asset loading from `assets/`, fetched over http on wasm32
+
 use cfg_if::cfg_if;
 use cgmath::{Vector2, Vector3};
 use std::io::{BufReader, Cursor};
@@ -16,6 +18,7 @@ fn format_url(file_name: &str) -> reqwest::Url {
     base.join(file_name).unwrap()
 }
 
+/// Loads a text file relative to the assets directory
 pub async fn load_string(file_name: &str) -> anyhow::Result<String> {
     cfg_if! {
         if #[cfg(target_arch = "wasm32")] {
@@ -37,6 +40,7 @@ pub async fn load_string(file_name: &str) -> anyhow::Result<String> {
     Ok(text)
 }
 
+/// Loads a binary file relative to the assets directory
 pub async fn load_binary(file_name: &str) -> anyhow::Result<Vec<u8>> {
     cfg_if! {
         if #[cfg(target_arch = "wasm32")] {
@@ -57,6 +61,7 @@ pub async fn load_binary(file_name: &str) -> anyhow::Result<Vec<u8>> {
     Ok(data)
 }
 
+/// Loads a texture from `assets/models/`
 pub async fn load_texture(
     file_name: &str,
     device: &wgpu::Device,
@@ -69,7 +74,8 @@ pub async fn load_texture(
     texture::Texture::from_bytes(device, queue, &data, file_name, is_srgb)
 }
 
-pub async fn load_model(
+// Loads an obj model from assets/models/, use Context::load_model from outside the crate
+pub(crate) async fn load_model(
     file_name: &str,
     device: &wgpu::Device,
     queue: &wgpu::Queue,
@@ -87,7 +93,9 @@ pub async fn load_model(
             ..Default::default()
         },
         |file_name| async move {
-            let mat_text = load_string(&format!("models/{}", file_name)).await.unwrap();
+            let mat_text = load_string(&format!("models/{}", file_name))
+                .await
+                .map_err(|_| tobj::LoadError::OpenFileFailed)?;
             tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text)))
         },
     )
@@ -192,7 +200,7 @@ pub async fn load_model(
             // Average the tangents/bitangents
             for (i, n) in triangles_included.into_iter().enumerate() {
                 let denom = 1.0 / n as f32;
-                let mut v = &mut vertices[i];
+                let v = &mut vertices[i];
                 v.tangent = (Vector3::from(v.tangent) * denom).into();
                 v.bitangent = (Vector3::from(v.bitangent) * denom).into();
             }
@@ -213,6 +221,8 @@ pub async fn load_model(
                 vertex_buffer,
                 index_buffer,
                 num_elements: m.mesh.indices.len() as u32,
+                positions: vertices.iter().map(|vertex| vertex.position).collect(),
+                indices: m.mesh.indices,
                 material: m.mesh.material_id.unwrap_or(0),
             }
         })
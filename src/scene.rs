@@ -0,0 +1,464 @@
+//! Hierarchical transforms, see [`SceneGraph`]
+
+use cgmath::prelude::*;
+use std::hash::Hasher;
+
+use cgmath::Matrix4;
+
+use crate::hash::{StateHash, StateHasher};
+use crate::model::instance::{Instance, InstanceRaw};
+use crate::model::ModelHandle;
+
+/// Handle to a node in a [`SceneGraph`], invalidated when the node is removed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId {
+    index: usize,
+    generation: u32,
+}
+
+/// A transform relative to its parent, or to the world for root nodes
+#[derive(Debug)]
+pub struct Node {
+    local: Instance,
+    // Local transform as of the last snapshot, for interpolated drawing
+    previous: Instance,
+    world: Matrix4<f32>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    visible: bool,
+    model: ModelHandle,
+}
+
+impl Node {
+    pub fn local(&self) -> &Instance {
+        &self.local
+    }
+
+    /// World matrix as of the last [`SceneGraph::update_world_matrices`]
+    pub fn world_matrix(&self) -> Matrix4<f32> {
+        self.world
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    /// Whether the node is drawn, pivots used only to group children are not
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn model(&self) -> ModelHandle {
+        self.model
+    }
+}
+
+struct Slot {
+    generation: u32,
+    node: Option<Node>,
+}
+
+/// Tree of nodes whose world matrices are the product of their ancestors'
+/// local transforms, e.g. for robot arms, orbit systems and compound bodies
+#[derive(Default)]
+pub struct SceneGraph {
+    slots: Vec<Slot>,
+    free_indices: Vec<usize>,
+    roots: Vec<NodeId>,
+    len: usize,
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&Node> {
+        self.slots
+            .get(id.index)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.node.as_ref())
+    }
+
+    fn get_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.slots
+            .get_mut(id.index)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.node.as_mut())
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Adds a node under `parent`, or as a root when `None`. Panics if the
+    /// parent has been removed.
+    pub fn add(&mut self, local: Instance, parent: Option<NodeId>) -> NodeId {
+        let node = Node {
+            local,
+            previous: local,
+            world: Matrix4::identity(),
+            parent,
+            children: Vec::new(),
+            visible: true,
+            model: ModelHandle::default(),
+        };
+
+        let id = match self.free_indices.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index];
+                slot.node = Some(node);
+
+                NodeId {
+                    index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    node: Some(node),
+                });
+
+                NodeId {
+                    index: self.slots.len() - 1,
+                    generation: 0,
+                }
+            }
+        };
+
+        match parent {
+            Some(parent) => self
+                .get_mut(parent)
+                .expect("Parent node has been removed")
+                .children
+                .push(id),
+            None => self.roots.push(id),
+        }
+
+        self.len += 1;
+
+        id
+    }
+
+    /// Removes the node and all of its descendants
+    pub fn remove(&mut self, id: NodeId) {
+        let Some(parent) = self.get(id).map(|node| node.parent) else {
+            return;
+        };
+
+        self.detach(id, parent);
+
+        let mut stack = vec![id];
+
+        while let Some(id) = stack.pop() {
+            let slot = &mut self.slots[id.index];
+
+            if let Some(node) = slot.node.take() {
+                stack.extend(node.children);
+                slot.generation = slot.generation.wrapping_add(1);
+                self.free_indices.push(id.index);
+                self.len -= 1;
+            }
+        }
+    }
+
+    /// Moves the node under a new parent, keeping its local transform.
+    /// Returns false if either node is missing or the move would create a
+    /// cycle.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> bool {
+        let Some(old_parent) = self.get(id).map(|node| node.parent) else {
+            return false;
+        };
+
+        if let Some(parent) = parent {
+            // Walk up from the new parent to make sure the node isn't an ancestor
+            let mut ancestor = Some(parent);
+
+            while let Some(ancestor_id) = ancestor {
+                if ancestor_id == id {
+                    return false;
+                }
+
+                match self.get(ancestor_id) {
+                    Some(node) => ancestor = node.parent,
+                    None => return false,
+                }
+            }
+        }
+
+        self.detach(id, old_parent);
+
+        match parent {
+            Some(parent) => self.get_mut(parent).unwrap().children.push(id),
+            None => self.roots.push(id),
+        }
+
+        self.get_mut(id).unwrap().parent = parent;
+
+        true
+    }
+
+    fn detach(&mut self, id: NodeId, parent: Option<NodeId>) {
+        let siblings = match parent {
+            Some(parent) => &mut self.get_mut(parent).unwrap().children,
+            None => &mut self.roots,
+        };
+
+        siblings.retain(|sibling| *sibling != id);
+    }
+
+    pub fn local(&self, id: NodeId) -> Option<&Instance> {
+        self.get(id).map(|node| &node.local)
+    }
+
+    pub fn local_mut(&mut self, id: NodeId) -> Option<&mut Instance> {
+        self.get_mut(id).map(|node| &mut node.local)
+    }
+
+    pub fn set_visible(&mut self, id: NodeId, visible: bool) {
+        if let Some(node) = self.get_mut(id) {
+            node.visible = visible;
+        }
+    }
+
+    /// Model the node is drawn with, the object model unless set
+    pub fn set_model(&mut self, id: NodeId, model: ModelHandle) {
+        if let Some(node) = self.get_mut(id) {
+            node.model = model;
+        }
+    }
+
+    pub fn world_matrix(&self, id: NodeId) -> Option<Matrix4<f32>> {
+        self.get(id).map(|node| node.world)
+    }
+
+    /// Recomputes every node's world matrix from the roots down
+    pub fn update_world_matrices(&mut self) {
+        let mut stack = self
+            .roots
+            .iter()
+            .map(|root| (*root, Matrix4::identity()))
+            .collect::<Vec<_>>();
+
+        while let Some((id, parent_world)) = stack.pop() {
+            let node = self.get_mut(id).unwrap();
+
+            node.world = parent_world * node.local.matrix();
+
+            let world = node.world;
+            stack.extend(node.children.iter().map(|child| (*child, world)));
+        }
+    }
+
+    // Records the local transforms as the state interpolated from
+    pub(crate) fn snapshot(&mut self) {
+        for node in self.slots.iter_mut().filter_map(|slot| slot.node.as_mut()) {
+            node.previous = node.local;
+        }
+    }
+
+    // Per instance data for the visible nodes with the model they're drawn
+    // with. Below an alpha of 1 local transforms are blended from the snapshot.
+    pub(crate) fn instance_data(&self, alpha: f32) -> Vec<(ModelHandle, InstanceRaw)> {
+        if alpha >= 1.0 {
+            return self
+                .slots
+                .iter()
+                .filter_map(|slot| slot.node.as_ref())
+                .filter(|node| node.visible)
+                .map(|node| {
+                    (
+                        node.model,
+                        InstanceRaw::from_matrix(node.world, node.local.color()),
+                    )
+                })
+                .collect();
+        }
+
+        let mut instance_data = Vec::with_capacity(self.len);
+        let mut stack = self
+            .roots
+            .iter()
+            .map(|root| (*root, Matrix4::identity()))
+            .collect::<Vec<_>>();
+
+        while let Some((id, parent_world)) = stack.pop() {
+            let node = self.get(id).unwrap();
+            let local = node.previous.interpolate(&node.local, alpha);
+            let world = parent_world * local.matrix();
+
+            if node.visible {
+                instance_data.push((node.model, InstanceRaw::from_matrix(world, local.color())));
+            }
+
+            stack.extend(node.children.iter().map(|child| (*child, world)));
+        }
+
+        instance_data
+    }
+}
+
+impl StateHash for NodeId {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_usize(self.index);
+        hasher.write_u32(self.generation);
+    }
+}
+
+impl StateHash for SceneGraph {
+    fn hash_state(&self, hasher: &mut StateHasher) {
+        hasher.write_usize(self.len);
+
+        for (index, slot) in self.slots.iter().enumerate() {
+            let Some(node) = &slot.node else {
+                continue;
+            };
+
+            hasher.write_usize(index);
+            hasher.write_u32(slot.generation);
+            node.local.hash_state(hasher);
+            node.parent.hash_state(hasher);
+            node.visible.hash_state(hasher);
+            hasher.write_usize(node.model.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{assert_abs_diff_eq, Deg, Point3, Quaternion, Vector3};
+
+    use super::*;
+
+    fn at(x: f32, y: f32, z: f32) -> Instance {
+        Instance::new(Vector3::new(x, y, z), Quaternion::one())
+    }
+
+    fn origin(matrix: Matrix4<f32>) -> Point3<f32> {
+        matrix.transform_point(Point3::origin())
+    }
+
+    // Root turned a quarter about z, a child and a scaled grandchild
+    fn three_levels(scene: &mut SceneGraph) -> [NodeId; 3] {
+        let root = scene.add(
+            Instance::new(
+                Vector3::unit_x(),
+                Quaternion::from_axis_angle(Vector3::unit_z(), Deg(90.0)),
+            ),
+            None,
+        );
+        let child = scene.add(at(1.0, 0.0, 0.0), Some(root));
+        let grandchild = scene.add(
+            at(0.0, 2.0, 0.0).with_scale(Vector3::new(2.0, 2.0, 2.0)),
+            Some(child),
+        );
+
+        [root, child, grandchild]
+    }
+
+    #[test]
+    fn world_matrices_compose_down_the_hierarchy() {
+        let mut scene = SceneGraph::new();
+        let [root, child, grandchild] = three_levels(&mut scene);
+
+        scene.update_world_matrices();
+
+        let expected = scene.local(root).unwrap().matrix()
+            * scene.local(child).unwrap().matrix()
+            * scene.local(grandchild).unwrap().matrix();
+
+        assert_abs_diff_eq!(scene.world_matrix(grandchild).unwrap(), expected);
+        assert_abs_diff_eq!(
+            origin(scene.world_matrix(child).unwrap()),
+            Point3::new(1.0, 1.0, 0.0),
+            epsilon = 1e-6
+        );
+        assert_abs_diff_eq!(
+            origin(scene.world_matrix(grandchild).unwrap()),
+            Point3::new(-1.0, 1.0, 0.0),
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn set_parent_refuses_cycles() {
+        let mut scene = SceneGraph::new();
+        let [root, child, grandchild] = three_levels(&mut scene);
+
+        assert!(!scene.set_parent(root, Some(grandchild)));
+        assert!(!scene.set_parent(child, Some(child)));
+        assert_eq!(scene.get(root).unwrap().parent(), None);
+        assert_eq!(scene.roots(), &[root]);
+
+        assert!(scene.set_parent(grandchild, Some(root)));
+        assert_eq!(scene.get(root).unwrap().children(), &[child, grandchild]);
+        assert!(scene.get(child).unwrap().children().is_empty());
+    }
+
+    #[test]
+    fn remove_takes_the_subtree_and_invalidates_ids() {
+        let mut scene = SceneGraph::new();
+        let [root, child, grandchild] = three_levels(&mut scene);
+
+        scene.remove(child);
+
+        assert_eq!(scene.len(), 1);
+        assert!(scene.contains(root));
+        assert!(!scene.contains(child));
+        assert!(!scene.contains(grandchild));
+        assert!(scene.get(root).unwrap().children().is_empty());
+
+        // Reused slots don't revive the old ids
+        let reused = scene.add(at(0.0, 0.0, 0.0), None);
+
+        assert!(scene.contains(reused));
+        assert!(!scene.contains(child));
+        assert!(!scene.contains(grandchild));
+        assert!(!scene.set_parent(grandchild, None));
+    }
+
+    #[test]
+    fn instance_data_blends_from_the_snapshot() {
+        let mut scene = SceneGraph::new();
+        let root = scene.add(at(0.0, 1.0, 0.0), None);
+        let node = scene.add(at(0.0, 0.0, 0.0), Some(root));
+        let hidden = scene.add(at(0.0, 0.0, 0.0), Some(root));
+
+        scene.set_visible(root, false);
+        scene.set_visible(hidden, false);
+        scene.snapshot();
+        scene
+            .local_mut(node)
+            .unwrap()
+            .set_position(Vector3::new(2.0, 0.0, 0.0));
+        scene.update_world_matrices();
+
+        for (alpha, x) in [(0.0, 0.0), (0.5, 1.0), (1.0, 2.0)] {
+            let instance_data = scene.instance_data(alpha);
+
+            assert_eq!(instance_data.len(), 1);
+            assert_abs_diff_eq!(
+                origin(instance_data[0].1.matrix()),
+                Point3::new(x, 1.0, 0.0),
+                epsilon = 1e-6
+            );
+        }
+    }
+}
@@ -0,0 +1,101 @@
+// Draws GPU particles as quads facing the camera, straight from the buffer
+// the compute shader integrates
+
+struct Camera {
+    view_position: vec4<f32>,
+    view_projection_matrix: mat4x4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+// Colour and size sampled evenly over life, four sizes to a vector
+struct Appearance {
+    colors: array<vec4<f32>, 16>,
+    sizes: array<vec4<f32>, 4>,
+}
+@group(1) @binding(0)
+var<uniform> appearance: Appearance;
+
+struct ParticleInput {
+    // Age in w
+    @location(0) position: vec4<f32>,
+    // Lifetime in w
+    @location(1) velocity: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) corner: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+fn size_at(index: u32) -> f32 {
+    return appearance.sizes[index / 4u][index % 4u];
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex_index: u32,
+    particle: ParticleInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    let age = particle.position.w;
+    let lifetime = particle.velocity.w;
+
+    // Unborn and dead particles fall outside the clip volume
+    if age < 0.0 || age >= lifetime {
+        out.clip_position = vec4<f32>(0.0, 0.0, -2.0, 1.0);
+        return out;
+    }
+
+    // Two counter clockwise triangles
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[vertex_index];
+
+    // Blend between the samples either side of the particle's life
+    let sample = clamp(age / lifetime, 0.0, 1.0) * 15.0;
+    let index = u32(floor(sample));
+    let next = min(index + 1u, 15u);
+    let blend = sample - f32(index);
+    let color = mix(appearance.colors[index], appearance.colors[next], blend);
+    let size = mix(size_at(index), size_at(next), blend);
+
+    // Face the camera, falling back to the x axis when looking straight down
+    let forward = normalize(camera.view_position.xyz - particle.position.xyz);
+    var right = cross(vec3<f32>(0.0, 1.0, 0.0), forward);
+
+    if dot(right, right) < 1e-8 {
+        right = vec3<f32>(1.0, 0.0, 0.0);
+    }
+
+    right = normalize(right);
+
+    let up = cross(forward, right);
+    let position = particle.position.xyz + (right * corner.x + up * corner.y) * size;
+
+    out.clip_position = camera.view_projection_matrix * vec4<f32>(position, 1.0);
+    out.corner = corner;
+    out.color = color;
+
+    return out;
+}
+
+// Fragment shader
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Round off the quad's corners
+    if dot(in.corner, in.corner) > 1.0 {
+        discard;
+    }
+
+    return in.color;
+}
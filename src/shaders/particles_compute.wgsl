@@ -0,0 +1,260 @@
+// Integrates GPU particles, respawning them at the emitter once they die
+
+struct Particle {
+    // Age in w, negative until the particle is first born
+    position: vec4<f32>,
+    // Lifetime in w
+    velocity: vec4<f32>,
+}
+
+struct Field {
+    // Strength in w
+    center: vec4<f32>,
+    // Radius in w
+    axis: vec4<f32>,
+    // Softening in x
+    softening: vec4<f32>,
+    // Kind (0 attractor, 1 vortex) and falloff (0 constant, 1 linear,
+    // 2 inverse square)
+    kinds: vec4<u32>,
+}
+
+struct Params {
+    // Step in w
+    position: vec4<f32>,
+    rotation: vec4<f32>,
+    // Cone angle and radius, or sphere radius in x
+    shape: vec4<f32>,
+    // Lifetime range then speed range
+    ranges: vec4<f32>,
+    // Mass in w
+    gravity: vec4<f32>,
+    // Linear then quadratic
+    drag: vec4<f32>,
+    // Shape kind (0 point, 1 sphere, 2 cone, 3 mesh), field count, seed,
+    // emitting
+    counts: vec4<u32>,
+    fields: array<Field, 8>,
+}
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+@group(0) @binding(1)
+var<storage, read_write> particles: array<Particle>;
+// Three corners per triangle, the first carrying the running total of areas
+@group(0) @binding(2)
+var<storage, read> triangles: array<vec4<f32>>;
+
+// Where a particle spawns and which way it heads, in the emitter's frame
+struct Spawn {
+    offset: vec3<f32>,
+    direction: vec3<f32>,
+}
+
+const TAU: f32 = 6.283185307;
+
+var<private> rng_state: u32;
+
+// PCG hash
+fn hash(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+
+    return (word >> 22u) ^ word;
+}
+
+// Uniform in [0, 1)
+fn random() -> f32 {
+    rng_state = hash(rng_state);
+
+    return f32(rng_state >> 8u) / 16777216.0;
+}
+
+fn random_range(start: f32, end: f32) -> f32 {
+    return start + (end - start) * random();
+}
+
+// Uniform on the unit sphere
+fn random_direction() -> vec3<f32> {
+    let z = random_range(-1.0, 1.0);
+    let turn = random() * TAU;
+    let radius = sqrt(max(1.0 - z * z, 0.0));
+
+    return vec3<f32>(radius * cos(turn), radius * sin(turn), z);
+}
+
+fn rotate(rotation: vec4<f32>, vector: vec3<f32>) -> vec3<f32> {
+    let t = 2.0 * cross(rotation.xyz, vector);
+
+    return vector + rotation.w * t + cross(rotation.xyz, t);
+}
+
+// Uniform point on the mesh surface and the normal of its triangle
+fn sample_mesh() -> Spawn {
+    let count = arrayLength(&triangles) / 3u;
+    let target_area = random() * triangles[(count - 1u) * 3u].w;
+
+    // First triangle whose running total passes the target
+    var low = 0u;
+    var high = count - 1u;
+
+    while low < high {
+        let middle = (low + high) / 2u;
+
+        if triangles[middle * 3u].w <= target_area {
+            low = middle + 1u;
+        } else {
+            high = middle;
+        }
+    }
+
+    let a = triangles[low * 3u].xyz;
+    let b = triangles[low * 3u + 1u].xyz;
+    let c = triangles[low * 3u + 2u].xyz;
+
+    // Folding the unit square in half keeps points uniform on the triangle
+    var u = random();
+    var v = random();
+
+    if u + v > 1.0 {
+        u = 1.0 - u;
+        v = 1.0 - v;
+    }
+
+    return Spawn(a + (b - a) * u + (c - a) * v, normalize(cross(b - a, c - a)));
+}
+
+fn sample_shape() -> Spawn {
+    switch params.counts.x {
+        case 1u: {
+            let direction = random_direction();
+
+            return Spawn(direction * params.shape.x * pow(random(), 1.0 / 3.0), direction);
+        }
+        case 2u: {
+            // Uniform over the cap of directions within the angle
+            let cos_angle = 1.0 - random() * (1.0 - cos(params.shape.x));
+            let sin_angle = sqrt(max(1.0 - cos_angle * cos_angle, 0.0));
+            let turn = random() * TAU;
+            let direction = vec3<f32>(sin_angle * cos(turn), cos_angle, sin_angle * sin(turn));
+
+            let distance = params.shape.y * sqrt(random());
+            let offset_turn = random() * TAU;
+            let offset = vec3<f32>(distance * cos(offset_turn), 0.0, distance * sin(offset_turn));
+
+            return Spawn(offset, direction);
+        }
+        case 3u: {
+            return sample_mesh();
+        }
+        default: {
+            return Spawn(vec3<f32>(0.0), random_direction());
+        }
+    }
+}
+
+// Scale of a field's strength at the distance, 0 beyond the radius
+fn falloff(kind: u32, distance: f32, radius: f32, softening: f32) -> f32 {
+    if distance > radius {
+        return 0.0;
+    }
+
+    switch kind {
+        case 1u: {
+            return 1.0 - distance / radius;
+        }
+        case 2u: {
+            let softened = max(distance, softening);
+
+            return 1.0 / (softened * softened);
+        }
+        default: {
+            return 1.0;
+        }
+    }
+}
+
+fn field_force(field: Field, position: vec3<f32>) -> vec3<f32> {
+    let radius = field.axis.w;
+    let softening = field.softening.x;
+
+    if field.kinds.x == 1u {
+        let offset = position - field.center.xyz;
+        let radial = offset - field.axis.xyz * dot(offset, field.axis.xyz);
+        let distance = length(radial);
+
+        if distance <= 1e-6 {
+            return vec3<f32>(0.0);
+        }
+
+        return cross(field.axis.xyz, radial / distance) * field.center.w * falloff(field.kinds.y, distance, radius, softening);
+    }
+
+    let offset = field.center.xyz - position;
+    let distance = length(offset);
+
+    if distance <= 1e-6 {
+        return vec3<f32>(0.0);
+    }
+
+    return offset / distance * field.center.w * falloff(field.kinds.y, distance, radius, softening);
+}
+
+fn acceleration(position: vec3<f32>, velocity: vec3<f32>) -> vec3<f32> {
+    let mass = params.gravity.w;
+    var force = -velocity * (params.drag.x + params.drag.y * length(velocity));
+
+    for (var index = 0u; index < params.counts.y; index += 1u) {
+        force += field_force(params.fields[index], position);
+    }
+
+    return params.gravity.xyz + force / mass;
+}
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+
+    if index >= arrayLength(&particles) {
+        return;
+    }
+
+    let dt = params.position.w;
+    var particle = particles[index];
+
+    particle.position.w += dt;
+
+    if particle.position.w < 0.0 {
+        particles[index] = particle;
+        return;
+    }
+
+    if particle.position.w >= particle.velocity.w {
+        // Dead particles wait while the emitter is off
+        if params.counts.w == 0u {
+            particles[index] = particle;
+            return;
+        }
+
+        rng_state = hash(index ^ hash(params.counts.z));
+
+        let spawn = sample_shape();
+        let velocity = rotate(params.rotation, spawn.direction) * random_range(params.ranges.z, params.ranges.w);
+
+        // Spread spawns through the step so steady streams don't leave in
+        // clumps
+        let head_start = random() * dt;
+        let position = params.position.xyz + rotate(params.rotation, spawn.offset) + velocity * head_start;
+
+        particle.position = vec4<f32>(position, head_start);
+        particle.velocity = vec4<f32>(velocity, random_range(params.ranges.x, params.ranges.y));
+    } else {
+        // Semi-implicit Euler, as on the CPU
+        let velocity = particle.velocity.xyz + acceleration(particle.position.xyz, particle.velocity.xyz) * dt;
+
+        particle.position = vec4<f32>(particle.position.xyz + velocity * dt, particle.position.w);
+        particle.velocity = vec4<f32>(velocity, particle.velocity.w);
+    }
+
+    particles[index] = particle;
+}
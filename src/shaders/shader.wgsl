@@ -14,6 +14,7 @@ struct InstanceInput {
     @location(9) normal_matrix_0: vec3<f32>,
     @location(10) normal_matrix_1: vec3<f32>,
     @location(11) normal_matrix_2: vec3<f32>,
+    @location(12) color: vec4<f32>,
 };
 
 struct VertexInput {
@@ -30,6 +31,7 @@ struct VertexOutput {
     @location(1) tangent_position: vec3<f32>,
     @location(2) tangent_light_position: vec3<f32>,
     @location(3) tangent_view_position: vec3<f32>,
+    @location(4) color: vec4<f32>,
 }
 
 @vertex
@@ -67,6 +69,7 @@ fn vs_main(
     out.tangent_position = tangent_matrix * world_position.xyz;
     out.tangent_view_position = tangent_matrix * camera.view_position.xyz;
     out.tangent_light_position = tangent_matrix * light.position;
+    out.color = instance.color;
     return out;
 }
 
@@ -92,7 +95,7 @@ var<uniform> light: Light;
 @fragment
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let object_color: vec4<f32> = textureSample(diffuse_texture, diffuse_sampler, in.texture_coordinates);
+    let object_color: vec4<f32> = textureSample(diffuse_texture, diffuse_sampler, in.texture_coordinates) * in.color;
     let object_normal: vec4<f32> = textureSample(normal_texture, normal_sampler, in.texture_coordinates);
 
     // We don't need (or want) much ambient light, so 0.1 is fine
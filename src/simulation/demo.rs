@@ -0,0 +1,68 @@
+use cgmath::prelude::*;
+use cgmath::{Deg, Quaternion, Vector3};
+use winit::event::WindowEvent;
+
+use crate::model::instance::Instance;
+use crate::simulation::Simulation;
+use crate::window::Context;
+
+const NUM_INSTANCES_PER_ROW: u32 = 10;
+const SPACE_BETWEEN_MODELS: f32 = 3.0;
+
+/// Grid of cubes lit by an orbiting light
+#[derive(Default)]
+pub struct Demo;
+
+impl Simulation for Demo {
+    fn init(&mut self, context: &mut Context) {
+        let instances = (0..NUM_INSTANCES_PER_ROW)
+            .flat_map(|z| {
+                (0..NUM_INSTANCES_PER_ROW).map(move |x| {
+                    let x = SPACE_BETWEEN_MODELS * (x as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
+                    let z = SPACE_BETWEEN_MODELS * (z as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
+
+                    let position = Vector3 { x, y: 0.0, z };
+
+                    let rotation = if position.is_zero() {
+                        Quaternion::from_axis_angle(Vector3::unit_z(), Deg(0.0))
+                    } else {
+                        Quaternion::from_axis_angle(position.normalize(), Deg(45.0))
+                    };
+
+                    Instance::new(position, rotation)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        context.set_instances(instances);
+    }
+
+    fn fixed_update(&mut self, context: &mut Context, dt: f32) {
+        // Rotate light
+        let light_rotation_per_second = context.config().light_rotation_per_second;
+        let light = context.light();
+        let old_position: Vector3<_> = light.position.into();
+        let rotation_angle = Deg(light_rotation_per_second) * dt;
+
+        light.set_position_into(
+            Quaternion::from_axis_angle(Vector3::unit_y(), rotation_angle) * old_position,
+        );
+    }
+
+    fn input(&mut self, context: &mut Context, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                // Update light color using cursor position
+                let size = context.size();
+                let x = (position.x / size.width as f64) as f32;
+                let y = (position.y / size.height as f64) as f32;
+
+                context.light().set_color([x, y, 0.5]);
+
+                true
+            }
+
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,40 @@
+//! The [`Simulation`] trait user logic implements to run inside the engine
+
+use winit::event::WindowEvent;
+
+use crate::hash::StateHasher;
+use crate::window::Context;
+
+mod demo;
+pub mod ode;
+
+pub use demo::Demo;
+pub use ode::{OdeSimulation, OdeSystem};
+
+/// User logic plugged into the engine's main loop
+pub trait Simulation {
+    /// Called once after the context has been created, before the first frame
+    fn init(&mut self, _context: &mut Context) {}
+
+    /// Called every frame with the simulation time in seconds since the
+    /// previous frame, after any fixed steps due that frame. Zero while paused.
+    fn update(&mut self, _context: &mut Context, _dt: f32) {}
+
+    /// Called at `EngineConfig::fixed_update_rate` in simulation time with the
+    /// fixed step in seconds, independently of the frame rate. Transforms changed here are
+    /// drawn interpolated between steps.
+    fn fixed_update(&mut self, _context: &mut Context, _dt: f32) {}
+
+    /// Called for every window event before the engine handles it. Returning
+    /// true marks the event as consumed.
+    fn input(&mut self, _context: &mut Context, _event: &WindowEvent) -> bool {
+        false
+    }
+
+    /// Adds state the simulation keeps outside the context to
+    /// [`crate::HeadlessContext::state_hash`]
+    fn state_hash(&self, _hasher: &mut StateHasher) {}
+
+    /// Called inside the main render pass after the engine has drawn the scene
+    fn render<'a>(&'a self, _context: &'a Context, _render_pass: &mut wgpu::RenderPass<'a>) {}
+}
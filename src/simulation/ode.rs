@@ -0,0 +1,622 @@
+//! Small ODE systems such as pendulums, attractors and population models,
+//! integrated and drawn without further glue, see [`OdeSimulation`]
+
+use std::collections::VecDeque;
+
+use cgmath::prelude::*;
+use cgmath::{Quaternion, Rad, Vector3};
+
+use crate::hash::{StateHash, StateHasher};
+use crate::integrate::{DormandPrince, Integrator, IntegratorKind, StateVector};
+use crate::model::instance::{Instance, WHITE};
+use crate::model::instance_buffer::{InstanceBuffer, InstanceId};
+use crate::model::ModelHandle;
+use crate::simulation::Simulation;
+use crate::window::Context;
+
+// Plot defaults
+const TRAIL_LENGTH_DEFAULT: usize = 256;
+const POINT_SIZE_DEFAULT: f32 = 0.05;
+const PLOT_LENGTH_DEFAULT: usize = 256;
+
+/// A first order system `dy/dt = f(t, y)` and how its state is drawn
+pub trait OdeSystem {
+    fn initial_state(&self) -> StateVector;
+
+    fn derivative(&self, t: f32, y: &StateVector) -> StateVector;
+
+    /// Declarative mappings from state components to instances, used by the
+    /// default [`OdeSystem::instances`]. Every state component they read must
+    /// exist, which is checked when the simulation starts.
+    fn mappings(&self) -> Vec<InstanceMapping> {
+        Vec::new()
+    }
+
+    /// One instance per drawn body. Override for mappings that aren't a
+    /// component per coordinate, e.g. a pendulum bob at `(sin θ, -cos θ)`.
+    fn instances(&self, y: &StateVector) -> Vec<Instance> {
+        self.mappings()
+            .iter()
+            .map(|mapping| mapping.instance(y))
+            .collect()
+    }
+}
+
+/// Source of one value of a mapped instance
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Component {
+    /// The state component at this index
+    State(usize),
+    Constant(f32),
+}
+
+impl Component {
+    pub fn value(&self, y: &StateVector) -> f32 {
+        match self {
+            Component::State(index) => y[*index],
+            Component::Constant(value) => *value,
+        }
+    }
+}
+
+/// Places an instance from state components, e.g. a Lorenz attractor's
+/// `x`, `y` and `z` as the position
+#[derive(Clone, Copy, Debug)]
+pub struct InstanceMapping {
+    pub position: [Component; 3],
+    /// Multiplies the position, to fit the system into view
+    pub position_scale: f32,
+    /// Rotation about the axis by the component's angle in radians
+    pub rotation: Option<(Vector3<f32>, Component)>,
+    pub scale: Vector3<f32>,
+    pub color: [f32; 4],
+}
+
+impl InstanceMapping {
+    pub fn new(x: Component, y: Component, z: Component) -> Self {
+        Self {
+            position: [x, y, z],
+            position_scale: 1.0,
+            rotation: None,
+            scale: Vector3::new(1.0, 1.0, 1.0),
+            color: WHITE,
+        }
+    }
+
+    pub fn with_position_scale(mut self, position_scale: f32) -> Self {
+        self.position_scale = position_scale;
+        self
+    }
+
+    pub fn with_rotation(mut self, axis: Vector3<f32>, angle: Component) -> Self {
+        self.rotation = Some((axis.normalize(), angle));
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Vector3<f32>) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    // State components the mapping reads
+    fn state_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.position
+            .iter()
+            .chain(self.rotation.as_ref().map(|(_, angle)| angle))
+            .filter_map(|component| match component {
+                Component::State(index) => Some(*index),
+                Component::Constant(_) => None,
+            })
+    }
+
+    pub fn instance(&self, y: &StateVector) -> Instance {
+        let [x_component, y_component, z_component] = self.position;
+        let position = Vector3::new(
+            x_component.value(y),
+            y_component.value(y),
+            z_component.value(y),
+        ) * self.position_scale;
+
+        let rotation = match self.rotation {
+            Some((axis, angle)) => Quaternion::from_axis_angle(axis, Rad(angle.value(y))),
+            None => Quaternion::one(),
+        };
+
+        Instance::new(position, rotation)
+            .with_scale(self.scale)
+            .with_color(self.color)
+    }
+}
+
+/// Strip chart of one state component against time, drawn as points in the
+/// XY plane from `origin`, newest at the origin and older points to its left
+#[derive(Clone, Copy, Debug)]
+pub struct Plot {
+    pub component: usize,
+    pub origin: Vector3<f32>,
+    /// Horizontal units per second
+    pub time_scale: f32,
+    /// Vertical units per unit of the component
+    pub value_scale: f32,
+    /// Number of points kept
+    pub length: usize,
+    pub color: [f32; 4],
+}
+
+impl Plot {
+    pub fn new(component: usize, origin: Vector3<f32>) -> Self {
+        Self {
+            component,
+            origin,
+            time_scale: 1.0,
+            value_scale: 1.0,
+            length: PLOT_LENGTH_DEFAULT,
+            color: WHITE,
+        }
+    }
+
+    pub fn with_scales(mut self, time_scale: f32, value_scale: f32) -> Self {
+        self.time_scale = time_scale;
+        self.value_scale = value_scale;
+        self
+    }
+
+    pub fn with_length(mut self, length: usize) -> Self {
+        self.length = length;
+        self
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+enum Solver {
+    Fixed(IntegratorKind),
+    // Runs ahead of the simulation and is sampled at the step times
+    Adaptive(Box<DormandPrince<StateVector>>),
+}
+
+// Points drawn for a plot and the times they were taken at, oldest first
+struct PlotPoints {
+    plot: Plot,
+    ids: VecDeque<InstanceId>,
+    times: VecDeque<f32>,
+}
+
+/// Runs an [`OdeSystem`]: integrates it each fixed step, moves its instances
+/// and leaves a trail behind each body. Uses [`DormandPrince`] sampled at
+/// the fixed step times unless a fixed step integrator is chosen.
+pub struct OdeSimulation<O: OdeSystem> {
+    system: O,
+    solver: Solver,
+    t: f32,
+    state: StateVector,
+    model: ModelHandle,
+    bodies: Vec<InstanceId>,
+    trail_length: usize,
+    point_size: f32,
+    trails: Vec<VecDeque<InstanceId>>,
+    plots: Vec<PlotPoints>,
+}
+
+impl<O: OdeSystem> OdeSimulation<O> {
+    pub fn new(system: O) -> Self {
+        let state = system.initial_state();
+
+        Self {
+            system,
+            solver: Solver::Adaptive(Box::new(DormandPrince::new(0.0, state.clone()))),
+            t: 0.0,
+            state,
+            model: ModelHandle::default(),
+            bodies: Vec::new(),
+            trail_length: TRAIL_LENGTH_DEFAULT,
+            point_size: POINT_SIZE_DEFAULT,
+            trails: Vec::new(),
+            plots: Vec::new(),
+        }
+    }
+
+    /// Integrates with a fixed step integrator at the fixed update rate
+    pub fn with_integrator(mut self, integrator: IntegratorKind) -> Self {
+        self.solver = Solver::Fixed(integrator);
+        self
+    }
+
+    /// Integrates adaptively with these tolerances
+    pub fn with_tolerances(mut self, absolute_tolerance: f32, relative_tolerance: f32) -> Self {
+        self.solver = Solver::Adaptive(Box::new(
+            DormandPrince::new(self.t, self.state.clone())
+                .with_tolerances(absolute_tolerance, relative_tolerance),
+        ));
+        self
+    }
+
+    /// Model the bodies are drawn with, trails and plots use the object model
+    pub fn with_model(mut self, model: ModelHandle) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// Number of past positions kept per body, 0 disables trails
+    pub fn with_trail(mut self, trail_length: usize) -> Self {
+        self.trail_length = trail_length;
+        self
+    }
+
+    /// Size of trail and plot points
+    pub fn with_point_size(mut self, point_size: f32) -> Self {
+        self.point_size = point_size;
+        self
+    }
+
+    pub fn with_plot(mut self, plot: Plot) -> Self {
+        self.plots.push(PlotPoints {
+            plot,
+            ids: VecDeque::with_capacity(plot.length),
+            times: VecDeque::with_capacity(plot.length),
+        });
+        self
+    }
+
+    pub fn system(&self) -> &O {
+        &self.system
+    }
+
+    pub fn time(&self) -> f32 {
+        self.t
+    }
+
+    pub fn state(&self) -> &StateVector {
+        &self.state
+    }
+
+    // Advances the system by dt
+    fn advance(&mut self, dt: f32) {
+        let system = &self.system;
+        let derivative = |t: f32, y: &StateVector| system.derivative(t, y);
+
+        self.state = match &mut self.solver {
+            Solver::Fixed(integrator) => integrator.step(&self.state, self.t, dt, derivative),
+            Solver::Adaptive(solver) => solver.state_at(self.t + dt, derivative),
+        };
+        self.t += dt;
+    }
+
+    fn point(position: Vector3<f32>, color: [f32; 4], size: f32) -> Instance {
+        Instance::new(position, Quaternion::one())
+            .with_scale(Vector3::new(size, size, size))
+            .with_color(color)
+    }
+
+    // Adds a point to the end of the line, recycling the oldest once full.
+    // Recycled points jump to the end rather than sweeping along the line.
+    fn push_point(
+        instances: &mut InstanceBuffer,
+        ids: &mut VecDeque<InstanceId>,
+        length: usize,
+        point: Instance,
+    ) {
+        if length == 0 {
+            return;
+        }
+
+        let id = if ids.len() >= length {
+            let id = ids.pop_front().unwrap();
+
+            instances.teleport(id, point);
+
+            id
+        } else {
+            instances.insert(point)
+        };
+
+        ids.push_back(id);
+    }
+
+    // Moves the body instances, adding and removing them as the system's
+    // instance count changes
+    fn update_bodies(&mut self, buffer: &mut InstanceBuffer, bodies: &[Instance]) {
+        for id in self.bodies.drain(bodies.len().min(self.bodies.len())..) {
+            buffer.remove(id);
+        }
+
+        for (id, body) in self.bodies.iter().zip(bodies) {
+            if let Some(instance) = buffer.get_mut(*id) {
+                *instance = *body;
+            }
+        }
+
+        for body in &bodies[self.bodies.len()..] {
+            self.bodies.push(buffer.insert(*body));
+        }
+    }
+
+    fn update_trails(&mut self, instances: &mut InstanceBuffer, bodies: &[Instance]) {
+        if self.trail_length == 0 {
+            return;
+        }
+
+        // Trails of bodies that are gone go with them
+        for trail in self.trails.drain(bodies.len().min(self.trails.len())..) {
+            for id in trail {
+                instances.remove(id);
+            }
+        }
+
+        self.trails.resize_with(bodies.len(), VecDeque::new);
+
+        for (body, trail) in bodies.iter().zip(&mut self.trails) {
+            // Trails are a darker shade of their body
+            let mut color = body.color();
+            color[..3].iter_mut().for_each(|channel| *channel *= 0.6);
+
+            Self::push_point(
+                instances,
+                trail,
+                self.trail_length,
+                Self::point(body.position(), color, self.point_size),
+            );
+        }
+    }
+
+    fn update_plots(&mut self, instances: &mut InstanceBuffer) {
+        let t = self.t;
+        let y = &self.state;
+
+        for PlotPoints { plot, ids, times } in &mut self.plots {
+            let value = y.0.get(plot.component).copied().unwrap_or_default();
+            let position = plot.origin + Vector3::unit_y() * value * plot.value_scale;
+
+            Self::push_point(
+                instances,
+                ids,
+                plot.length,
+                Self::point(position, plot.color, self.point_size),
+            );
+
+            if times.len() >= plot.length {
+                times.pop_front();
+            }
+            times.push_back(t);
+
+            // Scroll older points to the left, a step at a time
+            for (id, point_t) in ids.iter().zip(times.iter()) {
+                if let Some(mut instance) = instances.get(*id).copied() {
+                    let mut position = instance.position();
+                    position.x = plot.origin.x - (t - point_t) * plot.time_scale;
+                    instance.set_position(position);
+                    instances.teleport(*id, instance);
+                }
+            }
+        }
+    }
+
+    // Catches mappings past the end of the state before they panic mid-run
+    fn check_mappings(&self) {
+        let dimension = self.state.len();
+
+        for mapping in self.system.mappings() {
+            for index in mapping.state_indices() {
+                assert!(
+                    index < dimension,
+                    "Mapping reads state component {} of a {} dimensional system",
+                    index,
+                    dimension
+                );
+            }
+        }
+    }
+
+    fn bodies_buffer(model: ModelHandle, context: &mut Context) -> &mut InstanceBuffer {
+        if context.model_instances(model).is_some() {
+            context.model_instances_mut(model).unwrap()
+        } else {
+            context.instances_mut()
+        }
+    }
+}
+
+impl<O: OdeSystem> Simulation for OdeSimulation<O> {
+    fn init(&mut self, context: &mut Context) {
+        self.check_mappings();
+
+        let bodies = self.system.instances(&self.state);
+
+        self.update_bodies(Self::bodies_buffer(self.model, context), &bodies);
+    }
+
+    fn fixed_update(&mut self, context: &mut Context, dt: f32) {
+        self.advance(dt);
+
+        let bodies = self.system.instances(&self.state);
+
+        self.update_bodies(Self::bodies_buffer(self.model, context), &bodies);
+        self.update_trails(context.instances_mut(), &bodies);
+        self.update_plots(context.instances_mut());
+    }
+
+    fn state_hash(&self, hasher: &mut StateHasher) {
+        self.time().hash_state(hasher);
+        self.state().hash_state(hasher);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Draws round(y[0]) bodies in a row, y[0] changing at a constant rate
+    struct Row {
+        start: f32,
+        rate: f32,
+    }
+
+    impl OdeSystem for Row {
+        fn initial_state(&self) -> StateVector {
+            StateVector::from(vec![self.start])
+        }
+
+        fn derivative(&self, _: f32, _: &StateVector) -> StateVector {
+            StateVector::from(vec![self.rate])
+        }
+
+        fn instances(&self, y: &StateVector) -> Vec<Instance> {
+            (0..y[0].round() as usize)
+                .map(|i| Instance::new(Vector3::new(i as f32, y[0], 0.0), Quaternion::one()))
+                .collect()
+        }
+    }
+
+    // The context's fixed update with one buffer for bodies and points
+    fn step<O: OdeSystem>(simulation: &mut OdeSimulation<O>, buffer: &mut InstanceBuffer) {
+        simulation.advance(1.0);
+
+        let bodies = simulation.system.instances(&simulation.state);
+
+        simulation.update_bodies(buffer, &bodies);
+        simulation.update_trails(buffer, &bodies);
+        simulation.update_plots(buffer);
+    }
+
+    fn x(buffer: &InstanceBuffer, id: InstanceId) -> f32 {
+        buffer.get(id).unwrap().position().x
+    }
+
+    #[test]
+    fn mappings_read_state_components() {
+        let y = StateVector::from(vec![1.0, 2.0, std::f32::consts::FRAC_PI_2]);
+        let mapping = InstanceMapping::new(
+            Component::State(0),
+            Component::Constant(3.0),
+            Component::State(1),
+        )
+        .with_position_scale(2.0)
+        .with_rotation(Vector3::unit_z() * 5.0, Component::State(2))
+        .with_scale(Vector3::new(0.5, 0.5, 0.5))
+        .with_color([1.0, 0.0, 0.0, 1.0]);
+
+        let instance = mapping.instance(&y);
+        let turned = instance.rotation().rotate_vector(Vector3::unit_x());
+
+        assert_eq!(instance.position(), Vector3::new(2.0, 6.0, 4.0));
+        assert!((turned - Vector3::unit_y()).magnitude() < 1e-6);
+        assert_eq!(instance.scale(), Vector3::new(0.5, 0.5, 0.5));
+        assert_eq!(instance.color(), [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(mapping.state_indices().collect::<Vec<_>>(), [0, 1, 2]);
+    }
+
+    struct Mapped(usize);
+
+    impl OdeSystem for Mapped {
+        fn initial_state(&self) -> StateVector {
+            StateVector::zeros(2)
+        }
+
+        fn derivative(&self, _: f32, y: &StateVector) -> StateVector {
+            y.clone()
+        }
+
+        fn mappings(&self) -> Vec<InstanceMapping> {
+            vec![InstanceMapping::new(
+                Component::State(0),
+                Component::State(self.0),
+                Component::Constant(0.0),
+            )]
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Mapping reads state component 2 of a 2 dimensional system")]
+    fn mappings_past_the_state_are_rejected() {
+        OdeSimulation::new(Mapped(1)).check_mappings();
+        OdeSimulation::new(Mapped(2)).check_mappings();
+    }
+
+    #[test]
+    fn bodies_and_trails_follow_the_instance_count() {
+        let mut buffer = InstanceBuffer::new("Test");
+
+        for (start, rate) in [(1.0, 1.0), (4.0, -1.0)] {
+            let mut simulation = OdeSimulation::new(Row { start, rate })
+                .with_integrator(IntegratorKind::Euler)
+                .with_trail(2);
+
+            buffer.clear();
+
+            for i in 1..=3 {
+                step(&mut simulation, &mut buffer);
+
+                let count = (start + rate * i as f32) as usize;
+                let trail_points = simulation.trails.iter().map(VecDeque::len).sum::<usize>();
+
+                assert_eq!(simulation.bodies.len(), count);
+                assert_eq!(simulation.trails.len(), count);
+                assert_eq!(buffer.len(), count + trail_points);
+
+                for (i, id) in simulation.bodies.iter().enumerate() {
+                    assert_eq!(x(&buffer, *id), i as f32);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn trails_and_plots_recycle_their_oldest_points() {
+        let plot = Plot::new(0, Vector3::new(5.0, 0.0, 0.0))
+            .with_scales(0.5, 2.0)
+            .with_length(4);
+        let mut simulation = OdeSimulation::new(Row {
+            start: 1.0,
+            rate: 0.05,
+        })
+        .with_integrator(IntegratorKind::Euler)
+        .with_trail(3)
+        .with_plot(plot);
+        let mut buffer = InstanceBuffer::new("Test");
+
+        for _ in 0..3 {
+            step(&mut simulation, &mut buffer);
+        }
+
+        let trail_ids = simulation.trails[0].iter().copied().collect::<Vec<_>>();
+
+        for _ in 0..6 {
+            step(&mut simulation, &mut buffer);
+        }
+
+        let trail = &simulation.trails[0];
+        let PlotPoints { ids, times, .. } = &simulation.plots[0];
+
+        assert_eq!(buffer.len(), 1 + 3 + 4);
+        assert_eq!(trail.len(), 3);
+        assert!(trail.iter().all(|id| trail_ids.contains(id)));
+
+        // Trail points hold the body's last positions, oldest first
+        for (id, age) in trail.iter().zip([2.0, 1.0, 0.0]) {
+            let y = buffer.get(*id).unwrap().position().y;
+
+            assert!((y - (1.0 + 0.05 * (9.0 - age))).abs() < 1e-5);
+        }
+
+        // Plot points scroll left from the origin, newest at the origin
+        assert_eq!(
+            times.iter().copied().collect::<Vec<_>>(),
+            [6.0, 7.0, 8.0, 9.0]
+        );
+
+        for (id, t) in ids.iter().zip(times) {
+            let position = buffer.get(*id).unwrap().position();
+
+            assert!((position.x - (5.0 - (9.0 - t) * 0.5)).abs() < 1e-5);
+            assert!((position.y - (1.0 + 0.05 * t) * 2.0).abs() < 1e-5);
+        }
+    }
+}
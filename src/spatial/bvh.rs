@@ -0,0 +1,572 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::Hash;
+
+use cgmath::prelude::*;
+use cgmath::Vector3;
+
+use super::{KNearest, Ranked};
+use crate::geometry::{Bounds, Ray};
+
+enum NodeKind<K> {
+    // Exact bounds, the node's own are grown by the margin
+    Leaf { key: K, bounds: Bounds },
+    Branch([usize; 2]),
+}
+
+struct Node<K> {
+    bounds: Bounds,
+    parent: Option<usize>,
+    kind: NodeKind<K>,
+}
+
+/// Bounding volume hierarchy over keyed bounds, a binary tree whose nodes
+/// bound their children. Leaves are padded by a margin so small movements
+/// don't change the tree, see [`Bvh::with_margin`].
+pub struct Bvh<K> {
+    nodes: Vec<Node<K>>,
+    free_nodes: Vec<usize>,
+    root: Option<usize>,
+    leaves: HashMap<K, usize>,
+    margin: f32,
+}
+
+impl<K: Copy + Eq + Hash> Bvh<K> {
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            free_nodes: Vec::new(),
+            root: None,
+            leaves: HashMap::new(),
+            margin: 0.0,
+        }
+    }
+
+    /// Builds a balanced tree over every item at once, better than inserting
+    /// them one by one
+    pub fn build(items: impl IntoIterator<Item = (K, Bounds)>) -> Self {
+        let mut bvh = Self::new();
+        let mut items = items.into_iter().collect::<Vec<_>>();
+
+        bvh.root = bvh.build_node(&mut items, None);
+        bvh
+    }
+
+    /// Padding added around leaves, moving items only restructure the tree
+    /// once they leave it. Applies to items inserted afterwards.
+    pub fn with_margin(mut self, margin: f32) -> Self {
+        self.margin = margin.max(0.0);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    pub fn contains(&self, key: K) -> bool {
+        self.leaves.contains_key(&key)
+    }
+
+    /// Bounds the item was last inserted with
+    pub fn bounds(&self, key: K) -> Option<Bounds> {
+        match self.nodes[*self.leaves.get(&key)?].kind {
+            NodeKind::Leaf { bounds, .. } => Some(bounds),
+            NodeKind::Branch(_) => None,
+        }
+    }
+
+    /// Bounds of everything in the tree, padding included
+    pub fn root_bounds(&self) -> Option<Bounds> {
+        self.root.map(|root| self.nodes[root].bounds)
+    }
+
+    fn allocate(&mut self, node: Node<K>) -> usize {
+        match self.free_nodes.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    // Splits the items at the median of their centres along the axis they
+    // spread furthest on
+    fn build_node(&mut self, items: &mut [(K, Bounds)], parent: Option<usize>) -> Option<usize> {
+        match items {
+            [] => None,
+            [(key, bounds)] => {
+                let leaf = self.allocate(Node {
+                    bounds: bounds.expanded(self.margin),
+                    parent,
+                    kind: NodeKind::Leaf {
+                        key: *key,
+                        bounds: *bounds,
+                    },
+                });
+
+                self.leaves.insert(*key, leaf);
+                Some(leaf)
+            }
+            _ => {
+                let centers = Bounds::from_points(items.iter().map(|(_, bounds)| bounds.center()));
+                let axis = centers.longest_axis();
+                let middle = items.len() / 2;
+
+                items.select_nth_unstable_by(middle, |a, b| {
+                    a.1.center()[axis].total_cmp(&b.1.center()[axis])
+                });
+
+                let node = self.allocate(Node {
+                    bounds: Bounds::empty(),
+                    parent,
+                    kind: NodeKind::Branch([0, 0]),
+                });
+                let (low, high) = items.split_at_mut(middle);
+                let children = [
+                    self.build_node(low, Some(node)).unwrap(),
+                    self.build_node(high, Some(node)).unwrap(),
+                ];
+
+                self.nodes[node].bounds = self.nodes[children[0]]
+                    .bounds
+                    .union(&self.nodes[children[1]].bounds);
+                self.nodes[node].kind = NodeKind::Branch(children);
+                Some(node)
+            }
+        }
+    }
+
+    /// Adds an item, or updates its bounds if the key is already in the tree.
+    /// Returns whether the tree changed shape, which items that stay inside
+    /// their padding don't.
+    pub fn insert(&mut self, key: K, bounds: Bounds) -> bool {
+        if let Some(&leaf) = self.leaves.get(&key) {
+            let padded = self.nodes[leaf].bounds;
+
+            if padded.contains(bounds.min) && padded.contains(bounds.max) {
+                self.nodes[leaf].kind = NodeKind::Leaf { key, bounds };
+                return false;
+            }
+
+            self.remove(key);
+        }
+
+        let leaf = self.allocate(Node {
+            bounds: bounds.expanded(self.margin),
+            parent: None,
+            kind: NodeKind::Leaf { key, bounds },
+        });
+
+        self.leaves.insert(key, leaf);
+        self.insert_leaf(leaf);
+        true
+    }
+
+    fn insert_leaf(&mut self, leaf: usize) {
+        let Some(root) = self.root else {
+            self.root = Some(leaf);
+            return;
+        };
+
+        let bounds = self.nodes[leaf].bounds;
+        let mut sibling = root;
+
+        // Descend towards the cheapest place to pair the leaf, by the surface
+        // area it adds to the tree
+        while let NodeKind::Branch(children) = self.nodes[sibling].kind {
+            let area = self.nodes[sibling].bounds.surface_area();
+            let combined = self.nodes[sibling].bounds.union(&bounds).surface_area();
+            let pair_here = 2.0 * combined;
+            let inherited = 2.0 * (combined - area);
+
+            let descend = |child: usize| {
+                let node = &self.nodes[child];
+                let union = node.bounds.union(&bounds).surface_area();
+
+                match node.kind {
+                    NodeKind::Leaf { .. } => union + inherited,
+                    NodeKind::Branch(_) => union - node.bounds.surface_area() + inherited,
+                }
+            };
+            let costs = children.map(descend);
+
+            if pair_here < costs[0] && pair_here < costs[1] {
+                break;
+            }
+
+            sibling = if costs[0] <= costs[1] {
+                children[0]
+            } else {
+                children[1]
+            };
+        }
+
+        let parent = self.nodes[sibling].parent;
+        let branch = self.allocate(Node {
+            bounds: self.nodes[sibling].bounds.union(&bounds),
+            parent,
+            kind: NodeKind::Branch([sibling, leaf]),
+        });
+
+        match parent {
+            Some(parent) => self.replace_child(parent, sibling, branch),
+            None => self.root = Some(branch),
+        }
+
+        self.nodes[sibling].parent = Some(branch);
+        self.nodes[leaf].parent = Some(branch);
+        self.refit(parent);
+    }
+
+    fn replace_child(&mut self, parent: usize, old: usize, new: usize) {
+        if let NodeKind::Branch(children) = &mut self.nodes[parent].kind {
+            for child in children {
+                if *child == old {
+                    *child = new;
+                }
+            }
+        }
+    }
+
+    // Recomputes the bounds of `node` and its ancestors
+    fn refit(&mut self, mut node: Option<usize>) {
+        while let Some(current) = node {
+            if let NodeKind::Branch([a, b]) = self.nodes[current].kind {
+                self.nodes[current].bounds = self.nodes[a].bounds.union(&self.nodes[b].bounds);
+            }
+
+            node = self.nodes[current].parent;
+        }
+    }
+
+    /// Removes an item, returning the bounds it was inserted with
+    pub fn remove(&mut self, key: K) -> Option<Bounds> {
+        let leaf = self.leaves.remove(&key)?;
+        let bounds = match self.nodes[leaf].kind {
+            NodeKind::Leaf { bounds, .. } => bounds,
+            NodeKind::Branch(_) => unreachable!(),
+        };
+
+        self.free_nodes.push(leaf);
+
+        let Some(parent) = self.nodes[leaf].parent else {
+            self.root = None;
+            return Some(bounds);
+        };
+
+        // The sibling takes the parent's place
+        let NodeKind::Branch(children) = self.nodes[parent].kind else {
+            unreachable!()
+        };
+        let sibling = if children[0] == leaf {
+            children[1]
+        } else {
+            children[0]
+        };
+        let grandparent = self.nodes[parent].parent;
+
+        self.free_nodes.push(parent);
+        self.nodes[sibling].parent = grandparent;
+
+        match grandparent {
+            Some(grandparent) => {
+                self.replace_child(grandparent, parent, sibling);
+                self.refit(Some(grandparent));
+            }
+            None => self.root = Some(sibling),
+        }
+
+        Some(bounds)
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.free_nodes.clear();
+        self.root = None;
+        self.leaves.clear();
+    }
+
+    /// Calls `f` with every item whose bounds overlap `bounds`
+    pub fn for_each_overlapping(&self, bounds: &Bounds, mut f: impl FnMut(K)) {
+        let mut stack = self.root.into_iter().collect::<Vec<_>>();
+
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+
+            if !node.bounds.overlaps(bounds) {
+                continue;
+            }
+
+            match node.kind {
+                NodeKind::Leaf { key, bounds: exact } => {
+                    if exact.overlaps(bounds) {
+                        f(key);
+                    }
+                }
+                NodeKind::Branch(children) => stack.extend(children),
+            }
+        }
+    }
+
+    /// Keys of every item whose bounds overlap `bounds`
+    pub fn overlapping(&self, bounds: &Bounds) -> Vec<K> {
+        let mut keys = Vec::new();
+
+        self.for_each_overlapping(bounds, |key| keys.push(key));
+        keys
+    }
+
+    /// Keys of every item whose bounds come within `radius` of `center`
+    pub fn within(&self, center: Vector3<f32>, radius: f32) -> Vec<K> {
+        let mut keys = Vec::new();
+
+        self.for_each_overlapping(
+            &Bounds::from_center(center, Vector3::from_value(radius)),
+            |key| {
+                if self.bounds(key).unwrap().distance2(center) <= radius * radius {
+                    keys.push(key);
+                }
+            },
+        );
+        keys
+    }
+
+    /// Up to `k` items whose bounds are closest to `point`, closest first
+    /// with their distances
+    pub fn nearest(&self, point: Vector3<f32>, k: usize) -> Vec<(K, f32)> {
+        self.nearest_by(point, k, |_, bounds| bounds.distance2(point).sqrt())
+    }
+
+    /// Up to `k` items closest to `point` by `distance`, given each item's
+    /// key and bounds, which must be no less than the distance to the bounds
+    pub fn nearest_by(
+        &self,
+        point: Vector3<f32>,
+        k: usize,
+        mut distance: impl FnMut(K, &Bounds) -> f32,
+    ) -> Vec<(K, f32)> {
+        let mut nearest = KNearest::new(k);
+        let mut queue = self
+            .root
+            .map(|root| {
+                Reverse(Ranked {
+                    distance: self.nodes[root].bounds.distance2(point).sqrt(),
+                    item: root,
+                })
+            })
+            .into_iter()
+            .collect::<BinaryHeap<_>>();
+
+        // Visit nodes closest first until none can beat the items found
+        while let Some(Reverse(Ranked {
+            distance: bound,
+            item,
+        })) = queue.pop()
+        {
+            if bound >= nearest.bound() {
+                break;
+            }
+
+            match &self.nodes[item].kind {
+                NodeKind::Leaf { key, bounds } => nearest.offer(*key, distance(*key, bounds)),
+                NodeKind::Branch(children) => {
+                    for &child in children {
+                        queue.push(Reverse(Ranked {
+                            distance: self.nodes[child].bounds.distance2(point).sqrt(),
+                            item: child,
+                        }));
+                    }
+                }
+            }
+        }
+
+        nearest.into_sorted()
+    }
+
+    /// First item whose bounds the ray enters, with the distance along it
+    pub fn raycast(&self, ray: &Ray, max_distance: f32) -> Option<(K, f32)> {
+        self.raycast_by(ray, max_distance, |_, bounds, max_distance| {
+            bounds.ray_intersection(ray, max_distance)
+        })
+    }
+
+    /// First item `hit` reports the ray hitting within the given distance,
+    /// given each item whose bounds the ray enters
+    pub fn raycast_by(
+        &self,
+        ray: &Ray,
+        max_distance: f32,
+        mut hit: impl FnMut(K, &Bounds, f32) -> Option<f32>,
+    ) -> Option<(K, f32)> {
+        let mut best: Option<(K, f32)> = None;
+        let mut queue = self
+            .root
+            .and_then(|root| {
+                let entry = self.nodes[root]
+                    .bounds
+                    .ray_intersection(ray, max_distance)?;
+
+                Some(Reverse(Ranked {
+                    distance: entry,
+                    item: root,
+                }))
+            })
+            .into_iter()
+            .collect::<BinaryHeap<_>>();
+
+        // Visit nodes in the order the ray enters them
+        while let Some(Reverse(Ranked {
+            distance: entry,
+            item,
+        })) = queue.pop()
+        {
+            let limit = best.map_or(max_distance, |(_, t)| t);
+
+            if entry > limit {
+                break;
+            }
+
+            match &self.nodes[item].kind {
+                NodeKind::Leaf { key, bounds } => {
+                    if let Some(t) = hit(*key, bounds, limit).filter(|t| *t <= limit) {
+                        best = Some((*key, t));
+                    }
+                }
+                NodeKind::Branch(children) => {
+                    for &child in children {
+                        if let Some(entry) = self.nodes[child].bounds.ray_intersection(ray, limit) {
+                            queue.push(Reverse(Ranked {
+                                distance: entry,
+                                item: child,
+                            }));
+                        }
+                    }
+                }
+            }
+        }
+
+        best
+    }
+}
+
+impl<K: Copy + Eq + Hash> Default for Bvh<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::random::Rng;
+
+    fn random_point(rng: &mut Rng) -> Vector3<f32> {
+        Vector3::new(
+            rng.range_f32(-10.0..10.0),
+            rng.range_f32(-10.0..10.0),
+            rng.range_f32(-10.0..10.0),
+        )
+    }
+
+    fn random_bounds(rng: &mut Rng) -> Bounds {
+        let half_extents = Vector3::new(
+            rng.range_f32(0.05..0.5),
+            rng.range_f32(0.05..0.5),
+            rng.range_f32(0.05..0.5),
+        );
+
+        Bounds::from_center(random_point(rng), half_extents)
+    }
+
+    fn sorted(mut keys: Vec<u32>) -> Vec<u32> {
+        keys.sort_unstable();
+        keys
+    }
+
+    #[test]
+    fn queries_match_brute_force() {
+        let mut rng = Rng::new(3, 0);
+        let mut items = HashMap::new();
+
+        for key in 0..250 {
+            items.insert(key, random_bounds(&mut rng));
+        }
+
+        let mut bvh = Bvh::build(items.iter().map(|(key, bounds)| (*key, *bounds)));
+
+        for key in 250..500 {
+            let bounds = random_bounds(&mut rng);
+
+            bvh.insert(key, bounds);
+            items.insert(key, bounds);
+        }
+
+        for key in (0..500).step_by(5) {
+            let bounds = random_bounds(&mut rng);
+
+            bvh.insert(key, bounds);
+            items.insert(key, bounds);
+        }
+
+        for key in (0..500).step_by(7) {
+            assert_eq!(bvh.remove(key).is_some(), items.remove(&key).is_some());
+        }
+
+        assert_eq!(bvh.len(), items.len());
+
+        for _ in 0..20 {
+            let query = random_bounds(&mut rng);
+            let expected = items
+                .iter()
+                .filter(|(_, bounds)| bounds.overlaps(&query))
+                .map(|(key, _)| *key)
+                .collect();
+
+            assert_eq!(sorted(bvh.overlapping(&query)), sorted(expected));
+
+            let center = random_point(&mut rng);
+            let expected = items
+                .iter()
+                .filter(|(_, bounds)| bounds.distance2(center) <= 2.5 * 2.5)
+                .map(|(key, _)| *key)
+                .collect();
+
+            assert_eq!(sorted(bvh.within(center, 2.5)), sorted(expected));
+
+            let nearest = bvh.nearest(center, 5);
+            let mut distances = items
+                .values()
+                .map(|bounds| bounds.distance2(center).sqrt())
+                .collect::<Vec<_>>();
+
+            distances.sort_by(f32::total_cmp);
+            assert_eq!(nearest.len(), 5);
+
+            for ((_, distance), expected) in nearest.iter().zip(&distances) {
+                assert!((distance - expected).abs() < 1e-5);
+            }
+
+            let ray = Ray::new(center, random_point(&mut rng));
+            let hit = bvh.raycast(&ray, 30.0).map(|(_, t)| t);
+            let expected = items
+                .values()
+                .filter_map(|bounds| bounds.ray_intersection(&ray, 30.0))
+                .min_by(f32::total_cmp);
+
+            match (hit, expected) {
+                (Some(hit), Some(expected)) => assert!((hit - expected).abs() < 1e-4),
+                (hit, expected) => assert_eq!(hit, expected),
+            }
+        }
+    }
+}
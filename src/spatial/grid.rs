@@ -0,0 +1,516 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use cgmath::prelude::*;
+use cgmath::Vector3;
+
+use super::KNearest;
+use crate::geometry::Ray;
+
+type Cell = [i32; 3];
+
+struct Item<K> {
+    key: K,
+    position: Vector3<f32>,
+    cell: Cell,
+}
+
+/// Points bucketed into a uniform grid of cubic cells, storing only occupied
+/// cells. Suits many evenly spread points queried about a cell size away, as
+/// in flocking, SPH or molecular dynamics.
+pub struct HashGrid<K> {
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<usize>>,
+    // Dense so iteration and results follow insertion order
+    items: Vec<Item<K>>,
+    indices: HashMap<K, usize>,
+    // Lowest and highest cells ever occupied, bounding ray and nearest
+    // searches
+    extent: Option<(Cell, Cell)>,
+}
+
+impl<K: Copy + Eq + Hash> HashGrid<K> {
+    /// Panics unless `cell_size` is positive
+    pub fn new(cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "Grid cells need a positive size");
+
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            items: Vec::new(),
+            indices: HashMap::new(),
+            extent: None,
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn contains(&self, key: K) -> bool {
+        self.indices.contains_key(&key)
+    }
+
+    pub fn position(&self, key: K) -> Option<Vector3<f32>> {
+        self.indices
+            .get(&key)
+            .map(|index| self.items[*index].position)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (K, Vector3<f32>)> + '_ {
+        self.items.iter().map(|item| (item.key, item.position))
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.items.clear();
+        self.indices.clear();
+        self.extent = None;
+    }
+
+    fn cell_of(&self, position: Vector3<f32>) -> Cell {
+        [0, 1, 2].map(|axis| (position[axis] / self.cell_size).floor() as i32)
+    }
+
+    /// Adds a point, or moves it if the key is already in the grid. Points
+    /// that stay in their cell only have their position changed.
+    pub fn insert(&mut self, key: K, position: Vector3<f32>) {
+        let cell = self.cell_of(position);
+
+        self.extent = Some(match self.extent {
+            Some((low, high)) => (
+                [0, 1, 2].map(|axis| low[axis].min(cell[axis])),
+                [0, 1, 2].map(|axis| high[axis].max(cell[axis])),
+            ),
+            None => (cell, cell),
+        });
+
+        if let Some(&index) = self.indices.get(&key) {
+            let item = &mut self.items[index];
+            let previous = item.cell;
+
+            item.position = position;
+            item.cell = cell;
+
+            if previous != cell {
+                self.unlink(previous, index);
+                self.cells.entry(cell).or_default().push(index);
+            }
+
+            return;
+        }
+
+        let index = self.items.len();
+
+        self.items.push(Item {
+            key,
+            position,
+            cell,
+        });
+        self.indices.insert(key, index);
+        self.cells.entry(cell).or_default().push(index);
+    }
+
+    /// Removes a point, returning where it was
+    pub fn remove(&mut self, key: K) -> Option<Vector3<f32>> {
+        let index = self.indices.remove(&key)?;
+        let item = self.items.swap_remove(index);
+
+        self.unlink(item.cell, index);
+
+        // The last item moved into the gap
+        if let Some(moved) = self.items.get(index) {
+            let previous = self.items.len();
+
+            self.indices.insert(moved.key, index);
+
+            for slot in self.cells.get_mut(&moved.cell).unwrap() {
+                if *slot == previous {
+                    *slot = index;
+                }
+            }
+        }
+
+        Some(item.position)
+    }
+
+    // Drops an item index from a cell, and the cell once it's empty
+    fn unlink(&mut self, cell: Cell, index: usize) {
+        let indices = self.cells.get_mut(&cell).unwrap();
+
+        indices.retain(|other| *other != index);
+
+        if indices.is_empty() {
+            self.cells.remove(&cell);
+        }
+    }
+
+    // Item indices in the cells from `low` to `high` inclusive
+    fn block(&self, low: Cell, high: Cell) -> impl Iterator<Item = usize> + '_ {
+        (low[0]..=high[0]).flat_map(move |x| {
+            (low[1]..=high[1]).flat_map(move |y| {
+                (low[2]..=high[2])
+                    .filter_map(move |z| self.cells.get(&[x, y, z]))
+                    .flatten()
+                    .copied()
+            })
+        })
+    }
+
+    // Whether a block reaching `radius` around a cell spans more cells than
+    // there are points, so checking every point is cheaper. Counted in f64
+    // since huge or infinite radii overflow cell coordinates.
+    fn scan_all(&self, radius: f32) -> bool {
+        let side = 2.0 * (f64::from(radius) / f64::from(self.cell_size)).ceil() + 3.0;
+
+        side.powi(3) > self.items.len() as f64
+    }
+
+    /// Calls `f` with every point within `radius` of `center`
+    pub fn for_each_within(
+        &self,
+        center: Vector3<f32>,
+        radius: f32,
+        mut f: impl FnMut(K, Vector3<f32>),
+    ) {
+        let mut visit = |item: &Item<K>| {
+            if (item.position - center).magnitude2() <= radius * radius {
+                f(item.key, item.position);
+            }
+        };
+
+        if self.scan_all(radius) {
+            self.items.iter().for_each(visit);
+            return;
+        }
+
+        let low = self.cell_of(center - Vector3::from_value(radius));
+        let high = self.cell_of(center + Vector3::from_value(radius));
+
+        for index in self.block(low, high) {
+            visit(&self.items[index]);
+        }
+    }
+
+    /// Keys of every point within `radius` of `center`
+    pub fn within(&self, center: Vector3<f32>, radius: f32) -> Vec<K> {
+        let mut keys = Vec::new();
+
+        self.for_each_within(center, radius, |key, _| keys.push(key));
+        keys
+    }
+
+    /// Every pair of points within `radius` of each other, once each
+    pub fn pairs_within(&self, radius: f32) -> Vec<(K, K)> {
+        let mut pairs = Vec::new();
+        let mut check = |item: &Item<K>, other: &Item<K>| {
+            if (other.position - item.position).magnitude2() <= radius * radius {
+                pairs.push((item.key, other.key));
+            }
+        };
+
+        if self.scan_all(radius) {
+            for (index, item) in self.items.iter().enumerate() {
+                for other in &self.items[index + 1..] {
+                    check(item, other);
+                }
+            }
+
+            return pairs;
+        }
+
+        let reach = (radius / self.cell_size).ceil() as i32;
+
+        for (index, item) in self.items.iter().enumerate() {
+            let low = item.cell.map(|c| c - reach);
+            let high = item.cell.map(|c| c + reach);
+
+            for other in self.block(low, high).filter(|other| *other > index) {
+                check(item, &self.items[other]);
+            }
+        }
+
+        pairs
+    }
+
+    /// Up to `k` points closest to `point`, closest first with their
+    /// distances
+    pub fn nearest(&self, point: Vector3<f32>, k: usize) -> Vec<(K, f32)> {
+        let mut nearest = KNearest::new(k);
+        let Some((low, high)) = self.extent.filter(|_| k > 0) else {
+            return Vec::new();
+        };
+
+        let center = self.cell_of(point);
+        let rings = (0..3)
+            .map(|axis| (center[axis] - low[axis]).max(high[axis] - center[axis]))
+            .max()
+            .unwrap()
+            .max(0);
+
+        for ring in 0..=rings {
+            // Past this many cells checking every point is cheaper
+            if (2 * ring as usize + 1).pow(3) > self.items.len() * 8 {
+                for item in &self.items {
+                    nearest.offer(item.key, (item.position - point).magnitude());
+                }
+
+                break;
+            }
+
+            for index in self.shell(center, ring) {
+                let item = &self.items[index];
+
+                nearest.offer(item.key, (item.position - point).magnitude());
+            }
+
+            // Points beyond this ring are at least a ring's width away
+            if nearest.bound() <= ring as f32 * self.cell_size {
+                break;
+            }
+        }
+
+        nearest.into_sorted()
+    }
+
+    // Item indices in the cells exactly `ring` cells from `center` along some
+    // axis
+    fn shell(&self, center: Cell, ring: i32) -> impl Iterator<Item = usize> + '_ {
+        (-ring..=ring).flat_map(move |x| {
+            (-ring..=ring).flat_map(move |y| {
+                let step = if x.abs() == ring || y.abs() == ring {
+                    1
+                } else {
+                    (2 * ring).max(1)
+                };
+
+                (-ring..=ring)
+                    .step_by(step as usize)
+                    .filter_map(move |z| {
+                        self.cells
+                            .get(&[center[0] + x, center[1] + y, center[2] + z])
+                    })
+                    .flatten()
+                    .copied()
+            })
+        })
+    }
+
+    /// First point the ray passes within `radius` of, treating points as
+    /// spheres, with the distance along the ray to the sphere
+    pub fn raycast(&self, ray: &Ray, max_distance: f32, radius: f32) -> Option<(K, f32)> {
+        let (low, high) = self.extent?;
+        let reach = (radius / self.cell_size).ceil() as i32;
+        let (low, high) = (low.map(|c| c - reach), high.map(|c| c + reach));
+
+        // Walk the cells the ray passes through in order
+        let mut cell = self.cell_of(ray.origin);
+        let mut step = [0; 3];
+        let mut next = [f32::INFINITY; 3];
+        let mut delta = [f32::INFINITY; 3];
+
+        for axis in 0..3 {
+            let direction = ray.direction[axis];
+
+            if direction != 0.0 {
+                step[axis] = direction.signum() as i32;
+                delta[axis] = self.cell_size / direction.abs();
+
+                let boundary = (cell[axis] + (step[axis] > 0) as i32) as f32 * self.cell_size;
+                next[axis] = (boundary - ray.origin[axis]) / direction;
+            }
+        }
+
+        let mut entry = 0.0;
+        let mut best: Option<(K, f32)> = None;
+        let mut scanned = HashSet::new();
+
+        loop {
+            let leaving = (0..3).any(|axis| {
+                (step[axis] >= 0 && cell[axis] > high[axis])
+                    || (step[axis] <= 0 && cell[axis] < low[axis])
+            });
+
+            if leaving || entry > max_distance || best.is_some_and(|(_, t)| entry > t) {
+                break;
+            }
+
+            // Spheres reaching into this cell have centres within reach
+            for x in -reach..=reach {
+                for y in -reach..=reach {
+                    for z in -reach..=reach {
+                        let neighbour = [cell[0] + x, cell[1] + y, cell[2] + z];
+
+                        if !scanned.insert(neighbour) {
+                            continue;
+                        }
+
+                        for &index in self.cells.get(&neighbour).into_iter().flatten() {
+                            let item = &self.items[index];
+
+                            if let Some(t) = ray.sphere_intersection(item.position, radius) {
+                                if t <= max_distance && best.is_none_or(|(_, best)| t < best) {
+                                    best = Some((item.key, t));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
+            let axis = (0..3).min_by(|a, b| next[*a].total_cmp(&next[*b])).unwrap();
+
+            entry = next[axis];
+            cell[axis] += step[axis];
+            next[axis] += delta[axis];
+        }
+
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::random::Rng;
+
+    fn random_point(rng: &mut Rng) -> Vector3<f32> {
+        Vector3::new(
+            rng.range_f32(-10.0..10.0),
+            rng.range_f32(-10.0..10.0),
+            rng.range_f32(-10.0..10.0),
+        )
+    }
+
+    // A grid and the same points in a map, after moves and removals
+    fn scattered() -> (HashGrid<u32>, HashMap<u32, Vector3<f32>>, Rng) {
+        let mut rng = Rng::new(1, 0);
+        let mut grid = HashGrid::new(1.5);
+        let mut points = HashMap::new();
+
+        for key in 0..500 {
+            let position = random_point(&mut rng);
+
+            grid.insert(key, position);
+            points.insert(key, position);
+        }
+
+        for key in (0..500).step_by(5) {
+            let position = random_point(&mut rng);
+
+            grid.insert(key, position);
+            points.insert(key, position);
+        }
+
+        for key in (0..500).step_by(7) {
+            assert_eq!(grid.remove(key), points.remove(&key));
+        }
+
+        assert_eq!(grid.len(), points.len());
+
+        (grid, points, rng)
+    }
+
+    #[test]
+    fn queries_match_brute_force() {
+        let (grid, points, mut rng) = scattered();
+
+        for _ in 0..20 {
+            let center = random_point(&mut rng);
+
+            let mut within = grid.within(center, 2.5);
+            let mut expected = points
+                .iter()
+                .filter(|(_, position)| (*position - center).magnitude() <= 2.5)
+                .map(|(key, _)| *key)
+                .collect::<Vec<_>>();
+
+            within.sort_unstable();
+            expected.sort_unstable();
+            assert_eq!(within, expected);
+
+            let nearest = grid.nearest(center, 5);
+            let mut distances = points
+                .values()
+                .map(|position| (position - center).magnitude())
+                .collect::<Vec<_>>();
+
+            distances.sort_by(f32::total_cmp);
+            assert_eq!(nearest.len(), 5);
+
+            for ((_, distance), expected) in nearest.iter().zip(&distances) {
+                assert!((distance - expected).abs() < 1e-5);
+            }
+
+            let ray = Ray::new(center, random_point(&mut rng));
+            let hit = grid.raycast(&ray, 30.0, 0.3).map(|(_, t)| t);
+            let expected = points
+                .values()
+                .filter_map(|position| ray.sphere_intersection(*position, 0.3))
+                .filter(|t| *t <= 30.0)
+                .min_by(f32::total_cmp);
+
+            match (hit, expected) {
+                (Some(hit), Some(expected)) => assert!((hit - expected).abs() < 1e-4),
+                (hit, expected) => assert_eq!(hit, expected),
+            }
+        }
+    }
+
+    #[test]
+    fn pairs_match_brute_force() {
+        let (grid, points, _) = scattered();
+
+        let mut pairs = grid
+            .pairs_within(1.0)
+            .into_iter()
+            .map(|(a, b)| (a.min(b), a.max(b)))
+            .collect::<Vec<_>>();
+        let mut expected = Vec::new();
+
+        for (a, position_a) in &points {
+            for (b, position_b) in &points {
+                if a < b && (position_a - position_b).magnitude() <= 1.0 {
+                    expected.push((*a, *b));
+                }
+            }
+        }
+
+        pairs.sort_unstable();
+        expected.sort_unstable();
+        assert!(!expected.is_empty());
+        assert_eq!(pairs, expected);
+    }
+
+    #[test]
+    fn huge_radii_scan_every_point() {
+        let (grid, points, _) = scattered();
+
+        for radius in [1e9, f32::MAX, f32::INFINITY] {
+            let mut within = grid.within(Vector3::zero(), radius);
+            let mut expected = points.keys().copied().collect::<Vec<_>>();
+
+            within.sort_unstable();
+            expected.sort_unstable();
+            assert_eq!(within, expected);
+
+            let n = points.len();
+
+            assert_eq!(grid.pairs_within(radius).len(), n * (n - 1) / 2);
+        }
+
+        assert!(HashGrid::<u32>::new(1.0)
+            .pairs_within(f32::INFINITY)
+            .is_empty());
+    }
+}
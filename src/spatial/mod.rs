@@ -0,0 +1,88 @@
+//! Spatial indices for neighbour, nearest and ray queries: a [`HashGrid`] and
+//! [`Octree`] over points, and a [`Bvh`] over bounds, also built over a
+//! model's triangles as a [`TriangleBvh`]. Each is updated in place as what
+//! it holds moves.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+mod bvh;
+mod grid;
+mod octree;
+mod triangles;
+
+pub use bvh::Bvh;
+pub use grid::HashGrid;
+pub use octree::Octree;
+pub use triangles::{TriangleBvh, TriangleHit};
+
+// Heap entry ordered by distance alone
+struct Ranked<T> {
+    distance: f32,
+    item: T,
+}
+
+impl<T> PartialEq for Ranked<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.distance.total_cmp(&other.distance) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Ranked<T> {}
+
+impl<T> PartialOrd for Ranked<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Ranked<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance.total_cmp(&other.distance)
+    }
+}
+
+// The k closest items offered so far
+struct KNearest<K> {
+    k: usize,
+    heap: BinaryHeap<Ranked<K>>,
+}
+
+impl<K> KNearest<K> {
+    fn new(k: usize) -> Self {
+        Self {
+            k,
+            heap: BinaryHeap::with_capacity(k + 1),
+        }
+    }
+
+    // Distance an item must beat to be kept
+    fn bound(&self) -> f32 {
+        if self.heap.len() < self.k {
+            f32::INFINITY
+        } else {
+            self.heap
+                .peek()
+                .map_or(f32::INFINITY, |worst| worst.distance)
+        }
+    }
+
+    fn offer(&mut self, item: K, distance: f32) {
+        if distance < self.bound() {
+            self.heap.push(Ranked { distance, item });
+
+            if self.heap.len() > self.k {
+                self.heap.pop();
+            }
+        }
+    }
+
+    // Closest first
+    fn into_sorted(self) -> Vec<(K, f32)> {
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|ranked| (ranked.item, ranked.distance))
+            .collect()
+    }
+}
@@ -0,0 +1,495 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::hash::Hash;
+
+use cgmath::prelude::*;
+use cgmath::Vector3;
+
+use super::{KNearest, Ranked};
+use crate::geometry::{Bounds, Ray};
+
+// Points a leaf holds before it splits
+const LEAF_CAPACITY_DEFAULT: usize = 8;
+// Nodes this small never split, so coincident points can't split forever
+const MIN_NODE_SIZE: f32 = 1e-3;
+
+struct Node<K> {
+    bounds: Bounds,
+    parent: Option<usize>,
+    children: Option<[usize; 8]>,
+    items: Vec<(K, Vector3<f32>)>,
+    // Points anywhere below the node
+    count: usize,
+}
+
+impl<K> Node<K> {
+    fn leaf(bounds: Bounds, parent: Option<usize>) -> Self {
+        Self {
+            bounds,
+            parent,
+            children: None,
+            items: Vec::new(),
+            count: 0,
+        }
+    }
+}
+
+/// Points in a tree of cubes, each split into eight once it holds too many.
+/// Adapts to clustered points better than a [`super::HashGrid`]. The root
+/// grows to take in points placed outside it.
+pub struct Octree<K> {
+    nodes: Vec<Node<K>>,
+    free_nodes: Vec<usize>,
+    root: usize,
+    // Leaf holding each key
+    leaves: HashMap<K, usize>,
+    leaf_capacity: usize,
+}
+
+impl<K: Copy + Eq + Hash> Octree<K> {
+    /// Octree whose root is the cube about `center` reaching `half_size`
+    /// along each axis
+    pub fn new(center: Vector3<f32>, half_size: f32) -> Self {
+        let half_size = half_size.max(MIN_NODE_SIZE);
+
+        Self {
+            nodes: vec![Node::leaf(
+                Bounds::from_center(center, Vector3::from_value(half_size)),
+                None,
+            )],
+            free_nodes: Vec::new(),
+            root: 0,
+            leaves: HashMap::new(),
+            leaf_capacity: LEAF_CAPACITY_DEFAULT,
+        }
+    }
+
+    /// Points a leaf holds before it splits
+    pub fn with_leaf_capacity(mut self, leaf_capacity: usize) -> Self {
+        self.leaf_capacity = leaf_capacity.max(1);
+        self
+    }
+
+    /// Bounds of the root cube
+    pub fn bounds(&self) -> Bounds {
+        self.nodes[self.root].bounds
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    pub fn contains(&self, key: K) -> bool {
+        self.leaves.contains_key(&key)
+    }
+
+    pub fn position(&self, key: K) -> Option<Vector3<f32>> {
+        let leaf = &self.nodes[*self.leaves.get(&key)?];
+
+        leaf.items
+            .iter()
+            .find(|(other, _)| *other == key)
+            .map(|(_, position)| *position)
+    }
+
+    fn allocate(&mut self, node: Node<K>) -> usize {
+        match self.free_nodes.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    // Octant of `bounds` holding `position`, one bit per axis
+    fn octant(bounds: &Bounds, position: Vector3<f32>) -> usize {
+        let center = bounds.center();
+
+        (0..3)
+            .filter(|axis| position[*axis] >= center[*axis])
+            .map(|axis| 1 << axis)
+            .sum()
+    }
+
+    fn octant_bounds(bounds: &Bounds, octant: usize) -> Bounds {
+        let center = bounds.center();
+        let half = bounds.half_extents() * 0.5;
+        let offset = Vector3::new(
+            if octant & 1 != 0 { half.x } else { -half.x },
+            if octant & 2 != 0 { half.y } else { -half.y },
+            if octant & 4 != 0 { half.z } else { -half.z },
+        );
+
+        Bounds::from_center(center + offset, half)
+    }
+
+    // Doubles the root towards `position` until it holds it
+    fn grow(&mut self, position: Vector3<f32>) {
+        while !self.nodes[self.root].bounds.contains(position) {
+            let old = self.nodes[self.root].bounds;
+            let size = old.size();
+            let mut bounds = old;
+
+            for axis in 0..3 {
+                if position[axis] < old.min[axis] {
+                    bounds.min[axis] -= size[axis];
+                } else {
+                    bounds.max[axis] += size[axis];
+                }
+            }
+
+            let root = self.allocate(Node::leaf(bounds, None));
+            let old_octant = Self::octant(&bounds, old.center());
+            let children = [0, 1, 2, 3, 4, 5, 6, 7].map(|octant| {
+                if octant == old_octant {
+                    self.root
+                } else {
+                    self.allocate(Node::leaf(Self::octant_bounds(&bounds, octant), Some(root)))
+                }
+            });
+
+            self.nodes[self.root].parent = Some(root);
+            self.nodes[root].children = Some(children);
+            self.nodes[root].count = self.nodes[self.root].count;
+            self.root = root;
+        }
+    }
+
+    /// Adds a point, or moves it if the key is already in the tree. Points
+    /// that stay in their leaf only have their position changed. Panics
+    /// unless `position` is finite, since the root can't grow to hold it.
+    pub fn insert(&mut self, key: K, position: Vector3<f32>) {
+        assert!(
+            (0..3).all(|axis| position[axis].is_finite()),
+            "Octree positions must be finite, got {position:?}"
+        );
+
+        if let Some(&leaf) = self.leaves.get(&key) {
+            if self.nodes[leaf].bounds.contains(position) {
+                let item = self.nodes[leaf]
+                    .items
+                    .iter_mut()
+                    .find(|(other, _)| *other == key)
+                    .unwrap();
+
+                item.1 = position;
+                return;
+            }
+
+            self.remove(key);
+        }
+
+        self.grow(position);
+
+        let mut node = self.root;
+
+        loop {
+            self.nodes[node].count += 1;
+
+            match self.nodes[node].children {
+                Some(children) => {
+                    node = children[Self::octant(&self.nodes[node].bounds, position)];
+                }
+                None => break,
+            }
+        }
+
+        self.nodes[node].items.push((key, position));
+        self.leaves.insert(key, node);
+        self.split(node);
+    }
+
+    // Splits a leaf holding too many points, and any child that still does
+    fn split(&mut self, node: usize) {
+        let bounds = self.nodes[node].bounds;
+
+        if self.nodes[node].items.len() <= self.leaf_capacity
+            || bounds.size().x * 0.5 < MIN_NODE_SIZE
+        {
+            return;
+        }
+
+        let children = [0, 1, 2, 3, 4, 5, 6, 7].map(|octant| {
+            self.allocate(Node::leaf(Self::octant_bounds(&bounds, octant), Some(node)))
+        });
+
+        for (key, position) in std::mem::take(&mut self.nodes[node].items) {
+            let child = children[Self::octant(&bounds, position)];
+
+            self.nodes[child].items.push((key, position));
+            self.nodes[child].count += 1;
+            self.leaves.insert(key, child);
+        }
+
+        self.nodes[node].children = Some(children);
+
+        for child in children {
+            self.split(child);
+        }
+    }
+
+    /// Removes a point, returning where it was
+    pub fn remove(&mut self, key: K) -> Option<Vector3<f32>> {
+        let leaf = self.leaves.remove(&key)?;
+        let items = &mut self.nodes[leaf].items;
+        let index = items.iter().position(|(other, _)| *other == key).unwrap();
+        let (_, position) = items.swap_remove(index);
+
+        // Collapse the highest ancestor left with few enough points
+        let mut node = Some(leaf);
+        let mut collapse = None;
+
+        while let Some(current) = node {
+            self.nodes[current].count -= 1;
+
+            if self.nodes[current].count <= self.leaf_capacity
+                && self.nodes[current].children.is_some()
+            {
+                collapse = Some(current);
+            }
+
+            node = self.nodes[current].parent;
+        }
+
+        if let Some(node) = collapse {
+            let mut items = Vec::new();
+
+            self.drain(node, &mut items);
+
+            for (key, _) in &items {
+                self.leaves.insert(*key, node);
+            }
+
+            self.nodes[node].items = items;
+        }
+
+        Some(position)
+    }
+
+    // Moves every point below `node` into `items`, freeing its descendants
+    fn drain(&mut self, node: usize, items: &mut Vec<(K, Vector3<f32>)>) {
+        items.append(&mut self.nodes[node].items);
+
+        if let Some(children) = self.nodes[node].children.take() {
+            for child in children {
+                self.drain(child, items);
+                self.free_nodes.push(child);
+            }
+        }
+    }
+
+    /// Calls `f` with every point within `radius` of `center`
+    pub fn for_each_within(
+        &self,
+        center: Vector3<f32>,
+        radius: f32,
+        mut f: impl FnMut(K, Vector3<f32>),
+    ) {
+        let mut stack = vec![self.root];
+
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+
+            if node.count == 0 || node.bounds.distance2(center) > radius * radius {
+                continue;
+            }
+
+            match node.children {
+                Some(children) => stack.extend(children.iter().rev()),
+                None => {
+                    for &(key, position) in &node.items {
+                        if (position - center).magnitude2() <= radius * radius {
+                            f(key, position);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Keys of every point within `radius` of `center`
+    pub fn within(&self, center: Vector3<f32>, radius: f32) -> Vec<K> {
+        let mut keys = Vec::new();
+
+        self.for_each_within(center, radius, |key, _| keys.push(key));
+        keys
+    }
+
+    /// Up to `k` points closest to `point`, closest first with their
+    /// distances
+    pub fn nearest(&self, point: Vector3<f32>, k: usize) -> Vec<(K, f32)> {
+        let mut nearest = KNearest::new(k);
+        let mut queue = BinaryHeap::from([Reverse(Ranked {
+            distance: self.nodes[self.root].bounds.distance2(point).sqrt(),
+            item: self.root,
+        })]);
+
+        // Visit nodes closest first until none can beat the points found
+        while let Some(Reverse(Ranked { distance, item })) = queue.pop() {
+            if distance >= nearest.bound() {
+                break;
+            }
+
+            let node = &self.nodes[item];
+
+            match node.children {
+                Some(children) => {
+                    for child in children.into_iter().filter(|c| self.nodes[*c].count > 0) {
+                        queue.push(Reverse(Ranked {
+                            distance: self.nodes[child].bounds.distance2(point).sqrt(),
+                            item: child,
+                        }));
+                    }
+                }
+                None => {
+                    for &(key, position) in &node.items {
+                        nearest.offer(key, (position - point).magnitude());
+                    }
+                }
+            }
+        }
+
+        nearest.into_sorted()
+    }
+
+    /// First point the ray passes within `radius` of, treating points as
+    /// spheres, with the distance along the ray to the sphere
+    pub fn raycast(&self, ray: &Ray, max_distance: f32, radius: f32) -> Option<(K, f32)> {
+        let mut best: Option<(K, f32)> = None;
+        let mut stack = vec![self.root];
+
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            let limit = best.map_or(max_distance, |(_, t)| t);
+
+            if node.count == 0
+                || node
+                    .bounds
+                    .expanded(radius)
+                    .ray_intersection(ray, limit)
+                    .is_none()
+            {
+                continue;
+            }
+
+            match node.children {
+                Some(children) => stack.extend(children),
+                None => {
+                    for &(key, position) in &node.items {
+                        if let Some(t) = ray.sphere_intersection(position, radius) {
+                            if t <= limit && best.is_none_or(|(_, best)| t < best) {
+                                best = Some((key, t));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::random::Rng;
+
+    fn random_point(rng: &mut Rng) -> Vector3<f32> {
+        Vector3::new(
+            rng.range_f32(-10.0..10.0),
+            rng.range_f32(-10.0..10.0),
+            rng.range_f32(-10.0..10.0),
+        )
+    }
+
+    #[test]
+    fn queries_match_brute_force() {
+        let mut rng = Rng::new(2, 0);
+        let mut octree = Octree::new(Vector3::zero(), 10.0).with_leaf_capacity(4);
+        let mut points = HashMap::new();
+
+        for key in 0..500 {
+            let position = random_point(&mut rng);
+
+            octree.insert(key, position);
+            points.insert(key, position);
+        }
+
+        for key in (0..500).step_by(5) {
+            let position = random_point(&mut rng);
+
+            octree.insert(key, position);
+            points.insert(key, position);
+        }
+
+        for key in (0..500).step_by(7) {
+            assert_eq!(octree.remove(key), points.remove(&key));
+        }
+
+        assert_eq!(octree.len(), points.len());
+
+        for _ in 0..20 {
+            let center = random_point(&mut rng);
+
+            let mut within = octree.within(center, 2.5);
+            let mut expected = points
+                .iter()
+                .filter(|(_, position)| (*position - center).magnitude() <= 2.5)
+                .map(|(key, _)| *key)
+                .collect::<Vec<_>>();
+
+            within.sort_unstable();
+            expected.sort_unstable();
+            assert_eq!(within, expected);
+
+            let nearest = octree.nearest(center, 5);
+            let mut distances = points
+                .values()
+                .map(|position| (position - center).magnitude())
+                .collect::<Vec<_>>();
+
+            distances.sort_by(f32::total_cmp);
+            assert_eq!(nearest.len(), 5);
+
+            for ((_, distance), expected) in nearest.iter().zip(&distances) {
+                assert!((distance - expected).abs() < 1e-5);
+            }
+
+            let ray = Ray::new(center, random_point(&mut rng));
+            let hit = octree.raycast(&ray, 30.0, 0.3).map(|(_, t)| t);
+            let expected = points
+                .values()
+                .filter_map(|position| ray.sphere_intersection(*position, 0.3))
+                .filter(|t| *t <= 30.0)
+                .min_by(f32::total_cmp);
+
+            match (hit, expected) {
+                (Some(hit), Some(expected)) => assert!((hit - expected).abs() < 1e-4),
+                (hit, expected) => assert_eq!(hit, expected),
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "must be finite")]
+    fn infinite_positions_are_rejected() {
+        Octree::new(Vector3::zero(), 1.0).insert(0, Vector3::new(0.0, f32::INFINITY, 0.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "must be finite")]
+    fn nan_positions_are_rejected() {
+        Octree::new(Vector3::zero(), 1.0).insert(0, Vector3::new(f32::NAN, 0.0, 0.0));
+    }
+}
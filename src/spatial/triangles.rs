@@ -0,0 +1,146 @@
+use cgmath::prelude::*;
+use cgmath::Vector3;
+
+use super::Bvh;
+use crate::geometry::{closest_point_on_triangle, Bounds, Ray};
+use crate::model::instance::Instance;
+use crate::model::Model;
+
+/// Where a ray hit a [`TriangleBvh`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TriangleHit {
+    pub triangle: usize,
+    pub distance: f32,
+    pub point: Vector3<f32>,
+}
+
+/// A [`Bvh`] over a model's triangles in model space, numbered through each
+/// mesh in turn
+pub struct TriangleBvh {
+    triangles: Vec<[Vector3<f32>; 3]>,
+    // Mesh each triangle came from
+    meshes: Vec<usize>,
+    bvh: Bvh<usize>,
+}
+
+impl TriangleBvh {
+    pub fn new(model: &Model) -> Self {
+        let (meshes, triangles) = model.triangles().unzip();
+
+        Self::with_meshes(triangles, meshes)
+    }
+
+    /// Tree over loose triangles, all counted as mesh 0
+    pub fn from_triangles(triangles: Vec<[Vector3<f32>; 3]>) -> Self {
+        let meshes = vec![0; triangles.len()];
+
+        Self::with_meshes(triangles, meshes)
+    }
+
+    fn with_meshes(triangles: Vec<[Vector3<f32>; 3]>, meshes: Vec<usize>) -> Self {
+        let bvh = Bvh::build(
+            triangles
+                .iter()
+                .enumerate()
+                .map(|(index, triangle)| (index, Bounds::from_points(*triangle))),
+        );
+
+        Self {
+            triangles,
+            meshes,
+            bvh,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.triangles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty()
+    }
+
+    /// Panics if `triangle` is out of range
+    pub fn triangle(&self, triangle: usize) -> [Vector3<f32>; 3] {
+        self.triangles[triangle]
+    }
+
+    /// Mesh of the model the triangle came from
+    pub fn mesh(&self, triangle: usize) -> usize {
+        self.meshes[triangle]
+    }
+
+    /// Moves a triangle's corners, for deforming meshes. Panics if
+    /// `triangle` is out of range.
+    pub fn set_triangle(&mut self, triangle: usize, corners: [Vector3<f32>; 3]) {
+        self.triangles[triangle] = corners;
+        self.bvh.insert(triangle, Bounds::from_points(corners));
+    }
+
+    /// Closest point on the triangle to `point`
+    pub fn closest_point(&self, triangle: usize, point: Vector3<f32>) -> Vector3<f32> {
+        let [a, b, c] = self.triangles[triangle];
+
+        closest_point_on_triangle(point, a, b, c)
+    }
+
+    /// Triangles within `radius` of `center`
+    pub fn within(&self, center: Vector3<f32>, radius: f32) -> Vec<usize> {
+        let mut triangles = self.bvh.within(center, radius);
+
+        triangles.retain(|triangle| {
+            (self.closest_point(*triangle, center) - center).magnitude2() <= radius * radius
+        });
+        triangles
+    }
+
+    /// Up to `k` triangles closest to `point`, closest first with their
+    /// distances
+    pub fn nearest(&self, point: Vector3<f32>, k: usize) -> Vec<(usize, f32)> {
+        self.bvh.nearest_by(point, k, |triangle, _| {
+            (self.closest_point(triangle, point) - point).magnitude()
+        })
+    }
+
+    /// First triangle the ray hits from either side, in model space
+    pub fn raycast(&self, ray: &Ray, max_distance: f32) -> Option<TriangleHit> {
+        let (triangle, distance) = self.bvh.raycast_by(ray, max_distance, |triangle, _, _| {
+            let [a, b, c] = self.triangles[triangle];
+
+            ray.triangle_intersection(a, b, c)
+        })?;
+
+        Some(TriangleHit {
+            triangle,
+            distance,
+            point: ray.at(distance),
+        })
+    }
+
+    /// First triangle a world space ray hits on the model drawn at
+    /// `instance`, with the distance and point in world space
+    pub fn raycast_instance(
+        &self,
+        ray: &Ray,
+        instance: &Instance,
+        max_distance: f32,
+    ) -> Option<TriangleHit> {
+        let inverse = instance.rotation().conjugate();
+        let scale = instance.scale();
+        let origin = inverse
+            .rotate_vector(ray.origin - instance.position())
+            .div_element_wise(scale);
+        let direction = inverse.rotate_vector(ray.direction).div_element_wise(scale);
+
+        // Distances in model space are stretched by the scale
+        let stretch = direction.magnitude();
+        let hit = self.raycast(&Ray::new(origin, direction), max_distance * stretch)?;
+        let distance = hit.distance / stretch;
+
+        Some(TriangleHit {
+            triangle: hit.triangle,
+            distance,
+            point: ray.at(distance),
+        })
+    }
+}
@@ -1,6 +1,9 @@
+//! GPU textures created from images, plus depth textures
+
 use anyhow::*;
 use image::GenericImageView;
 
+/// A texture along with the view and sampler used to bind it
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
@@ -10,6 +13,7 @@ pub struct Texture {
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
+    /// Decodes an encoded image (png, jpeg) and uploads it
     pub fn from_bytes(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
@@ -94,14 +98,16 @@ impl Texture {
         })
     }
 
+    /// Creates a depth texture to pair with a render target of the given size
     pub fn create_depth_texture(
         device: &wgpu::Device,
-        config: &wgpu::SurfaceConfiguration,
+        width: u32,
+        height: u32,
         label: &str,
     ) -> Self {
         let size = wgpu::Extent3d {
-            width: config.width,
-            height: config.height,
+            width,
+            height,
             depth_or_array_layers: 1,
         };
 
@@ -0,0 +1,109 @@
+// Bounds for the time scale so fast-forward can't starve rendering
+const TIME_SCALE_MIN: f32 = 1.0 / 64.0;
+const TIME_SCALE_MAX: f32 = 64.0;
+
+/// Simulation time kept separately from wall time, so the simulation can be
+/// paused, stepped a tick at a time and slowed down or sped up
+#[derive(Clone, Copy, Debug)]
+pub struct SimulationClock {
+    paused: bool,
+    time_scale: f32,
+    pending_steps: u32,
+    ticks: u64,
+    simulation_time: f64,
+    wall_time: f64,
+}
+
+impl SimulationClock {
+    pub fn new() -> Self {
+        Self {
+            paused: false,
+            time_scale: 1.0,
+            pending_steps: 0,
+            ticks: 0,
+            simulation_time: 0.0,
+            wall_time: 0.0,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Resumes and drops any steps that haven't run yet
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.pending_steps = 0;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        if self.paused {
+            self.resume();
+        } else {
+            self.pause();
+        }
+    }
+
+    /// Pauses and queues `ticks` fixed steps to run over the next frame
+    pub fn step(&mut self, ticks: u32) {
+        self.pause();
+        self.pending_steps = self.pending_steps.saturating_add(ticks);
+    }
+
+    /// Multiplier applied to wall time, below 1 for slow motion and above 1
+    /// to fast-forward. Deterministic runs ignore it.
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// Clamped to between 1/64 and 64
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale.clamp(TIME_SCALE_MIN, TIME_SCALE_MAX);
+    }
+
+    /// Number of fixed steps run so far
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Seconds the simulation has advanced through
+    pub fn simulation_time(&self) -> f64 {
+        self.simulation_time
+    }
+
+    /// Seconds of real time since the clock started, including while paused
+    pub fn wall_time(&self) -> f64 {
+        self.wall_time
+    }
+
+    // Converts a frame's wall time into simulation time
+    pub(crate) fn scale(&mut self, dt: f32) -> f32 {
+        self.wall_time += dt as f64;
+
+        if self.paused {
+            0.0
+        } else {
+            dt * self.time_scale
+        }
+    }
+
+    // Single steps requested since the last frame
+    pub(crate) fn take_pending_steps(&mut self) -> u32 {
+        std::mem::take(&mut self.pending_steps)
+    }
+
+    pub(crate) fn tick(&mut self, step: f32) {
+        self.ticks += 1;
+        self.simulation_time += step as f64;
+    }
+}
+
+impl Default for SimulationClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,9 +1,19 @@
+//! Wall clock timing that works on both native and wasm32 targets, the
+//! fixed timestep simulations advance by and the clock that scales it
+
 #[cfg(target_arch = "wasm32")]
 use js_sys::Date;
 
 #[cfg(not(target_arch = "wasm32"))]
 use std::time::Instant;
 
+mod clock;
+mod step;
+
+pub use clock::SimulationClock;
+pub use step::FixedTimestep;
+
+/// A point in wall time. An empty timestamp reports zero elapsed time.
 #[derive(Clone, Copy, Debug)]
 pub struct Timestamp {
     #[cfg(target_arch = "wasm32")]
@@ -32,6 +42,7 @@ impl Timestamp {
         }
     }
 
+    /// Seconds since the timestamp was taken
     #[cfg(target_arch = "wasm32")]
     pub fn elapsed(&self) -> f32 {
         if self.is_empty {
@@ -62,6 +73,7 @@ impl Timestamp {
         }
     }
 
+    /// Difference in seconds between the two timestamps' elapsed times
     pub fn delta(&self, other: &Self) -> f32 {
         if self.is_empty || other.is_empty {
             return 0.0;
@@ -0,0 +1,90 @@
+/// Accumulates frame time and converts it into a whole number of fixed
+/// steps, leaving the remainder to blend the last two states by
+#[derive(Clone, Copy, Debug)]
+pub struct FixedTimestep {
+    step: f32,
+    max_steps_per_frame: u32,
+    accumulator: f32,
+}
+
+impl FixedTimestep {
+    /// Steps `rate` times per second, running at most `max_steps_per_frame`
+    /// steps per frame so slow frames can't snowball
+    pub fn new(rate: f32, max_steps_per_frame: u32) -> Self {
+        assert!(
+            rate > 0.0 && rate.is_finite(),
+            "Fixed rate must be positive"
+        );
+        assert!(max_steps_per_frame > 0, "Need at least one step per frame");
+
+        Self {
+            step: 1.0 / rate,
+            max_steps_per_frame,
+            accumulator: 0.0,
+        }
+    }
+
+    /// Seconds each step advances the simulation by
+    pub fn step(&self) -> f32 {
+        self.step
+    }
+
+    pub fn set_rate(&mut self, rate: f32) {
+        assert!(
+            rate > 0.0 && rate.is_finite(),
+            "Fixed rate must be positive"
+        );
+
+        self.step = 1.0 / rate;
+    }
+
+    /// Adds the frame time and returns how many steps are due
+    pub fn accumulate(&mut self, dt: f32) -> u32 {
+        self.accumulator += dt.max(0.0);
+
+        let steps = (self.accumulator / self.step).floor() as u32;
+
+        if steps > self.max_steps_per_frame {
+            // Drop the time we can't catch up on
+            self.accumulator %= self.step;
+
+            self.max_steps_per_frame
+        } else {
+            self.accumulator -= steps as f32 * self.step;
+
+            steps
+        }
+    }
+
+    /// How far between the previous and current step the frame lies, in [0, 1)
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.step).clamp(0.0, 1.0)
+    }
+
+    pub fn reset(&mut self) {
+        self.accumulator = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "Fixed rate must be positive")]
+    fn zero_rate_panics() {
+        FixedTimestep::new(0.0, 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "Fixed rate must be positive")]
+    fn negative_rate_panics() {
+        FixedTimestep::new(60.0, 4).set_rate(-1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Need at least one step per frame")]
+    fn zero_steps_per_frame_panics() {
+        FixedTimestep::new(60.0, 0);
+    }
+}
@@ -1,5 +1,6 @@
 use cgmath::Vector3;
 
+/// World axis the camera treats as up
 pub enum Axis {
     X,
     Y,
@@ -2,7 +2,6 @@ use cgmath::prelude::*;
 use winit::event::*;
 
 use crate::window::camera::Camera;
-use crate::window::frame::Frame;
 
 const CAMERA_SPEED_UNITS_PER_SECOND_DEFAULT: f32 = 30.0;
 
@@ -62,12 +61,12 @@ impl CameraController {
         }
     }
 
-    pub fn update_camera(&self, camera: &mut Camera, previous_frame: &Frame) {
+    pub fn update_camera(&self, camera: &mut Camera, dt: f32) {
         let forward = camera.target - camera.eye;
         let forward_normalized = forward.normalize();
         let forward_magnitude = forward.magnitude();
 
-        let speed = self.speed * previous_frame.delta_time();
+        let speed = self.speed * dt;
 
         // Prevents glitching when camera gets too close to the
         // center of the scene.
@@ -1,8 +1,8 @@
 use cgmath::{perspective, Deg, Matrix4, Point3, Vector3};
 
 pub mod axis;
-pub mod controller;
-pub mod uniform;
+pub(crate) mod controller;
+pub(crate) mod uniform;
 
 pub use axis::Axis;
 
@@ -27,6 +27,7 @@ pub const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
     0.0, 0.0, 0.5, 1.0,
 );
 
+/// Perspective camera looking from `eye` at `target`
 pub struct Camera {
     eye: Point3<f32>,
     target: Point3<f32>,
@@ -58,9 +59,33 @@ impl Camera {
         self.up = up_axis.to_vector3();
     }
 
+    /// Moves the eye, keeping the target fixed
     pub fn translate(&mut self, translation: Vector3<f32>) {
         self.eye += translation;
     }
+
+    pub fn eye(&self) -> Point3<f32> {
+        self.eye
+    }
+
+    pub fn target(&self) -> Point3<f32> {
+        self.target
+    }
+
+    pub fn look_at(&mut self, eye: Point3<f32>, target: Point3<f32>) {
+        self.eye = eye;
+        self.target = target;
+    }
+
+    /// Vertical field of view in degrees
+    pub fn set_fov_y(&mut self, fov_y: f32) {
+        self.fov_y = fov_y;
+    }
+
+    pub fn set_clip_planes(&mut self, z_near: f32, z_far: f32) {
+        self.z_near = z_near;
+        self.z_far = z_far;
+    }
 }
 
 impl Default for Camera {
@@ -1,14 +1,24 @@
-use std::collections::VecDeque;
-
-use cgmath::prelude::*;
-use cgmath::{Deg, Quaternion, Vector3};
-
-use winit::{event::*, window::Window};
-
-use bytemuck;
-use cgmath;
-use wgpu::{util::DeviceExt, CompositeAlphaMode};
-
+//! Rendering state shared by windowed and headless rendering, see [`Context`]
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::Hasher;
+use std::ops::Range;
+
+use winit::event::*;
+
+use wgpu::util::DeviceExt;
+
+use crate::config::EngineConfig;
+use crate::ecs::{self, Entity, Transform, World};
+use crate::error::EngineError;
+use crate::forces::Forces;
+use crate::hash::{StateHash, StateHasher};
+use crate::particles::{GpuEmitter, GpuParticles, GpuParticlesId, ParticleSystem};
+use crate::physics::{BodyId, PhysicsWorld, RigidBody};
+use crate::random::EngineRng;
+use crate::scene::SceneGraph;
+use crate::simulation::Simulation;
+use crate::time::{FixedTimestep, SimulationClock};
 use crate::{model, resource, texture, window};
 use window::frame::Frame;
 use window::pipeline::create_render_pipeline;
@@ -18,133 +28,122 @@ use camera::uniform::CameraUniform;
 use camera::Camera;
 use light::LightUniform;
 use model::instance::{Instance, InstanceRaw};
-use model::{DrawLight, DrawModel, Vertex};
+use model::instance_buffer::{DynamicBuffer, InstanceBuffer, InstanceId};
+use model::{DrawLight, DrawModel, ModelHandle, ModelRegistry, Vertex};
 use window::{camera, light};
 
 // Type alias for size
 type WindowSize = winit::dpi::PhysicalSize<u32>;
 
-const NUM_INSTANCES_PER_ROW: u32 = 10;
-const SPACE_BETWEEN_MODELS: f32 = 3.0;
-
-const MODEL_SHADER_STR: &'static str = include_str!("../shaders/shader.wgsl");
-const LIGHT_SHADER_STR: &'static str = include_str!("../shaders/light.wgsl");
-
-const BACKGROUND_COLOR: wgpu::Color = wgpu::Color {
-    r: 0.1,
-    g: 0.2,
-    b: 0.3,
-    a: 1.0,
-};
+const MODEL_SHADER_STR: &str = include_str!("../shaders/shader.wgsl");
+const LIGHT_SHADER_STR: &str = include_str!("../shaders/light.wgsl");
 
 // Frame rate config
-const FRAME_BUFFER_LENGTH: usize = 128;
 const FRAME_RATE_BUFFER_LENGTH: usize = 128;
 
-const PRESENT_MODE_DEFAULT: wgpu::PresentMode = if cfg!(target_arch = "wasm32") {
-    wgpu::PresentMode::AutoVsync
-} else {
-    // wgpu::PresentMode::AutoVsync
-    wgpu::PresentMode::AutoNoVsync
-};
-
-const CAMERA_ROTATION_PER_SECOND: f32 = 30.0;
+// Instances drawn with the same model and material override
+struct InstanceBatch {
+    model: ModelHandle,
+    material: Option<ecs::Material>,
+    instances: Range<u32>,
+}
 
+/// GPU state along with the scene being rendered, independent of where the
+/// frames end up
 pub struct Context {
-    surface: wgpu::Surface,
+    engine_config: EngineConfig,
     device: wgpu::Device,
     queue: wgpu::Queue,
-    config: wgpu::SurfaceConfiguration,
+    downlevel_flags: wgpu::DownlevelFlags,
+    format: wgpu::TextureFormat,
     size: WindowSize,
-    window: Window,
     render_pipeline: wgpu::RenderPipeline,
     depth_texture: texture::Texture,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
     light_uniform: LightUniform,
+    light_previous_position: [f32; 3],
     light_buffer: wgpu::Buffer,
     light_bind_group: wgpu::BindGroup,
     light_render_pipeline: wgpu::RenderPipeline,
     camera: Camera,
     camera_uniform: CameraUniform,
     camera_buffer: wgpu::Buffer,
+    camera_bind_group_layout: wgpu::BindGroupLayout,
     camera_bind_group: wgpu::BindGroup,
     camera_controller: CameraController,
-    object_model: model::Model,
-    instances: Vec<Instance>,
-    instance_buffer: wgpu::Buffer,
+    models: ModelRegistry,
+    model_instances: Vec<InstanceBuffer>,
+    light_model: ModelHandle,
+    scene: SceneGraph,
+    scene_instance_buffer: DynamicBuffer,
+    scene_batches: Vec<InstanceBatch>,
+    world: World,
+    world_instance_buffer: DynamicBuffer,
+    world_batches: Vec<InstanceBatch>,
+    world_previous: HashMap<Entity, Transform>,
+    physics: PhysicsWorld,
+    forces: Forces,
+    particles: ParticleSystem,
+    particle_instance_buffer: DynamicBuffer,
+    particle_batches: Vec<InstanceBatch>,
+    gpu_particles: Vec<Option<GpuParticles>>,
+    fixed_timestep: FixedTimestep,
+    clock: SimulationClock,
+    rng: EngineRng,
+    interpolation_alpha: f32,
     frame_buffer: VecDeque<Frame>,
     frame_current: Frame,
     frame_rate_buffer: VecDeque<f32>,
 }
 
 impl Context {
-    pub async fn new(window: Window) -> Self {
-        let size = window.inner_size();
-
-        // Get GPU handle and allow all gpu apis
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
-            dx12_shader_compiler: Default::default(),
-        });
-
-        // Get the surface
-        let surface = unsafe { instance.create_surface(&window) }.unwrap();
-
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .unwrap();
+    // Creates a device and queue on the adapter
+    pub(crate) async fn request_device(
+        adapter: &wgpu::Adapter,
+    ) -> Result<(wgpu::Device, wgpu::Queue), EngineError> {
+        // Software and GL adapters may not reach the default limits
+        let limits = if cfg!(target_arch = "wasm32") {
+            wgpu::Limits::downlevel_webgl2_defaults()
+        } else if wgpu::Limits::default().check_limits(&adapter.limits()) {
+            wgpu::Limits::default()
+        } else {
+            wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits())
+        };
 
-        // Next create a device and queue
-        let (device, queue) = adapter
+        adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
                     features: wgpu::Features::empty(),
-                    limits: if cfg!(target_arch = "wasm32") {
-                        wgpu::Limits::downlevel_webgl2_defaults()
-                    } else {
-                        wgpu::Limits::default()
-                    },
+                    limits,
                     label: None,
                 },
                 None,
             )
             .await
-            .unwrap();
-
-        // Configure surface
-        let surface_capabilities = surface.get_capabilities(&adapter);
-
-        // Assumes sRGB surface texture
-        let surface_format = surface_capabilities
-            .formats
-            .iter()
-            .copied()
-            .filter(|format| format.describe().srgb)
-            .next()
-            .unwrap_or(wgpu::TextureFormat::Bgra8UnormSrgb);
-
-        let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            width: size.width,
-            height: size.height,
-            present_mode: PRESENT_MODE_DEFAULT,
-            alpha_mode: CompositeAlphaMode::Opaque,
-            view_formats: vec![],
-        };
+            .map_err(|source| EngineError::Device {
+                adapter: adapter.get_info().name,
+                source,
+            })
+    }
 
-        surface.configure(&device, &config);
+    // Creates the pipelines and scene for frames of the given format and size
+    pub(crate) async fn new(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        downlevel_flags: wgpu::DownlevelFlags,
+        format: wgpu::TextureFormat,
+        size: WindowSize,
+        engine_config: &EngineConfig,
+    ) -> Result<Self, EngineError> {
+        engine_config.validate()?;
 
         // Create depth texture
-        let depth_texture =
-            texture::Texture::create_depth_texture(&device, &config, "DepthTexture");
-
-        // Create diffuse texture
-        const DIFFUSE_BYTES: &[u8] = include_bytes!("../textures/happy-tree.png");
+        let depth_texture = texture::Texture::create_depth_texture(
+            &device,
+            size.width,
+            size.height,
+            "DepthTexture",
+        );
 
         // Create texture bind group
         let texture_bind_group_layout =
@@ -223,6 +222,8 @@ impl Context {
         // Camera
         let mut camera = Camera::default();
 
+        camera.set_aspect(size.width as f32, size.height as f32);
+
         // Move camera back 4 units and up 2 units
         camera.translate([0.0, 2.0, 4.0].into());
 
@@ -259,32 +260,10 @@ impl Context {
 
         let camera_controller = Default::default();
 
-        // Create instances - TEMPORARY
-        let instances = (0..NUM_INSTANCES_PER_ROW)
-            .flat_map(|z| {
-                (0..NUM_INSTANCES_PER_ROW).map(move |x| {
-                    let x = SPACE_BETWEEN_MODELS * (x as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
-                    let z = SPACE_BETWEEN_MODELS * (z as f32 - NUM_INSTANCES_PER_ROW as f32 / 2.0);
-
-                    let position = Vector3 { x, y: 0.0, z };
-
-                    let rotation = if position.is_zero() {
-                        Quaternion::from_axis_angle(Vector3::unit_z(), Deg(0.0))
-                    } else {
-                        Quaternion::from_axis_angle(position.normalize(), Deg(45.0))
-                    };
-
-                    Instance::new(position, rotation)
-                })
-            })
-            .collect::<Vec<_>>();
-
-        let instance_data = instances.iter().map(InstanceRaw::from).collect::<Vec<_>>();
-        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("InstanceBuffer"),
-            contents: bytemuck::cast_slice(&instance_data),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
+        // Instances are provided by the simulation
+        let scene_instance_buffer = DynamicBuffer::new("SceneInstanceBuffer", 1);
+        let world_instance_buffer = DynamicBuffer::new("WorldInstanceBuffer", 1);
+        let particle_instance_buffer = DynamicBuffer::new("ParticleInstanceBuffer", 1);
 
         // Create render pipeline
         let render_pipeline_layout =
@@ -298,7 +277,7 @@ impl Context {
                 push_constant_ranges: &[],
             });
 
-        let render_pipeline = {
+        let render_pipeline = Self::validate_pipeline(&device, "NormalShader", || {
             let shader = wgpu::ShaderModuleDescriptor {
                 label: Some("NormalShader"),
                 source: wgpu::ShaderSource::Wgsl(MODEL_SHADER_STR.into()),
@@ -307,7 +286,7 @@ impl Context {
             create_render_pipeline(
                 &device,
                 &render_pipeline_layout,
-                config.format,
+                format,
                 Some(texture::Texture::DEPTH_FORMAT),
                 &[
                     model::ModelVertex::get_buffer_layout(),
@@ -315,9 +294,10 @@ impl Context {
                 ],
                 shader,
             )
-        };
+        })
+        .await?;
 
-        let light_render_pipeline = {
+        let light_render_pipeline = Self::validate_pipeline(&device, "LightShader", || {
             let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("LightPipelineLayout"),
                 bind_group_layouts: &[&camera_bind_group_layout, &light_bind_group_layout],
@@ -330,42 +310,95 @@ impl Context {
             create_render_pipeline(
                 &device,
                 &layout,
-                config.format,
+                format,
                 Some(texture::Texture::DEPTH_FORMAT),
                 &[model::ModelVertex::get_buffer_layout()],
                 shader,
             )
-        };
+        })
+        .await?;
 
         let object_model =
             resource::load_model("cube.obj", &device, &queue, &texture_bind_group_layout)
                 .await
-                .unwrap();
+                .map_err(|source| EngineError::Asset {
+                    file_name: String::from("cube.obj"),
+                    source,
+                })?;
 
-        Self {
-            surface,
+        let mut context = Self {
+            engine_config: engine_config.clone(),
             device,
             queue,
-            config,
+            downlevel_flags,
+            format,
             size,
-            window,
             render_pipeline,
             depth_texture,
+            texture_bind_group_layout,
             light_uniform,
+            light_previous_position: light_uniform.position,
             light_buffer,
             light_bind_group,
             light_render_pipeline,
             camera,
             camera_uniform,
             camera_buffer,
+            camera_bind_group_layout,
             camera_bind_group,
             camera_controller,
-            object_model,
-            instances,
-            instance_buffer,
-            frame_buffer: VecDeque::with_capacity(FRAME_BUFFER_LENGTH),
+            models: ModelRegistry::new(),
+            model_instances: Vec::new(),
+            light_model: ModelHandle::default(),
+            scene: SceneGraph::new(),
+            scene_instance_buffer,
+            scene_batches: Vec::new(),
+            world: World::new(),
+            world_instance_buffer,
+            world_batches: Vec::new(),
+            world_previous: HashMap::new(),
+            physics: PhysicsWorld::new().with_config(engine_config.physics.clone()),
+            forces: Forces::new(),
+            particles: ParticleSystem::new(),
+            particle_instance_buffer,
+            particle_batches: Vec::new(),
+            gpu_particles: Vec::new(),
+            fixed_timestep: FixedTimestep::new(
+                engine_config.fixed_update_rate,
+                engine_config.max_fixed_steps_per_frame,
+            ),
+            clock: SimulationClock::new(),
+            rng: engine_config.rng(),
+            interpolation_alpha: 1.0,
+            frame_buffer: VecDeque::with_capacity(engine_config.frame_buffer_length),
             frame_current: Frame::empty(),
             frame_rate_buffer: VecDeque::with_capacity(FRAME_RATE_BUFFER_LENGTH),
+        };
+
+        // The object model takes the default handle
+        context.register_model(Some("cube.obj"), object_model);
+
+        Ok(context)
+    }
+
+    // Shader compilation and pipeline validation errors are reported through
+    // wgpu's error handler, which panics by default. Scope them so they can be
+    // returned instead.
+    async fn validate_pipeline<P>(
+        device: &wgpu::Device,
+        label: &str,
+        create: impl FnOnce() -> P,
+    ) -> Result<P, EngineError> {
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let pipeline = create();
+
+        match device.pop_error_scope().await {
+            Some(error) => Err(EngineError::Shader {
+                label: String::from(label),
+                message: error.to_string(),
+            }),
+            None => Ok(pipeline),
         }
     }
 
@@ -373,47 +406,137 @@ impl Context {
         self.size
     }
 
-    pub fn resize(&mut self, new_size: WindowSize) {
-        // 1x1 minimum size to prevent wgpu panics - needs refactoring into config
-        if new_size.width > 1 && new_size.height > 1 {
-            self.size = new_size;
-            self.config.width = new_size.width;
-            self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
-            self.camera
-                .set_aspect(self.config.width as f32, self.config.height as f32);
-            self.depth_texture =
-                texture::Texture::create_depth_texture(&self.device, &self.config, "DepthTexture");
-        }
+    /// Texture format frames are rendered in
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    // Callers are expected to skip sizes too small to render to
+    pub(crate) fn resize(&mut self, new_size: WindowSize) {
+        self.size = new_size;
+        self.camera
+            .set_aspect(new_size.width as f32, new_size.height as f32);
+        self.depth_texture = texture::Texture::create_depth_texture(
+            &self.device,
+            new_size.width,
+            new_size.height,
+            "DepthTexture",
+        );
+    }
+
+    pub(crate) fn input(&mut self, event: &WindowEvent) -> bool {
+        self.camera_controller.process_events(event)
     }
 
-    pub fn input(&mut self, event: &WindowEvent) -> bool {
-        let should_return = self.camera_controller.process_events(event);
+    // Runs the fixed steps due after dt seconds of wall time, then the per
+    // frame update. The camera keeps moving in wall time while paused.
+    pub(crate) fn advance<S: Simulation>(&mut self, simulation: &mut S, dt: f32) {
+        // Deterministic runs never see the wall clock
+        let dt = if self.engine_config.deterministic {
+            self.fixed_timestep.step()
+        } else {
+            dt
+        };
+
+        let scaled_dt = self.clock.scale(dt);
+
+        // Deterministic runs take one step a frame whatever the time scale
+        let simulation_dt = if self.engine_config.deterministic && !self.clock.is_paused() {
+            dt
+        } else {
+            scaled_dt
+        };
+        let steps = self.fixed_timestep.accumulate(simulation_dt) + self.clock.take_pending_steps();
+        let step = self.fixed_timestep.step();
 
-        if should_return {
-            return true;
+        for _ in 0..steps {
+            if self.engine_config.interpolate {
+                self.snapshot();
+            }
+
+            self.forces.apply(&mut self.world, &mut self.physics, step);
+            simulation.fixed_update(self, step);
+            ecs::apply_velocity(&mut self.world, step);
+            self.step_physics(step);
+            self.particles
+                .step(&self.forces, self.rng.stream("particles"), step);
+            self.step_gpu_particles(step);
+            self.clock.tick(step);
         }
 
-        match event {
-            WindowEvent::CursorMoved { position, .. } => {
-                // Update light color using cursor position
-                let x = (position.x / self.size.width as f64) as f32;
-                let y = (position.y / self.size.height as f64) as f32;
+        simulation.update(self, simulation_dt);
+
+        // Paused and deterministic frames show the latest step rather than a blend
+        self.interpolation_alpha = if self.engine_config.interpolate
+            && !self.engine_config.deterministic
+            && !self.clock.is_paused()
+        {
+            self.fixed_timestep.alpha()
+        } else {
+            1.0
+        };
 
-                self.light_uniform.set_color([x, y, 0.5]);
+        self.update(dt);
+    }
+
+    // Steps the bodies and writes their transforms into their instances
+    fn step_physics(&mut self, dt: f32) {
+        if self.physics.is_empty() {
+            return;
+        }
 
-                true
+        self.physics.step(dt);
+
+        for (_, body) in self.physics.iter() {
+            let Some((model, id)) = body
+                .instance()
+                .filter(|_| !body.is_static() && !body.is_sleeping())
+            else {
+                continue;
+            };
+
+            if let Some(instance) = self
+                .model_instances
+                .get_mut(model.0)
+                .and_then(|instances| instances.get_mut(id))
+            {
+                instance.set_position(body.position());
+                instance.set_rotation(body.orientation());
             }
+        }
+    }
+
+    // Records transforms before a fixed step so frames can blend towards the result
+    fn snapshot(&mut self) {
+        self.light_previous_position = self.light_uniform.position;
 
-            _ => false,
+        for instances in &mut self.model_instances {
+            instances.snapshot();
         }
+
+        self.scene.snapshot();
+        self.particles.snapshot();
+
+        self.world_previous.clear();
+        self.world_previous.extend(
+            self.world
+                .query::<Transform>()
+                .map(|(entity, transform)| (entity, *transform)),
+        );
     }
 
-    pub fn update(&mut self) {
-        let last_frame = self.frame_buffer.back().unwrap();
+    pub(crate) fn update(&mut self, dt: f32) {
+        let alpha = self.interpolation_alpha;
 
-        self.camera_controller
-            .update_camera(&mut self.camera, last_frame);
+        self.camera_controller.update_camera(&mut self.camera, dt);
 
         self.camera_uniform
             .update_view_projection_matrix(&self.camera);
@@ -425,29 +548,163 @@ impl Context {
             bytemuck::cast_slice(&[self.camera_uniform]),
         );
 
-        // Rotate light
-        let old_position: Vector3<_> = self.light_uniform.position.into();
-        let rotation_angle = Deg(CAMERA_ROTATION_PER_SECOND) * last_frame.delta_time();
+        // Update light buffer
+        let mut light_uniform = self.light_uniform;
 
-        self.light_uniform.set_position_into(
-            Quaternion::from_axis_angle(Vector3::unit_y().into(), rotation_angle) * old_position,
-        );
+        for (axis, previous) in light_uniform
+            .position
+            .iter_mut()
+            .zip(self.light_previous_position)
+        {
+            *axis = previous + (*axis - previous) * alpha;
+        }
 
-        // Update light buffer
         self.queue.write_buffer(
             &self.light_buffer,
             0,
-            bytemuck::cast_slice(&[self.light_uniform]),
+            bytemuck::cast_slice(&[light_uniform]),
+        );
+
+        // Upload instances changed since the last frame
+        for instances in &mut self.model_instances {
+            instances.upload_interpolated(&self.device, &self.queue, alpha);
+        }
+
+        // Propagate, batch and upload scene graph transforms
+        self.scene.update_world_matrices();
+
+        let mut batched_instance_data = BTreeMap::<_, Vec<InstanceRaw>>::new();
+
+        for (model, instance_data) in self.scene.instance_data(alpha) {
+            batched_instance_data
+                .entry((model, None))
+                .or_default()
+                .push(instance_data);
+        }
+
+        Self::write_batches(
+            &self.device,
+            &self.queue,
+            &mut self.scene_instance_buffer,
+            &mut self.scene_batches,
+            batched_instance_data,
+        );
+
+        // Batch and upload entity transforms
+        let mut batched_instance_data = BTreeMap::<_, Vec<InstanceRaw>>::new();
+
+        for (entity, transform) in self.world.query::<Transform>() {
+            let transform = match self.world_previous.get(&entity) {
+                Some(previous) if alpha < 1.0 => previous.interpolate(transform, alpha),
+                _ => *transform,
+            };
+            let model = self
+                .world
+                .get::<ModelHandle>(entity)
+                .copied()
+                .unwrap_or_default();
+            let material = self.world.get::<ecs::Material>(entity).copied();
+
+            batched_instance_data
+                .entry((model, material))
+                .or_default()
+                .push(InstanceRaw::from(&transform));
+        }
+
+        Self::write_batches(
+            &self.device,
+            &self.queue,
+            &mut self.world_instance_buffer,
+            &mut self.world_batches,
+            batched_instance_data,
+        );
+
+        // Batch and upload live particles
+        let mut batched_instance_data = BTreeMap::<_, Vec<InstanceRaw>>::new();
+
+        for (_, emitter) in self.particles.iter() {
+            batched_instance_data
+                .entry((emitter.model(), None))
+                .or_default()
+                .extend(emitter.instance_data(alpha));
+        }
+
+        Self::write_batches(
+            &self.device,
+            &self.queue,
+            &mut self.particle_instance_buffer,
+            &mut self.particle_batches,
+            batched_instance_data,
         );
     }
 
-    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        // Get frame
-        let output = self.surface.get_current_texture()?;
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+    // Lays the batches out contiguously in the buffer
+    fn write_batches(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        buffer: &mut DynamicBuffer,
+        batches: &mut Vec<InstanceBatch>,
+        batched_instance_data: BTreeMap<(ModelHandle, Option<ecs::Material>), Vec<InstanceRaw>>,
+    ) {
+        let mut instance_data = Vec::new();
+
+        batches.clear();
 
+        for ((model, material), batch_instance_data) in batched_instance_data {
+            let start = instance_data.len() as u32;
+
+            instance_data.extend(batch_instance_data);
+
+            batches.push(InstanceBatch {
+                model,
+                material,
+                instances: start..instance_data.len() as u32,
+            });
+        }
+
+        buffer.write(device, queue, &instance_data);
+    }
+
+    fn draw_batches<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        buffer: &'a DynamicBuffer,
+        batches: &'a [InstanceBatch],
+    ) {
+        if batches.is_empty() {
+            return;
+        }
+
+        render_pass.set_vertex_buffer(1, buffer.slice());
+
+        for batch in batches {
+            let Some(model) = self.model(batch.model) else {
+                continue;
+            };
+
+            match batch
+                .material
+                .and_then(|material| model.materials.get(material.0))
+            {
+                Some(material) => render_pass.draw_model_instanced_with_material(
+                    model,
+                    material,
+                    batch.instances.clone(),
+                    &self.camera_bind_group,
+                    &self.light_bind_group,
+                ),
+                None => render_pass.draw_model_instanced(
+                    model,
+                    batch.instances.clone(),
+                    &self.camera_bind_group,
+                    &self.light_bind_group,
+                ),
+            }
+        }
+    }
+
+    // Renders the scene into the view, which must match the context's format and size
+    pub(crate) fn render<S: Simulation>(&mut self, view: &wgpu::TextureView, simulation: &S) {
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
@@ -459,10 +716,10 @@ impl Context {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("RenderPass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
+                    view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(BACKGROUND_COLOR),
+                        load: wgpu::LoadOp::Clear(self.engine_config.background_color()),
                         store: true,
                     },
                 })],
@@ -476,38 +733,67 @@ impl Context {
                 }),
             });
 
-            // Set vertex buffer
-            render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-
             // Draw light
+            let light_model = self
+                .model(self.light_model)
+                .unwrap_or_else(|| self.object_model());
+
             render_pass.set_pipeline(&self.light_render_pipeline);
             render_pass.draw_light_model(
-                &self.object_model,
+                light_model,
                 &self.camera_bind_group,
                 &self.light_bind_group,
             );
 
-            // Draw object
+            // Draw each model's instances
             render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.draw_model_instanced(
-                &self.object_model,
-                0..self.instances.len() as u32,
-                &self.camera_bind_group,
-                &self.light_bind_group,
+
+            for ((_, model), instances) in self.models.iter().zip(&self.model_instances) {
+                if instances.uploaded_len() > 0 {
+                    render_pass.set_vertex_buffer(1, instances.slice());
+                    render_pass.draw_model_instanced(
+                        model,
+                        0..instances.uploaded_len(),
+                        &self.camera_bind_group,
+                        &self.light_bind_group,
+                    );
+                }
+            }
+
+            // Draw scene graph nodes, entities and particles
+            self.draw_batches(
+                &mut render_pass,
+                &self.scene_instance_buffer,
+                &self.scene_batches,
+            );
+            self.draw_batches(
+                &mut render_pass,
+                &self.world_instance_buffer,
+                &self.world_batches,
+            );
+            self.draw_batches(
+                &mut render_pass,
+                &self.particle_instance_buffer,
+                &self.particle_batches,
             );
+
+            for gpu_particles in self.gpu_particles.iter().flatten() {
+                gpu_particles.draw(&mut render_pass, &self.camera_bind_group);
+            }
+
+            // Draw anything the simulation adds on top
+            simulation.render(self, &mut render_pass);
         }
 
         // Submit command buffer
         self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
-
-        Ok(())
     }
 
-    pub fn window(&self) -> &Window {
-        &self.window
+    pub fn config(&self) -> &EngineConfig {
+        &self.engine_config
     }
 
+    /// Frame rate over the last `frame_buffer_length` frames
     pub fn frame_rate(&self) -> f32 {
         // Mean of frame times
         let frame_time_sum = self
@@ -518,6 +804,7 @@ impl Context {
         self.frame_buffer.len() as f32 / ((frame_time_sum * 100.0).round() / 100.0)
     }
 
+    /// Mean of the recent frame rates
     pub fn average_frame_rate(&self) -> f32 {
         // Mean of frame rate buffer
         let frame_rate_sum = self
@@ -532,17 +819,324 @@ impl Context {
         &mut self.camera
     }
 
-    pub fn init(&mut self) {
-        self.frame_current.begin();
+    pub fn camera_bind_group(&self) -> &wgpu::BindGroup {
+        &self.camera_bind_group
+    }
+
+    pub fn light(&mut self) -> &mut LightUniform {
+        &mut self.light_uniform
+    }
+
+    pub fn light_bind_group(&self) -> &wgpu::BindGroup {
+        &self.light_bind_group
+    }
+
+    /// Loads an obj model from `assets/models/` and registers it, models
+    /// already loaded return their existing handle
+    pub async fn load_model(&mut self, file_name: &str) -> Result<ModelHandle, EngineError> {
+        if let Some(handle) = self.models.find(file_name) {
+            return Ok(handle);
+        }
+
+        let model = resource::load_model(
+            file_name,
+            &self.device,
+            &self.queue,
+            &self.texture_bind_group_layout,
+        )
+        .await
+        .map_err(|source| EngineError::Asset {
+            file_name: String::from(file_name),
+            source,
+        })?;
+
+        Ok(self.register_model(Some(file_name), model))
+    }
+
+    /// Registers a model built in code, giving it its own instance batch
+    pub fn add_model(&mut self, model: model::Model) -> ModelHandle {
+        self.register_model(None, model)
+    }
+
+    fn register_model(&mut self, name: Option<&str>, model: model::Model) -> ModelHandle {
+        let handle = match name {
+            Some(name) => self.models.insert_named(name, model),
+            None => self.models.insert(model),
+        };
+
+        self.model_instances
+            .push(InstanceBuffer::new(&format!("InstanceBuffer{}", handle.0)));
+
+        handle
+    }
+
+    /// Model behind the default handle, drawn for [`Context::instances`]
+    pub fn object_model(&self) -> &model::Model {
+        self.models.get(ModelHandle::default()).unwrap()
+    }
+
+    /// Replaces the object model, after which loading `cube.obj` registers it
+    /// afresh rather than finding the replacement
+    pub fn set_object_model(&mut self, object_model: model::Model) {
+        self.models.replace(ModelHandle::default(), object_model);
+    }
+
+    pub fn model(&self, handle: ModelHandle) -> Option<&model::Model> {
+        self.models.get(handle)
+    }
+
+    pub fn models(&self) -> &ModelRegistry {
+        &self.models
+    }
+
+    /// Model drawn at the light's position, the object model by default
+    pub fn light_model(&self) -> ModelHandle {
+        self.light_model
+    }
+
+    pub fn set_light_model(&mut self, handle: ModelHandle) {
+        self.light_model = handle;
+    }
+
+    /// Instances drawn with the object model, changes are uploaded each frame
+    pub fn instances(&self) -> &InstanceBuffer {
+        &self.model_instances[0]
+    }
+
+    pub fn instances_mut(&mut self) -> &mut InstanceBuffer {
+        &mut self.model_instances[0]
+    }
+
+    /// Instances drawn with the given model
+    pub fn model_instances(&self, handle: ModelHandle) -> Option<&InstanceBuffer> {
+        self.model_instances.get(handle.0)
+    }
+
+    pub fn model_instances_mut(&mut self, handle: ModelHandle) -> Option<&mut InstanceBuffer> {
+        self.model_instances.get_mut(handle.0)
+    }
+
+    /// Replaces the object model's instances, returning their ids in order
+    pub fn set_instances(
+        &mut self,
+        instances: impl IntoIterator<Item = Instance>,
+    ) -> Vec<InstanceId> {
+        let buffer = self.instances_mut();
+
+        buffer.clear();
+
+        instances
+            .into_iter()
+            .map(|instance| buffer.insert(instance))
+            .collect()
+    }
+
+    /// Hierarchy of transformed instances drawn alongside [`Context::instances`]
+    pub fn scene(&self) -> &SceneGraph {
+        &self.scene
+    }
+
+    pub fn scene_mut(&mut self) -> &mut SceneGraph {
+        &mut self.scene
+    }
+
+    /// Entities with a [`Transform`] are drawn with their [`ModelHandle`] and
+    /// [`ecs::Material`], if they have them
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// Rigid bodies stepped after every [`Simulation::fixed_update`]
+    pub fn physics(&self) -> &PhysicsWorld {
+        &self.physics
+    }
+
+    pub fn physics_mut(&mut self) -> &mut PhysicsWorld {
+        &mut self.physics
+    }
+
+    /// Force generators applied at the start of every fixed step
+    pub fn forces(&self) -> &Forces {
+        &self.forces
+    }
+
+    pub fn forces_mut(&mut self) -> &mut Forces {
+        &mut self.forces
+    }
+
+    /// Particle emitters stepped after the physics in every fixed step
+    pub fn particles(&self) -> &ParticleSystem {
+        &self.particles
+    }
+
+    pub fn particles_mut(&mut self) -> &mut ParticleSystem {
+        &mut self.particles
+    }
+
+    /// Creates the buffers and pipelines for `emitter`'s particles, which are
+    /// then stepped after the CPU particles in every fixed step and drawn
+    /// after them each frame. Fails on adapters without compute shaders, such
+    /// as WebGL2.
+    pub async fn add_gpu_particles(
+        &mut self,
+        emitter: GpuEmitter,
+    ) -> Result<GpuParticlesId, EngineError> {
+        if !self
+            .downlevel_flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+        {
+            return Err(EngineError::Unsupported {
+                feature: String::from("compute shaders"),
+            });
+        }
+
+        let gpu_particles = Self::validate_pipeline(&self.device, "GpuParticleShader", || {
+            GpuParticles::new(
+                &self.device,
+                self.format,
+                &self.camera_bind_group_layout,
+                emitter,
+            )
+        })
+        .await?;
+
+        self.gpu_particles.push(Some(gpu_particles));
+
+        Ok(GpuParticlesId(self.gpu_particles.len() - 1))
+    }
+
+    pub fn gpu_particles(&self, id: GpuParticlesId) -> Option<&GpuParticles> {
+        self.gpu_particles.get(id.0).and_then(Option::as_ref)
+    }
+
+    pub fn gpu_particles_mut(&mut self, id: GpuParticlesId) -> Option<&mut GpuParticles> {
+        self.gpu_particles.get_mut(id.0).and_then(Option::as_mut)
+    }
+
+    /// Frees the particles' buffers, ids aren't reused
+    pub fn remove_gpu_particles(&mut self, id: GpuParticlesId) -> Option<GpuParticles> {
+        self.gpu_particles.get_mut(id.0).and_then(Option::take)
     }
 
-    pub fn finalize(&mut self) {
-        self.window.request_redraw();
+    // GPU particles draw a seed per step so deterministic runs respawn them
+    // the same way, though their state stays on the GPU and out of the hash
+    fn step_gpu_particles(&mut self, dt: f32) {
+        for gpu_particles in self.gpu_particles.iter().flatten() {
+            let seed = self.rng.stream("gpu_particles").next_u32();
+
+            gpu_particles.step(&self.device, &self.queue, dt, seed);
+        }
+    }
+
+    /// Adds the body along with an instance of `model` at its transform,
+    /// which the body then drives. Falls back to the object model for unknown
+    /// handles.
+    pub fn spawn_body(&mut self, model: ModelHandle, body: RigidBody) -> BodyId {
+        let model = if self.models.get(model).is_some() {
+            model
+        } else {
+            ModelHandle::default()
+        };
+
+        let instance = Instance::new(body.position(), body.orientation());
+        let id = self.model_instances[model.0].insert(instance);
+
+        self.physics.add(body.with_instance(model, id))
+    }
+
+    /// Accumulator driving [`Simulation::fixed_update`]
+    pub fn fixed_timestep(&self) -> &FixedTimestep {
+        &self.fixed_timestep
+    }
+
+    pub fn fixed_timestep_mut(&mut self) -> &mut FixedTimestep {
+        &mut self.fixed_timestep
+    }
+
+    /// Simulation time, pause state and time scale
+    pub fn clock(&self) -> &SimulationClock {
+        &self.clock
+    }
+
+    pub fn clock_mut(&mut self) -> &mut SimulationClock {
+        &mut self.clock
+    }
+
+    /// Time scale the simulation actually runs at, 1 when deterministic
+    pub fn time_scale(&self) -> f32 {
+        if self.engine_config.deterministic {
+            return 1.0;
+        }
+
+        self.clock.time_scale()
+    }
+
+    /// Seeded generator with a stream per system, see [`EngineConfig::seed`]
+    pub fn rng(&self) -> &EngineRng {
+        &self.rng
+    }
+
+    pub fn rng_mut(&mut self) -> &mut EngineRng {
+        &mut self.rng
+    }
+
+    /// Hash of the state the engine owns: the clock, light, instances, scene
+    /// graph, rigid bodies, particles and the transforms, velocities and
+    /// masses of entities
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = StateHasher::new();
+
+        self.hash_state(&mut hasher);
+
+        hasher.finish()
+    }
+
+    pub(crate) fn hash_state(&self, hasher: &mut StateHasher) {
+        self.clock.ticks().hash_state(hasher);
+        self.clock.simulation_time().hash_state(hasher);
+        self.light_uniform.position.hash_state(hasher);
+        self.model_instances.hash_state(hasher);
+        self.scene.hash_state(hasher);
+        self.physics.hash_state(hasher);
+        self.particles.hash_state(hasher);
+        self.rng.hash_state(hasher);
+        self.world.hash_components::<Transform>(hasher);
+        self.world.hash_components::<ecs::Velocity>(hasher);
+        self.world.hash_components::<ecs::Mass>(hasher);
+    }
+
+    /// How far the frame being drawn lies between the previous and current
+    /// fixed step, 1 when interpolation is off
+    pub fn interpolation_alpha(&self) -> f32 {
+        self.interpolation_alpha
+    }
+
+    /// Time in seconds the previous frame took, or the fixed step when
+    /// deterministic
+    pub fn delta_time(&self) -> f32 {
+        if self.engine_config.deterministic {
+            return self.fixed_timestep.step();
+        }
+
+        self.frame_buffer
+            .back()
+            .map(|frame| frame.delta_time())
+            .unwrap_or(0.0)
+    }
+
+    pub(crate) fn init(&mut self) {
+        self.frame_current.begin();
+    }
 
+    pub(crate) fn finalize(&mut self) {
         self.frame_current.end();
         self.frame_buffer.push_back(self.frame_current);
 
-        if self.frame_buffer.len() > FRAME_BUFFER_LENGTH {
+        if self.frame_buffer.len() > self.engine_config.frame_buffer_length {
             self.frame_buffer.pop_front();
         }
 
@@ -1,3 +1,6 @@
+//! Point light uniform shared by the model and light shaders
+
+/// Position and color of the scene's point light, laid out for the GPU
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct LightUniform {
@@ -19,6 +22,10 @@ impl LightUniform {
         }
     }
 
+    pub fn color(&self) -> [f32; 3] {
+        self.color
+    }
+
     pub fn set_color<T>(&mut self, color: T)
     where
         T: Into<[f32; 3]>,
@@ -1,3 +1,5 @@
+//! Window creation and the main event loop
+
 use winit::{
     event::*,
     event_loop::{ControlFlow, EventLoop},
@@ -6,41 +8,49 @@ use winit::{
 
 pub mod camera;
 pub mod context;
-pub mod frame;
+pub(crate) mod frame;
 pub mod light;
 pub mod pipeline;
+pub(crate) mod surface;
 
+use crate::config::EngineConfig;
+use crate::error::EngineError;
+use crate::simulation::Simulation;
 use camera::Axis;
 pub use context::Context;
+use surface::WindowSurface;
 
+/// Id of the DOM element the canvas is appended to on wasm32
 #[cfg(target_arch = "wasm32")]
 pub const WASM_ELEMENT_ID: &str = "wasm-canvas";
 
-// Frame rate config
-const FRAME_RATE_SHOW_DEFAULT: bool = true;
-
-// Initial window config
-const INITIAL_WIDTH: u32 = 450;
-const INITIAL_HEIGHT: u32 = 400;
-const INITIAL_TITLE: &str = "Simulation Engine";
-const INITIAL_RESIZABLE: bool = true;
-
-// Wrapper for the winit window to handle wasm32 specific stuff
+/// Wrapper for the winit window to handle wasm32 specific stuff
 pub struct Window {
+    config: EngineConfig,
     show_frame_rate: bool,
 }
 
 impl Window {
-    pub fn new() -> Self {
+    pub fn new(config: EngineConfig) -> Self {
         Self {
-            show_frame_rate: FRAME_RATE_SHOW_DEFAULT,
+            show_frame_rate: config.show_frame_rate,
+            config,
         }
     }
 
-    pub async fn run(mut self) {
+    /// Opens the window and runs `simulation` in the event loop until the
+    /// window is closed or escape is pressed. Only returns if initialisation
+    /// fails.
+    ///
+    /// Space pauses the simulation clock, period steps it one tick, minus and
+    /// equals halve and double the time scale and 0 resets it.
+    pub async fn run<S: Simulation + 'static>(
+        mut self,
+        mut simulation: S,
+    ) -> Result<(), EngineError> {
         // Set up the window and event loop
         let event_loop = EventLoop::new();
-        let winit_window = WindowBuilder::new().build(&event_loop).unwrap();
+        let winit_window = WindowBuilder::new().build(&event_loop)?;
 
         // Append canvas to dom if wasm32
         #[cfg(target_arch = "wasm32")]
@@ -60,14 +70,18 @@ impl Window {
                 .expect("Couldn't append canvas to document body");
         }
 
-        winit_window.set_resizable(INITIAL_RESIZABLE);
-        winit_window.set_inner_size(winit::dpi::LogicalSize::new(INITIAL_WIDTH, INITIAL_HEIGHT));
-        Self::set_title(&winit_window, INITIAL_TITLE);
+        winit_window.set_resizable(self.config.resizable);
+        winit_window.set_inner_size(winit::dpi::LogicalSize::new(
+            self.config.width,
+            self.config.height,
+        ));
+        Self::set_title(&winit_window, &self.config.title);
 
-        // Create the context
-        let mut context = Context::new(winit_window).await;
+        // Create the surface and context
+        let (mut surface, mut context) = WindowSurface::new(winit_window, &self.config).await?;
 
         context.init();
+        simulation.init(&mut context);
 
         log::info!("Starting mainloop");
 
@@ -77,73 +91,97 @@ impl Window {
             Event::WindowEvent {
                 ref event,
                 window_id,
-            } if window_id == context.window().id() => {
-                if !context.input(event) {
-                    match event {
-                        // Close or escape key pressed
-                        WindowEvent::CloseRequested
-                        | WindowEvent::KeyboardInput {
-                            input:
-                                KeyboardInput {
-                                    state: ElementState::Pressed,
-                                    virtual_keycode: Some(VirtualKeyCode::Escape),
-                                    ..
-                                },
-                            ..
-                        } => *control_flow = ControlFlow::Exit,
-
-                        // Keyboard input
-                        WindowEvent::KeyboardInput {
-                            input:
-                                KeyboardInput {
-                                    state: ElementState::Pressed,
-                                    virtual_keycode: Some(keycode),
-                                    ..
-                                },
-                            ..
-                        } => {
-                            match keycode {
-                                // Toggle frame rate
-                                VirtualKeyCode::F => {
-                                    self.show_frame_rate = !self.show_frame_rate;
-                                }
-                                VirtualKeyCode::Up => {
-                                    context.camera().set_up_axis(Axis::Y);
-                                }
-                                VirtualKeyCode::Left => {
-                                    context.camera().set_up_axis(Axis::X);
-                                }
-                                VirtualKeyCode::Right => {
-                                    context.camera().set_up_axis(Axis::Z);
-                                }
-                                _ => {}
+            } if window_id == surface.window().id()
+                && !simulation.input(&mut context, event)
+                && !context.input(event) =>
+            {
+                match event {
+                    // Close or escape key pressed
+                    WindowEvent::CloseRequested
+                    | WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(VirtualKeyCode::Escape),
+                                ..
+                            },
+                        ..
+                    } => *control_flow = ControlFlow::Exit,
+
+                    // Keyboard input
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                state: ElementState::Pressed,
+                                virtual_keycode: Some(keycode),
+                                ..
+                            },
+                        ..
+                    } => {
+                        match keycode {
+                            // Toggle frame rate
+                            VirtualKeyCode::F => {
+                                self.show_frame_rate = !self.show_frame_rate;
+                            }
+                            // Pause, single step and time scale
+                            VirtualKeyCode::Space => {
+                                context.clock_mut().toggle_pause();
+                            }
+                            VirtualKeyCode::Period => {
+                                context.clock_mut().step(1);
+                            }
+                            VirtualKeyCode::Minus => {
+                                let clock = context.clock_mut();
+                                clock.set_time_scale(clock.time_scale() / 2.0);
                             }
+                            VirtualKeyCode::Equals => {
+                                let clock = context.clock_mut();
+                                clock.set_time_scale(clock.time_scale() * 2.0);
+                            }
+                            VirtualKeyCode::Key0 => {
+                                context.clock_mut().set_time_scale(1.0);
+                            }
+                            VirtualKeyCode::Up => {
+                                context.camera().set_up_axis(Axis::Y);
+                            }
+                            VirtualKeyCode::Left => {
+                                context.camera().set_up_axis(Axis::X);
+                            }
+                            VirtualKeyCode::Right => {
+                                context.camera().set_up_axis(Axis::Z);
+                            }
+                            _ => {}
                         }
+                    }
 
-                        // Resize
-                        WindowEvent::Resized(physical_size) => {
-                            context.resize(*physical_size);
-                        }
-                        WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                            context.resize(**new_inner_size);
-                        }
+                    // Resize
+                    WindowEvent::Resized(physical_size) => {
+                        surface.resize(&mut context, *physical_size);
+                    }
+                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                        surface.resize(&mut context, **new_inner_size);
+                    }
 
-                        _ => {
-                            // log::info!("Unhandled event: {:?}", event);
-                        }
+                    _ => {
+                        // log::info!("Unhandled event: {:?}", event);
                     }
                 }
             }
 
             // Render
-            Event::RedrawRequested(window_id) if window_id == context.window().id() => {
-                context.update();
+            Event::RedrawRequested(window_id) if window_id == surface.window().id() => {
+                let dt = context.delta_time();
+
+                context.advance(&mut simulation, dt);
 
-                match context.render() {
+                match surface.render(&mut context, &simulation) {
                     Ok(_) => {}
 
                     // Surface lost
-                    Err(wgpu::SurfaceError::Lost) => context.resize(context.size()),
+                    Err(wgpu::SurfaceError::Lost) => {
+                        let size = surface.size();
+                        surface.resize(&mut context, size);
+                    }
 
                     // Out of memory
                     Err(wgpu::SurfaceError::OutOfMemory) => *control_flow = ControlFlow::Exit,
@@ -153,6 +191,7 @@ impl Window {
                 }
             }
             Event::MainEventsCleared => {
+                surface.window().request_redraw();
                 context.finalize();
 
                 // let frame_rate = context.frame_rate();
@@ -161,27 +200,32 @@ impl Window {
                 // log::info!("FPS: {:.0}", frame_rate);
 
                 // Update window title
-                self.update_title(&context);
+                self.update_title(surface.window(), &context);
             }
 
             _ => {}
         });
     }
 
-    fn update_title(&mut self, context: &Context) {
+    fn update_title(&mut self, winit_window: &WinitWindow, context: &Context) {
+        let mut title = self.config.title.clone();
+
         if self.show_frame_rate {
-            Self::set_title(
-                context.window(),
-                &format!(
-                    "{} - {:.2} FPS - {:.2} AVG FPS",
-                    INITIAL_TITLE,
-                    context.frame_rate(),
-                    context.average_frame_rate()
-                ),
+            title += &format!(
+                " - {:.2} FPS - {:.2} AVG FPS",
+                context.frame_rate(),
+                context.average_frame_rate()
             );
-        } else {
-            Self::set_title(context.window(), INITIAL_TITLE);
         }
+
+        // Clock state
+        if context.clock().is_paused() {
+            title += " - Paused";
+        } else if context.time_scale() != 1.0 {
+            title += &format!(" - {}x", context.time_scale());
+        }
+
+        Self::set_title(winit_window, &title);
     }
 
     fn set_title(winit_window: &WinitWindow, title: &str) {
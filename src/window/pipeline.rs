@@ -1,6 +1,7 @@
 #[cfg(target_arch = "wasm32")]
 pub use wasm_bindgen::prelude::*;
 
+/// Creates a render pipeline for a shader with `vs_main` and `fs_main` entry points
 pub fn create_render_pipeline(
     device: &wgpu::Device,
     layout: &wgpu::PipelineLayout,
@@ -58,3 +59,19 @@ pub fn create_render_pipeline(
         multiview: None,
     })
 }
+
+/// Creates a compute pipeline for a shader with a `cs_main` entry point
+pub fn create_compute_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: wgpu::ShaderModuleDescriptor,
+) -> wgpu::ComputePipeline {
+    let shader = device.create_shader_module(shader);
+
+    device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("ComputePipeline"),
+        layout: Some(layout),
+        module: &shader,
+        entry_point: "cs_main",
+    })
+}
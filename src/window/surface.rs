@@ -0,0 +1,123 @@
+use wgpu::CompositeAlphaMode;
+use winit::window::Window as WinitWindow;
+
+use crate::adapter;
+use crate::config::EngineConfig;
+use crate::error::EngineError;
+use crate::simulation::Simulation;
+use crate::window::Context;
+
+// Type alias for size
+type WindowSize = winit::dpi::PhysicalSize<u32>;
+
+// The winit window and the swapchain frames are presented to
+pub(crate) struct WindowSurface {
+    window: WinitWindow,
+    surface: wgpu::Surface,
+    config: wgpu::SurfaceConfiguration,
+}
+
+impl WindowSurface {
+    // Creates the surface and a context on an adapter that can present to it
+    pub(crate) async fn new(
+        window: WinitWindow,
+        engine_config: &EngineConfig,
+    ) -> Result<(Self, Context), EngineError> {
+        let size = window.inner_size();
+
+        let adapter_config = engine_config.adapter.clone().with_env_overrides();
+
+        // Get GPU handle for the configured gpu apis
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: adapter_config.wgpu_backends(),
+            dx12_shader_compiler: Default::default(),
+        });
+
+        // Get the surface
+        let surface = unsafe { instance.create_surface(&window) }?;
+
+        let adapter = adapter::request_adapter(&instance, &adapter_config, Some(&surface)).await?;
+
+        log::info!("Rendering on {:?}", adapter.get_info());
+
+        // Next create a device and queue
+        let (device, queue) = Context::request_device(&adapter).await?;
+
+        // Configure surface
+        let surface_capabilities = surface.get_capabilities(&adapter);
+
+        // Assumes sRGB surface texture
+        let surface_format = surface_capabilities
+            .formats
+            .iter()
+            .copied()
+            .find(|format| format.describe().srgb)
+            .unwrap_or(wgpu::TextureFormat::Bgra8UnormSrgb);
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: surface_format,
+            width: size.width,
+            height: size.height,
+            present_mode: engine_config.present_mode.into(),
+            alpha_mode: CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+        };
+
+        surface.configure(&device, &config);
+
+        let context = Context::new(
+            device,
+            queue,
+            adapter.get_downlevel_capabilities().flags,
+            surface_format,
+            size,
+            engine_config,
+        )
+        .await?;
+
+        Ok((
+            Self {
+                window,
+                surface,
+                config,
+            },
+            context,
+        ))
+    }
+
+    pub(crate) fn window(&self) -> &WinitWindow {
+        &self.window
+    }
+
+    pub(crate) fn size(&self) -> WindowSize {
+        WindowSize::new(self.config.width, self.config.height)
+    }
+
+    pub(crate) fn resize(&mut self, context: &mut Context, new_size: WindowSize) {
+        // 1x1 minimum size to prevent wgpu panics - needs refactoring into config
+        if new_size.width > 1 && new_size.height > 1 {
+            self.config.width = new_size.width;
+            self.config.height = new_size.height;
+            self.surface.configure(context.device(), &self.config);
+            context.resize(new_size);
+        }
+    }
+
+    pub(crate) fn render<S: Simulation>(
+        &mut self,
+        context: &mut Context,
+        simulation: &S,
+    ) -> Result<(), wgpu::SurfaceError> {
+        // Get frame
+        let output = self.surface.get_current_texture()?;
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        context.render(&view, simulation);
+        output.present();
+
+        Ok(())
+    }
+}